@@ -0,0 +1,1041 @@
+use factorio_benchmark_helper::performance_results::breakdown::BreakdownScope;
+use factorio_benchmark_helper::performance_results::report::OutputFormat;
+use factorio_benchmark_helper::performance_results::statistics::OutlierPolicy;
+use factorio_benchmark_helper::procedure_file::print_all_procedures;
+use factorio_benchmark_helper::procedure_file::ProcedureFileKind;
+use factorio_benchmark_helper::procedure_file::ProcedureOverwrite;
+use factorio_benchmark_helper::regression_tester::HeadlessCommand;
+use factorio_benchmark_helper::regression_tester::KeepUnpacked;
+use factorio_benchmark_helper::regression_tester::RegressionReportFormat;
+use factorio_benchmark_helper::regression_tester::DEFAULT_HEADLESS_KEEP;
+use factorio_benchmark_helper::util::common::FACTORIO_BENCHMARK_HELPER_NAME;
+use factorio_benchmark_helper::util::common::FACTORIO_BENCHMARK_HELPER_VERSION;
+use factorio_benchmark_helper::util::config_file::parse_timeout_multiplier;
+use factorio_benchmark_helper::util::factorio_save_directory;
+use factorio_benchmark_helper::util::parse_nice;
+use factorio_benchmark_helper::util::prompt_until_allowed_val;
+use factorio_benchmark_helper::util::mirror::set_mirror_root;
+use factorio_benchmark_helper::util::mirror::set_offline;
+use factorio_benchmark_helper::util::set_minify_saves;
+use factorio_benchmark_helper::util::CpuList;
+use factorio_benchmark_helper::util::ProcedureKind;
+use clap::ArgMatches;
+use megabase_index_incrementer::FactorioVersion;
+use clap::{App, AppSettings, Arg};
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::process::exit;
+#[derive(Debug, Default)]
+pub struct UserArgs {
+    pub interactive: bool,
+    pub overwrite: ProcedureOverwrite,
+
+    pub regression_test: bool,
+    pub regression_test_clean: bool,
+    pub regression_test_path: Option<PathBuf>,
+    pub regression_report: Option<RegressionReportFormat>,
+    /// The map, and the good and bad versions to bisect between.
+    pub regression_bisect: Option<(String, FactorioVersion, FactorioVersion)>,
+    pub headless: Option<HeadlessCommand>,
+    /// The number of newest headless versions kept by --headless prune, and
+    /// downloaded by --regression-test. None if --keep was not given.
+    pub headless_keep: Option<usize>,
+    /// In percent, such as 5 for a change of more than ±5%.
+    pub regression_threshold: Option<f64>,
+
+    pub run_benchmark: bool,
+    pub create_benchmark: bool,
+    pub resume: bool,
+    pub output_format: OutputFormat,
+    pub output: Option<PathBuf>,
+    pub per_tick: bool,
+    pub interleave: bool,
+    pub dry_run: bool,
+    pub skip_error_check: bool,
+    pub timeout_multiplier: Option<f64>,
+    pub keep_going: bool,
+    /// In percent, such as 0.5 for ±0.5%.
+    pub target_precision: Option<f64>,
+    pub max_runs: Option<u32>,
+    pub max_map_time: Option<f64>,
+    pub factorio_versions: Option<Vec<String>>,
+    pub cpu_affinity: Option<CpuList>,
+    pub nice: Option<i32>,
+
+    pub benchmark_set_name: Option<String>,
+    pub folder: Option<PathBuf>,
+    pub ticks: Option<u32>,
+    pub runs: Option<u32>,
+    pub warmup_ticks: Option<u32>,
+    pub outlier_policy: Option<OutlierPolicy>,
+    pub mods_dirty: Option<String>,
+
+    pub run_meta: bool,
+    pub create_meta: bool,
+    pub meta_set_name: Option<String>,
+    pub meta_set_members: Option<String>,
+
+    pub commit_flag: bool,
+    pub commit_name: Option<String>,
+    pub commit_type: Option<ProcedureKind>,
+    pub commit_recursive: bool,
+
+    pub compare_collections: Option<(u32, u32)>,
+    pub breakdown: Option<BreakdownScope>,
+
+    pub export_bundle: Option<(String, PathBuf)>,
+    pub import_bundle: Option<PathBuf>,
+
+    pub validate: Option<Vec<ProcedureFileKind>>,
+}
+
+pub fn add_options_and_parse() -> UserArgs {
+    let matches = App::new(FACTORIO_BENCHMARK_HELPER_NAME)
+        .version(FACTORIO_BENCHMARK_HELPER_VERSION)
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .setting(AppSettings::DeriveDisplayOrder)
+        .setting(AppSettings::StrictUtf8)
+        .version_short("v")
+        .arg(
+            Arg::with_name("regression-test")
+                .long("regression-test")
+                .help("Performs a regression test of all new FactorioVersion \
+                    x Map combinations. Specify clean to run all regardless of \
+                    if they have been previous ran.")
+                .takes_value(true)
+                .value_name("clean|$PATH_TO_FILE_TO_REGRESSION_TEST")
+                .min_values(0)
+        )
+        .arg(
+            Arg::with_name("regression-report")
+                .long("regression-report")
+                .help("Reports the mean wholeUpdate and execution time of \
+                    every regression tested map across Factorio versions, \
+                    flagging versions which changed by more than \
+                    --regression-threshold, as markdown (the default), html \
+                    or csv. Written to stdout, or the --output PATH.")
+                .takes_value(true)
+                .value_name("markdown|html|csv")
+                .min_values(0)
+                .max_values(1)
+                .conflicts_with("regression-test")
+        )
+        .arg(
+            Arg::with_name("regression-threshold")
+                .long("regression-threshold")
+                .help("The change in PERCENT from the previous Factorio \
+                    version above which --regression-report flags a version, \
+                    or from the good version above which --regression-bisect \
+                    considers a version slow. Defaults to 5.")
+                .value_name("PERCENT")
+        )
+        .arg(
+            Arg::with_name("regression-bisect")
+                .long("regression-bisect")
+                .help("Benchmarks MAP, a path or the name of a map in the \
+                    regression test folder, in the downloaded headless \
+                    Factorio versions between GOOD_VERSION and BAD_VERSION, \
+                    binary searching for the first version more than \
+                    --regression-threshold slower than GOOD_VERSION. Uses \
+                    --ticks and --runs if given.")
+                .conflicts_with_all(&["regression-test", "regression-report"])
+                .value_names(&["MAP", "GOOD_VERSION", "BAD_VERSION"])
+        )
+        .arg(
+            Arg::with_name("headless")
+                .long("headless")
+                .help("Manages the headless Factorio versions used by \
+                    regression tests. list shows the disk usage of every \
+                    downloaded and unpacked version, fetch VERSION downloads \
+                    a version, unpack VERSION unpacks a downloaded version, \
+                    and prune removes all but the newest --keep versions.")
+                .conflicts_with_all(&[
+                    "regression-test",
+                    "regression-report",
+                    "regression-bisect"
+                ])
+                .takes_value(true)
+                .value_name("list|fetch VERSION|unpack VERSION|prune")
+                .min_values(1)
+                .max_values(2)
+        )
+        .arg(
+            Arg::with_name("keep")
+                .long("keep")
+                .help("The number of newest versions --headless prune keeps, \
+                    removing their archives and unpacked installs, defaulting \
+                    to 3, and the number of newest versions --regression-test \
+                    downloads, defaulting to every version its maps load in.")
+                .value_name("N")
+        )
+        .arg(
+            Arg::with_name("keep-unpacked")
+                .long("keep-unpacked")
+                .help("How many of the versions kept by --headless prune stay \
+                    unpacked, newest first: all (the default), none or a \
+                    number. Versions without a downloaded archive always stay \
+                    unpacked.")
+                .requires("headless")
+                .value_name("all|none|N")
+        )
+        .arg(
+            Arg::with_name("list")
+                .long("list")
+                .help("List available benchmark/meta sets")
+        )
+        .arg(
+            Arg::with_name("interactive")
+                .short("-i")
+                .long("interactive")
+                .help("Run program interactively, prompting for actions")
+        )
+        .arg(
+            Arg::with_name("overwrite")
+                .long("overwrite")
+                .help("If a benchmark/meta set already exists with NAME, overwrite")
+        )
+        .arg(
+            Arg::with_name("recursive")
+                .long("recursive")
+                .short("r")
+                .help("When committing a meta set, also recursively commit \
+                    every meta/benchmark set contained within that set.")
+        )
+        .args(&[
+            Arg::with_name("benchmark")
+                .long("benchmark")
+                .help("Run a benchmark of a named benchmark set")
+                .value_name("NAME"),
+            Arg::with_name("meta")
+                .long("meta")
+                .help("Runs benchmarks of all benchmark/meta sets found \
+                    recusively within this meta set.")
+                .value_name("NAME"),
+            Arg::with_name("resume")
+                .long("resume")
+                .help("When running a benchmark or meta set, continue the \
+                    unfinished collection of each set left by an interrupted \
+                    run, skipping maps which already have results."),
+            Arg::with_name("output-format")
+                .long("output-format")
+                .help("How to report the results of a benchmark or meta run. \
+                    csv writes the per tick wholeUpdate of every map, json \
+                    writes a document of every collection with summary stats \
+                    per map, and table prints a summary per map. Defaults to \
+                    csv.")
+                .possible_values(&["json", "csv", "table"])
+                .value_name("FORMAT"),
+            Arg::with_name("output")
+                .long("output")
+                .help("Write the results report to PATH instead of stdout, or \
+                    data.csv for csv reports.")
+                .value_name("PATH"),
+            Arg::with_name("per-tick")
+                .long("per-tick")
+                .help("Include the timings of every tick of every run in json \
+                    reports."),
+            Arg::with_name("offline")
+                .long("offline")
+                .help("Never download from the internet. Maps, mods, \
+                    master.json and the megabase list are only taken from the \
+                    cache and the mirror."),
+            Arg::with_name("mirror")
+                .long("mirror")
+                .help("Consult ROOT, a directory or http(s) URL, before \
+                    downloading from the internet. Overrides mirror-root in \
+                    config.ini.")
+                .value_name("ROOT"),
+            Arg::with_name("interleave")
+                .long("interleave")
+                .help("Runs the maps of each set round robin, one run of every \
+                    map at a time, instead of all runs of a map back to back. \
+                    Spreads thermal throttling and background load evenly \
+                    across maps, at the cost of loading each map once per run."),
+            Arg::with_name("skip-error-check")
+                .long("skip-error-check")
+                .help("When running a benchmark or meta set, skip the short \
+                    error checking run of maps which already ran without \
+                    errors on this cpu and Factorio version."),
+            Arg::with_name("timeout-multiplier")
+                .long("timeout-multiplier")
+                .help("Kills Factorio when a benchmark takes X times longer \
+                    than expected from earlier runs of the map, records the \
+                    map as failed and continues with the next one. 0 never \
+                    times out. Overrides timeout-multiplier in config.ini.")
+                .value_name("X"),
+            Arg::with_name("keep-going")
+                .long("keep-going")
+                .help("When running a benchmark or meta set, log a map or set \
+                    which fails along with the error Factorio reported, skip \
+                    it and continue with the rest. A summary of failures is \
+                    printed at the end."),
+            Arg::with_name("target-precision")
+                .long("target-precision")
+                .help("When running a benchmark or meta set, keep adding runs \
+                    to each map until the 95% confidence interval of its mean \
+                    wholeUpdate is within PERCENT of the mean, such as 0.5 \
+                    for ±0.5%. The runs of the set are ran first.")
+                .value_name("PERCENT"),
+            Arg::with_name("max-runs")
+                .long("max-runs")
+                .help("The most runs of a map with --target-precision. \
+                    Defaults to 30.")
+                .requires("target-precision")
+                .value_name("RUNS"),
+            Arg::with_name("max-map-time")
+                .long("max-map-time")
+                .help("Stop adding runs to a map with --target-precision \
+                    once it has been benchmarked for SECONDS.")
+                .requires("target-precision")
+                .value_name("SECONDS"),
+            Arg::with_name("factorio-versions")
+                .long("factorio-versions")
+                .help("When running a benchmark or meta set, run it with each \
+                    of the comma separated Factorio INSTALLS instead of the \
+                    one in config.ini, storing a collection for each, and \
+                    compare them against the first. An install is the \
+                    version of a headless Factorio unpacked by the regression \
+                    tester, a Factorio directory, or an executable.")
+                .use_delimiter(true)
+                .min_values(2)
+                .value_name("INSTALLS"),
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("When running a benchmark or meta set, only print the \
+                    cache status of every map and mod, the Factorio commands \
+                    that would be ran, and the total ticks, without launching \
+                    Factorio or storing results. With --headless prune, only \
+                    print what would be removed."),
+            Arg::with_name("cpu-affinity")
+                .long("cpu-affinity")
+                .help("Pins Factorio to the CPUs in CPUS while benchmarking, \
+                    given as a list such as 0-3,6. Overrides cpu-affinity in \
+                    config.ini. Linux only.")
+                .value_name("CPUS"),
+            Arg::with_name("nice")
+                .long("nice")
+                .help("Runs Factorio with the nice value N, from -20 (highest \
+                    priority) to 19. Overrides nice in config.ini. Negative \
+                    values need root or CAP_SYS_NICE. Linux only.")
+                .allow_hyphen_values(true)
+                .value_name("N"),
+            Arg::with_name("create-benchmark")
+                .long("create-benchmark")
+                .help("Creates a new benchmark, using NAME")
+                .requires("folder")
+                .value_name("NAME"),
+            Arg::with_name("folder")
+                .long("folder")
+                .help("Restrict maps to those matching contained within FOLDER. \
+                    This folder can be an absolute path, a relative path from \
+                    the Factorio saves directory, or a relative path from the \
+                    current directory. Priority is given in that order.")
+                .min_values(1)
+                .value_name("FOLDER"),
+            Arg::with_name("ticks")
+                .long("ticks")
+                .help("The number of ticks each map should be benchmarked for \
+                    per run. Overrides regression-ticks in config.ini for a \
+                    --regression-test.")
+                .value_name("TICKS"),
+            Arg::with_name("runs")
+                .long("runs")
+                .help("How many times each map should be benchmarked. \
+                    Overrides regression-runs in config.ini for a \
+                    --regression-test.")
+                .value_name("RUNS"),
+            Arg::with_name("warmup-ticks")
+                .long("warmup-ticks")
+                .help("The number of ticks at the start of every run left out \
+                    of the results. These are part of --ticks.")
+                .value_name("TICKS"),
+            Arg::with_name("outlier-policy")
+                .long("outlier-policy")
+                .help("How outlier runs are left out of the results. One of \
+                    none, drop-first-run, mad:N to drop runs whose mean is \
+                    more than N median absolute deviations from the median, \
+                    or trimmed:F to drop the fraction F of the fastest and \
+                    slowest ticks.")
+                .value_name("POLICY"),
+            Arg::with_name("mods")
+                .long("mods")
+                .help("A comma separated list of mods you wish to create this benchmark with.\
+                    'region-cloner' specifies the latest version of region cloner, whereas\
+                    'region-cloner_1.1.2' specifies that specific version.")
+                .value_name("MODS..."),
+            Arg::with_name("minify")
+                .long("minify")
+                .help("If present, will attempt to slightly reduce the size of \
+                        save files by removing the preview image from the save."),
+            Arg::with_name("create-meta")
+                .long("create-meta")
+                .help("Creates a meta set with NAME, with provided MEMBERS. \
+                    MEMBERS given as a comma separated list.")
+                .value_names(&["NAME","MEMBERS..."])
+                .min_values(2),
+            Arg::with_name("commit")
+                .long("commit")
+                .help("Writes the benchmark or meta set TYPE with NAME to the \
+                    master.json file. Types are \"benchmark\", \"meta\"")
+                .conflicts_with_all(&[
+                    "benchmark",
+                    "run_meta",
+                    "create-benchmark",
+                    "pattern",
+                    "ticks",
+                    "runs",
+                    "warmup-ticks",
+                    "outlier-policy",
+                    "google-drive-folder",
+                    "create-meta",
+                    "regression-test"
+                ])
+                .value_names(&["TYPE", "NAME"]),
+            Arg::with_name("compare")
+                .long("compare")
+                .help("Compares the results of two collections by id, matching \
+                    maps by their hash, and reports the percent change in \
+                    wholeUpdate with a 95% confidence interval.")
+                .conflicts_with_all(&[
+                    "benchmark",
+                    "meta",
+                    "create-benchmark",
+                    "create-meta",
+                    "commit",
+                    "regression-test"
+                ])
+                .value_names(&["COLLECTION_A", "COLLECTION_B"]),
+            Arg::with_name("breakdown")
+                .long("breakdown")
+                .help("Shows the mean time per tick spent in each subsystem, \
+                    and its share of gameUpdate, for every benchmark of a \
+                    collection or a single benchmark. Types are \"collection\", \
+                    \"benchmark\"")
+                .conflicts_with_all(&[
+                    "benchmark",
+                    "meta",
+                    "create-benchmark",
+                    "create-meta",
+                    "commit",
+                    "regression-test",
+                    "compare"
+                ])
+                .value_names(&["TYPE", "ID"]),
+            Arg::with_name("export-bundle")
+                .long("export-bundle")
+                .help("Exports the benchmark or meta set NAME to FILE, a tar \
+                    archive with every set, map and mod it needs.")
+                .conflicts_with_all(&[
+                    "benchmark",
+                    "meta",
+                    "create-benchmark",
+                    "create-meta",
+                    "commit",
+                    "regression-test",
+                    "compare",
+                    "breakdown"
+                ])
+                .value_names(&["NAME", "FILE"]),
+            Arg::with_name("import-bundle")
+                .long("import-bundle")
+                .help("Imports a bundle made by --export-bundle, verifying the \
+                    hashes of its maps and mods, and adds its sets to \
+                    local.json.")
+                .conflicts_with_all(&[
+                    "benchmark",
+                    "meta",
+                    "create-benchmark",
+                    "create-meta",
+                    "commit",
+                    "regression-test",
+                    "compare",
+                    "breakdown",
+                    "export-bundle"
+                ])
+                .value_name("FILE"),
+            Arg::with_name("validate")
+                .long("validate")
+                .help("Checks local.json, master.json, or the procedure file \
+                    at PATH for problems such as missing meta set members, \
+                    malformed hashes or zero ticks, and exits non-zero if any \
+                    are found. Checks both local.json and master.json if no \
+                    file is given.")
+                .conflicts_with_all(&[
+                    "benchmark",
+                    "meta",
+                    "create-benchmark",
+                    "create-meta",
+                    "commit",
+                    "regression-test",
+                    "compare",
+                    "breakdown",
+                    "export-bundle",
+                    "import-bundle"
+                ])
+                .takes_value(true)
+                .value_name("local|master|PATH")
+                .min_values(0)
+                .max_values(1),
+            ])
+        .get_matches();
+    parse_matches(&matches)
+}
+
+fn parse_matches(matches: &ArgMatches) -> UserArgs {
+    let args = &matches.args;
+    let mut arguments = UserArgs::default();
+    if args.contains_key("interactive") {
+        arguments.interactive = true;
+    }
+    if args.contains_key("overwrite") {
+        arguments.overwrite = true.into();
+    }
+
+    if args.contains_key("regression-test") {
+        arguments.regression_test = true;
+        if !args["regression-test"].vals.is_empty() {
+            if args["regression-test"].vals[0].to_str().unwrap().trim() == "clean" {
+                arguments.regression_test_clean = true;
+            } else {
+                let p: PathBuf = args["regression-test"].vals[..].iter().collect();
+                if !p.exists() {
+                    eprintln!("Could not find file {:?}", p);
+                    exit(1);
+                }
+                arguments.regression_test_path = Some(p);
+                arguments.regression_test_clean = true;
+            }
+        }
+    }
+
+    if args.contains_key("regression-report") {
+        let format = match args["regression-report"].vals.first() {
+            Some(format) => match format.to_str().unwrap().trim().parse() {
+                Ok(format) => format,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    exit(1);
+                }
+            },
+            None => RegressionReportFormat::default(),
+        };
+        arguments.regression_report = Some(format);
+    }
+
+    if args.contains_key("regression-bisect") {
+        let vals = &args["regression-bisect"].vals;
+        let map = vals[0].to_str().unwrap().trim().to_string();
+        let mut versions = Vec::new();
+        for val in &vals[1..] {
+            let version = val.to_str().unwrap().trim();
+            match FactorioVersion::try_from(version) {
+                Ok(version) => versions.push(version),
+                Err(_) => {
+                    eprintln!("Invalid Factorio version {:?}", version);
+                    exit(1);
+                }
+            }
+        }
+        if versions[0] >= versions[1] {
+            eprintln!(
+                "The good version {} of --regression-bisect must be older \
+                than the bad version {}",
+                versions[0], versions[1]
+            );
+            exit(1);
+        }
+        arguments.regression_bisect = Some((map, versions[0], versions[1]));
+    }
+
+    if args.contains_key("keep") {
+        if !args.contains_key("headless")
+            && !args.contains_key("regression-test")
+        {
+            eprintln!("--keep needs --headless prune or --regression-test");
+            exit(1);
+        }
+        let keep = args["keep"].vals[0].to_str().unwrap().trim();
+        match keep.parse() {
+            Ok(keep) => arguments.headless_keep = Some(keep),
+            Err(_) => {
+                eprintln!("Invalid --keep {:?}, expected a number", keep);
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("headless") {
+        let vals = &args["headless"].vals;
+        let command = vals[0].to_str().unwrap().trim();
+        let version = vals.get(1).map(|version| {
+            let version = version.to_str().unwrap().trim();
+            match FactorioVersion::try_from(version) {
+                Ok(version) => version,
+                Err(_) => {
+                    eprintln!("Invalid Factorio version {:?}", version);
+                    exit(1);
+                }
+            }
+        });
+        if (args.contains_key("keep") || args.contains_key("keep-unpacked"))
+            && command != "prune"
+        {
+            eprintln!("--keep and --keep-unpacked need --headless prune");
+            exit(1);
+        }
+        arguments.headless = Some(match (command, version) {
+            ("list", None) => HeadlessCommand::List,
+            ("fetch", Some(version)) => HeadlessCommand::Fetch(version),
+            ("unpack", Some(version)) => HeadlessCommand::Unpack(version),
+            ("prune", None) => {
+                let keep_unpacked = if args.contains_key("keep-unpacked") {
+                    let keep_unpacked =
+                        args["keep-unpacked"].vals[0].to_str().unwrap();
+                    match keep_unpacked.parse() {
+                        Ok(keep_unpacked) => keep_unpacked,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            exit(1);
+                        }
+                    }
+                } else {
+                    KeepUnpacked::default()
+                };
+                HeadlessCommand::Prune {
+                    keep: arguments
+                        .headless_keep
+                        .unwrap_or(DEFAULT_HEADLESS_KEEP),
+                    keep_unpacked,
+                    dry_run: args.contains_key("dry-run"),
+                }
+            }
+            _ => {
+                eprintln!(
+                    "Invalid --headless command, expected list, fetch VERSION, \
+                    unpack VERSION or prune"
+                );
+                exit(1);
+            }
+        });
+    }
+
+    if args.contains_key("regression-threshold") {
+        if !args.contains_key("regression-report")
+            && !args.contains_key("regression-bisect")
+        {
+            eprintln!(
+                "--regression-threshold needs --regression-report or \
+                --regression-bisect"
+            );
+            exit(1);
+        }
+        let percent = args["regression-threshold"].vals[0].to_str().unwrap();
+        match percent.trim().parse::<f64>() {
+            Ok(percent) if percent >= 0.0 && percent.is_finite() => {
+                arguments.regression_threshold = Some(percent)
+            }
+            _ => {
+                eprintln!(
+                    "Invalid --regression-threshold {:?}, expected a \
+                    percentage of at least 0",
+                    percent
+                );
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("list") {
+        print_all_procedures();
+        exit(0);
+    }
+
+    if args.contains_key("benchmark") {
+        arguments.run_benchmark = true;
+        arguments.benchmark_set_name = Some(
+            args["benchmark"].vals[0]
+                .to_str()
+                .unwrap()
+                .trim()
+                .to_string(),
+        );
+    }
+
+    if args.contains_key("resume") {
+        arguments.resume = true;
+    }
+
+    if args.contains_key("output-format") {
+        let format = args["output-format"].vals[0].to_str().unwrap().trim();
+        match format.parse() {
+            Ok(format) => arguments.output_format = format,
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("output") {
+        arguments.output = Some(PathBuf::from(
+            args["output"].vals[0].to_str().unwrap().trim(),
+        ));
+    }
+
+    if args.contains_key("per-tick") {
+        arguments.per_tick = true;
+    }
+
+    if args.contains_key("interleave") {
+        arguments.interleave = true;
+    }
+
+    if args.contains_key("skip-error-check") {
+        arguments.skip_error_check = true;
+    }
+
+    if args.contains_key("timeout-multiplier") {
+        let multiplier = args["timeout-multiplier"].vals[0].to_str().unwrap();
+        match parse_timeout_multiplier(multiplier) {
+            Ok(multiplier) => arguments.timeout_multiplier = Some(multiplier),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("target-precision") {
+        if !args.contains_key("benchmark") && !args.contains_key("meta") {
+            eprintln!("--target-precision needs --benchmark or --meta");
+            exit(1);
+        }
+        let percent = args["target-precision"].vals[0].to_str().unwrap();
+        match percent.trim().parse::<f64>() {
+            Ok(percent) if percent > 0.0 && percent.is_finite() => {
+                arguments.target_precision = Some(percent)
+            }
+            _ => {
+                eprintln!(
+                    "Invalid --target-precision {:?}, expected a percentage \
+                    above 0",
+                    percent
+                );
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("max-runs") {
+        let runs = args["max-runs"].vals[0].to_str().unwrap();
+        match runs.trim().parse::<u32>() {
+            Ok(runs) if runs >= 2 => arguments.max_runs = Some(runs),
+            _ => {
+                eprintln!("Invalid --max-runs {:?}, expected at least 2", runs);
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("max-map-time") {
+        let seconds = args["max-map-time"].vals[0].to_str().unwrap();
+        match seconds.trim().parse::<f64>() {
+            Ok(seconds) if seconds > 0.0 => {
+                arguments.max_map_time = Some(seconds)
+            }
+            _ => {
+                eprintln!(
+                    "Invalid --max-map-time {:?}, expected seconds above 0",
+                    seconds
+                );
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("factorio-versions") {
+        if !args.contains_key("benchmark") && !args.contains_key("meta") {
+            eprintln!("--factorio-versions needs --benchmark or --meta");
+            exit(1);
+        }
+        arguments.factorio_versions = Some(
+            args["factorio-versions"]
+                .vals
+                .iter()
+                .map(|v| v.to_string_lossy().trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect(),
+        );
+    }
+
+    if args.contains_key("keep-going") {
+        arguments.keep_going = true;
+    }
+
+    if args.contains_key("dry-run") {
+        if !args.contains_key("benchmark")
+            && !args.contains_key("meta")
+            && !args.contains_key("headless")
+        {
+            eprintln!("--dry-run needs --benchmark, --meta or --headless prune");
+            exit(1);
+        }
+        arguments.dry_run = true;
+    }
+
+    if args.contains_key("offline") {
+        set_offline(true);
+    }
+
+    if args.contains_key("mirror") {
+        match args["mirror"].vals[0].to_str().unwrap().parse() {
+            Ok(root) => set_mirror_root(root),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("cpu-affinity") {
+        let cpus = args["cpu-affinity"].vals[0].to_str().unwrap().trim();
+        match cpus.parse() {
+            Ok(cpus) => arguments.cpu_affinity = Some(cpus),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("nice") {
+        match parse_nice(args["nice"].vals[0].to_str().unwrap()) {
+            Ok(nice) => arguments.nice = Some(nice),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("meta") {
+        arguments.run_meta = true;
+        arguments.meta_set_name =
+            Some(args["meta"].vals[0].to_str().unwrap().trim().to_string());
+    }
+
+    if args.contains_key("create-benchmark") {
+        arguments.create_benchmark = true;
+        arguments.benchmark_set_name = Some(
+            args["create-benchmark"].vals[0]
+                .to_str()
+                .unwrap()
+                .trim()
+                .to_string(),
+        );
+        if args.contains_key("minify") {
+            set_minify_saves(true);
+        }
+    }
+
+    if args.contains_key("folder") {
+        let path =
+            PathBuf::from(args["folder"].vals[0].to_str().unwrap().trim());
+        if path.is_absolute() {
+            arguments.folder = Some(path);
+        } else if factorio_save_directory().join(&path).is_dir() {
+            arguments.folder = Some(factorio_save_directory().join(&path));
+        } else if path.is_dir() {
+            if let Ok(path) = path.canonicalize() {
+                arguments.folder = Some(path);
+            } else {
+                eprintln!(
+                    "Could not resolve path {:?} to a valid folder",
+                    path
+                );
+                exit(1);
+            }
+        } else {
+            eprintln!("Could not resolve path {:?} to a valid folder", path);
+            exit(1);
+        }
+    }
+
+    if args.contains_key("ticks") {
+        arguments.ticks =
+            try_parse_nonzero_u32(args["ticks"].vals[0].to_str().unwrap_or(""));
+    }
+
+    if args.contains_key("runs") {
+        arguments.runs =
+            try_parse_nonzero_u32(args["runs"].vals[0].to_str().unwrap_or(""));
+    }
+
+    if args.contains_key("warmup-ticks") {
+        let ticks = args["warmup-ticks"].vals[0].to_str().unwrap_or("");
+        match ticks.trim().parse::<u32>() {
+            Ok(ticks) => arguments.warmup_ticks = Some(ticks),
+            Err(_) => {
+                eprintln!("Failed to process --warmup-ticks as u32");
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("outlier-policy") {
+        let policy = args["outlier-policy"].vals[0].to_str().unwrap().trim();
+        match policy.parse() {
+            Ok(policy) => arguments.outlier_policy = Some(policy),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("mods") {
+        let collect_as_csv: String = args["mods"]
+            .vals
+            .iter()
+            .map(|x| x.to_str().unwrap().trim())
+            .collect();
+        arguments.mods_dirty = Some(collect_as_csv);
+    }
+
+    if args.contains_key("create-meta") {
+        arguments.create_meta = true;
+        let collect_as_csv: String = args["create-meta"].vals[1..]
+            .iter()
+            .map(|x| x.to_str().unwrap().trim())
+            .collect();
+        arguments.meta_set_members = Some(collect_as_csv);
+
+        let name = args["create-meta"].vals[0]
+            .to_str()
+            .unwrap()
+            .replace(',', "");
+        arguments.meta_set_name = Some(name);
+    }
+
+    if args.contains_key("commit") {
+        arguments.commit_flag = true;
+        let commit_name =
+            args["commit"].vals[1].to_str().unwrap().trim().to_string();
+        arguments.commit_name = Some(commit_name);
+        if let Ok(commit_type) =
+            args["commit"].vals[0].to_str().unwrap().trim().parse()
+        {
+            arguments.commit_type = Some(commit_type);
+        } else if arguments.interactive {
+            println!("You failed to set a valid type from args, and are running\
+             interactively, enter a commit type. types: benchmark, meta");
+            arguments.commit_type = Some(prompt_until_allowed_val(&[
+                ProcedureKind::Benchmark,
+                ProcedureKind::Meta,
+            ]));
+        } else {
+            eprintln!("Unexpected commit type provided! Expected \"benchmark\" or \"meta\"");
+            exit(1);
+        }
+    }
+
+    if args.contains_key("recursive") {
+        arguments.commit_recursive = true;
+    }
+
+    if args.contains_key("compare") {
+        let ids: Vec<u32> = args["compare"]
+            .vals
+            .iter()
+            .map(|x| {
+                x.to_str().unwrap().trim().parse().unwrap_or_else(|_| {
+                    eprintln!("Collection ids must be integers, found {:?}", x);
+                    exit(1);
+                })
+            })
+            .collect();
+        arguments.compare_collections = Some((ids[0], ids[1]));
+    }
+
+    if args.contains_key("breakdown") {
+        let kind = args["breakdown"].vals[0].to_str().unwrap().trim();
+        let id = args["breakdown"].vals[1].to_str().unwrap().trim();
+        let id = id.parse().unwrap_or_else(|_| {
+            eprintln!("Breakdown id must be an integer, found {:?}", id);
+            exit(1);
+        });
+        match BreakdownScope::from_kind_and_id(kind, id) {
+            Ok(scope) => arguments.breakdown = Some(scope),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if args.contains_key("export-bundle") {
+        let name = args["export-bundle"].vals[0].to_str().unwrap().trim();
+        let file = PathBuf::from(&args["export-bundle"].vals[1]);
+        arguments.export_bundle = Some((name.to_string(), file));
+    }
+
+    if args.contains_key("import-bundle") {
+        arguments.import_bundle =
+            Some(PathBuf::from(&args["import-bundle"].vals[0]));
+    }
+
+    if args.contains_key("validate") {
+        let file_kinds = match args["validate"].vals.first() {
+            None => vec![ProcedureFileKind::Local, ProcedureFileKind::Master],
+            Some(v) => match v.to_str().unwrap().trim() {
+                "local" => vec![ProcedureFileKind::Local],
+                "master" => vec![ProcedureFileKind::Master],
+                path => vec![ProcedureFileKind::Custom(PathBuf::from(path))],
+            },
+        };
+        arguments.validate = Some(file_kinds);
+    }
+
+    arguments
+}
+
+fn try_parse_nonzero_u32(s: &str) -> Option<u32> {
+    match s.parse::<u32>() {
+        Ok(u) => {
+            if u != 0 {
+                Some(u)
+            } else {
+                eprintln!("Parsed arg not allowd to be 0!");
+                exit(1);
+            }
+        }
+        _ => {
+            eprintln!("Failed to process --runs as u32");
+            exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate assert_cmd;
+    use factorio_benchmark_helper::util::common::FACTORIO_BENCHMARK_HELPER_NAME;
+    use factorio_benchmark_helper::util::common::FACTORIO_BENCHMARK_HELPER_VERSION;
+    use assert_cmd::Command;
+    #[test]
+    fn test_version() {
+        let output = Command::cargo_bin(FACTORIO_BENCHMARK_HELPER_NAME)
+            .unwrap()
+            .arg("--version")
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!(
+                "{} {}\n",
+                FACTORIO_BENCHMARK_HELPER_NAME,
+                FACTORIO_BENCHMARK_HELPER_VERSION
+            )
+        );
+    }
+}
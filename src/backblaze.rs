@@ -8,6 +8,7 @@ use percent_encoding::{AsciiSet, CONTROLS};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::Read;
+use std::path::Path;
 use std::path::PathBuf;
 use std::time::Duration;
 use ureq::Agent;
@@ -158,28 +159,28 @@ enum BackblazeUploadState {
 /// These fields are not written to the config.ini file normally,
 /// but will be carried over to future config file versions.
 fn authorize_test(
-    mut client: &mut Agent,
+    client: &mut Agent,
 ) -> Result<BackblazeAuth, BackblazeErrorResponse> {
     let vars = std::env::vars().collect::<HashMap<String, String>>();
-    let key_id = if vars.get("TRAVIS_CI_B2_KEYID").is_some() {
+    let key_id = if vars.contains_key("TRAVIS_CI_B2_KEYID") {
         vars.get("TRAVIS_CI_B2_KEYID").unwrap()
     } else {
         &CONFIG_FILE_SETTINGS.travis_ci_b2_key_id
     };
-    let application_key = if vars.get("TRAVIS_CI_B2_APPLICATIONKEY").is_some() {
+    let application_key = if vars.contains_key("TRAVIS_CI_B2_APPLICATIONKEY") {
         vars.get("TRAVIS_CI_B2_APPLICATIONKEY").unwrap()
     } else {
         &CONFIG_FILE_SETTINGS.travis_ci_b2_applicationkey
     };
-    authorize(&mut client, key_id, application_key)
+    authorize(client, key_id, application_key)
 }
 
 /// Authorize with Backblaze API using keys found within config.ini
 fn authorize_cfg(
-    mut client: &mut Agent,
+    client: &mut Agent,
 ) -> Result<BackblazeAuth, BackblazeErrorResponse> {
     if cfg!(test) {
-        return authorize_test(&mut client);
+        return authorize_test(client);
     }
     let key_id = &CONFIG_FILE_SETTINGS.b2_backblaze_key_id;
     let application_key = &CONFIG_FILE_SETTINGS.b2_backblaze_application_key;
@@ -217,10 +218,11 @@ fn b2_list_file_names(
     auth: &BackblazeAuth,
     file_subdirectory: &str,
 ) -> Result<BackblazeListFileNamesResponse, BackblazeErrorResponse> {
-    let mut body = BackblazeListFileNamesPostBody::default();
-    body.bucketId = auth.allowed.bucketId.to_owned();
-    body.maxFileCount = 1000;
-    body.prefix = file_subdirectory.to_string();
+    let body = BackblazeListFileNamesPostBody {
+        bucketId: auth.allowed.bucketId.to_owned(),
+        maxFileCount: 1000,
+        prefix: file_subdirectory.to_string(),
+    };
     let body = serde_json::to_string(&body).unwrap();
     let api_url_cmd =
         format!("{}{}", &auth.apiUrl, "/b2api/v2/b2_list_file_names");
@@ -269,7 +271,7 @@ fn get_b2_upload_urls_per_file(
 ) -> Result<(), BackblazeErrorResponse> {
     for file in &mut container.files {
         file.get_upload_url_response =
-            b2_get_upload_url(&client, &container.auth.as_ref().unwrap())?;
+            b2_get_upload_url(client, container.auth.as_ref().unwrap())?;
     }
     Ok(())
 }
@@ -295,7 +297,7 @@ fn b2_get_upload_url(
 
 /// Naive implementation to get the mime type of a file based on file extension
 /// Add additional extensions if you ever panic here
-fn get_file_mimetype(filepath: &PathBuf) -> String {
+fn get_file_mimetype(filepath: &Path) -> String {
     let extension = filepath.extension().unwrap_or_default().to_str().unwrap();
     match extension {
         "zip" => "application/zip".to_string(),
@@ -420,9 +422,10 @@ pub fn upload_files_to_backblaze(
 
     let mut client = Agent::new();
     let mut state = BackblazeUploadState::GetAuth;
-    let mut container = BackbazeDataContainer::default();
-    container.files =
-        collect_file_upload_instances(file_subdirectory, filepaths);
+    let mut container = BackbazeDataContainer {
+        files: collect_file_upload_instances(file_subdirectory, filepaths),
+        ..Default::default()
+    };
 
     let mut path_Url_pairs = Vec::new();
 
@@ -451,7 +454,7 @@ pub fn upload_files_to_backblaze(
             BackblazeUploadState::ListFileNames => {
                 match b2_list_file_names(
                     &client,
-                    &container.auth.as_ref().unwrap(),
+                    container.auth.as_ref().unwrap(),
                     file_subdirectory,
                 ) {
                     Ok(resp) => {
@@ -485,13 +488,13 @@ pub fn upload_files_to_backblaze(
             }
             BackblazeUploadState::TestIfAlreadyUploaded => {
                 populate_final_urls(
-                    &container.auth.as_ref().unwrap(),
+                    container.auth.as_ref().unwrap(),
                     &mut container.files,
                 );
                 b2_test_files_already_uploaded(
                     &client,
                     &mut container.files,
-                    &container.already_uploaded_files.as_ref().unwrap(),
+                    container.already_uploaded_files.as_ref().unwrap(),
                 );
                 state = BackblazeUploadState::GetUploadUrl;
             }
@@ -537,7 +540,7 @@ pub fn upload_files_to_backblaze(
                         match b2_upload_file(
                             &client,
                             &file.get_upload_url_response,
-                            &file,
+                            file,
                         ) {
                             Ok(_upload_resp) => {
                                 path_Url_pairs.push((
@@ -635,15 +638,19 @@ mod test {
         let mut file = OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(true)
             .open(&to_save_to_path)
             .unwrap();
         let mut buf = Vec::new();
         resp.into_reader().read_to_end(&mut buf).unwrap();
         file.write_all(&buf).unwrap();
-        let uploaded =
-            upload_files_to_backblaze("", &[to_save_to_path.clone()]).unwrap();
+        let uploaded = upload_files_to_backblaze(
+            "",
+            std::slice::from_ref(&to_save_to_path),
+        )
+        .unwrap();
         assert!(uploaded.len() == 1);
-        let (k, v) = uploaded.get(0).unwrap();
+        let (k, v) = uploaded.first().unwrap();
         assert_eq!(k, &to_save_to_path);
         assert_eq!(v, "https://f000.backblazeb2.com/file/cargo-test/this-is-a-test-generated-name-ignore-it.zip");
         std::fs::remove_file(&to_save_to_path).unwrap();
@@ -659,16 +666,19 @@ mod test {
         let mut newfile = std::fs::OpenOptions::new()
             .create(true)
             .write(true)
+            .truncate(true)
             .open(&newfilepath)
             .unwrap();
         let mut buf = Vec::new();
         resp.into_reader().read_to_end(&mut buf).unwrap();
         newfile.write_all(&buf).unwrap();
-        let uploaded =
-            upload_files_to_backblaze("Spa ce", &[newfilepath.clone()])
-                .unwrap();
+        let uploaded = upload_files_to_backblaze(
+            "Spa ce",
+            std::slice::from_ref(&newfilepath),
+        )
+        .unwrap();
         assert!(uploaded.len() == 1);
-        let (k, v) = uploaded.get(0).unwrap();
+        let (k, v) = uploaded.first().unwrap();
         assert_eq!(k, &newfilepath);
         assert_eq!(
             v,
@@ -1,40 +1,83 @@
 extern crate regex;
 
+use crate::error::FbhError;
+use crate::performance_results::checkpoint::clear_in_progress_collection;
+use crate::performance_results::checkpoint::get_in_progress_collection;
+use crate::performance_results::checkpoint::set_in_progress_collection;
 use crate::performance_results::collection_data::BenchmarkData;
+use crate::performance_results::collection_data::BenchmarkFailure;
 use crate::performance_results::collection_data::CollectionData;
+use crate::performance_results::collection_data::MapDuration;
 use crate::performance_results::collection_data::Mod;
-use crate::performance_results::database::upload_to_db;
+use crate::performance_results::compare::print_collection_comparison;
+use crate::performance_results::database::get_collection_progress;
+use crate::performance_results::database::get_map_duration;
+use crate::performance_results::database::insert_benchmark;
+use crate::performance_results::database::insert_benchmark_failure;
+use crate::performance_results::database::insert_collection;
+use crate::performance_results::database::record_map_duration;
+use crate::performance_results::database::CollectionProgress;
+use crate::performance_results::report::write_collection_reports;
+use crate::performance_results::report::ReportOptions;
+#[cfg(target_os = "linux")]
+use crate::regression_tester::get_unpacked_executables;
+use crate::util::config_file::CONFIG_FILE_SETTINGS;
+use crate::util::query_map_download_size;
+use crate::util::sha1sum;
 use crate::util::sha256sum;
+use crate::util::BenchmarkProgress;
+use crate::util::ProcessScheduling;
 use megabase_index_incrementer::FactorioVersion;
 
 use crate::util::{
-    download_benchmark_deps_parallel, factorio_executable_path, fbh_mod_dl_dir,
-    fbh_mod_use_dir, fbh_save_dl_dir, query_system_cpuid, BenchmarkSet,
-    FACTORIO_INFO,
+    download_benchmark_deps_parallel, factorio_executable_path, factorio_info,
+    fbh_mod_dl_dir, fbh_mod_use_dir, fbh_save_dl_dir, query_factorio_info,
+    query_system_cpuid, query_system_environment, BenchmarkSet, FactorioInfo,
 };
 use regex::Regex;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fs::read;
+use std::io::BufRead;
+use std::io::BufReader;
 use std::io::Read;
 use std::path::Path;
 use std::path::PathBuf;
-use std::process::exit;
 use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
 use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
 use std::time::Instant;
 
 static NUMBER_ERROR_CHECKING_TICKS: u32 = 250;
 static NUMBER_ERROR_CHECKING_RUNS: u32 = 3;
+/// The seconds the error checking pass of a map which never ran on this
+/// system is assumed to take, to derive its timeout from.
+const UNMEASURED_ERROR_CHECK_TIME: f64 = 600.0;
+/// The shortest timeout of any Factorio run, in seconds, since loading a
+/// map can vary a lot from run to run.
+const MINIMUM_TIMEOUT: f64 = 300.0;
+/// The most runs of a map with adaptive runs, unless given.
+pub const DEFAULT_ADAPTIVE_MAX_RUNS: u32 = 30;
+/// The most lines following an error kept to explain it.
+const MAX_ERROR_CONTEXT_LINES: usize = 10;
 
-const STANDARD_VERBOSE_TIMINGS: &str = "wholeUpdate,gameUpdate,\
+pub const STANDARD_VERBOSE_TIMINGS: &str = "wholeUpdate,gameUpdate,\
     circuitNetworkUpdate,transportLinesUpdate,fluidsUpdate,entityUpdate,\
     mapGenerator,electricNetworkUpdate,logisticManagerUpdate,\
     constructionManagerUpdate,pathFinder,trains,trainPathFinder,commander,\
     chartRefresh,luaGarbageIncremental,chartUpdate,scriptUpdate";
 
 lazy_static! {
-    static ref GENERIC_FACTORIO_ERROR_MATCH_PATTERN: Regex = Regex::new(r"[Ee]rror.*\n").unwrap();
+    //Factorio logs errors with the Error level after the timestamp, unlike map names or mod log lines.
+    static ref FACTORIO_ERROR_LINE_PATTERN: Regex = Regex::new(r"^(?: *\d+\.\d{3} )?(?:Error\b|Factorio crashed)").unwrap();
+    static ref FACTORIO_LOG_LINE_PATTERN: Regex = Regex::new(r"^ *\d+\.\d{3} ").unwrap();
     static ref GENERIC_NUMERIC_TIMESTAMP_PATTERN: Regex = Regex::new(r"\d+\.\d{3}").unwrap();
     static ref INITIALIZATION_TIME_PATTERN: Regex = Regex::new("\n .*[0-9].*.[0-9].*Factorio initialised\n").unwrap();
     static ref TOTAL_TIME_PATTERN: Regex = Regex::new("\n .*[0-9].*.[0-9].*Goodbye\n").unwrap();
@@ -42,8 +85,8 @@ lazy_static! {
     //Regexes include ; and : which are not user inputtable via a map.
     static ref MAP_VERSION_MATCH_PATTERN: Regex = Regex::new(r": Map version \d{1,2}\.\d{2,3}\.\d{2,3}").unwrap();
     static ref VERBOSE_COLUMN_HEADER_MATCH_PATTERN: Regex = Regex::new("tick,.*,*\n").unwrap();
-    static ref VERBOSE_DATA_ROW_MATCH_PATTERN: Regex = Regex::new("^t[0-9]*[0-9],[0-9]").unwrap();
-    static ref VERBOSE_RUN_MARKER_REGEX: Regex = Regex::new("^run ([0-9].*):").unwrap();
+    pub(crate) static ref VERBOSE_DATA_ROW_MATCH_PATTERN: Regex = Regex::new("^t[0-9]*[0-9],[0-9]").unwrap();
+    pub(crate) static ref VERBOSE_RUN_MARKER_REGEX: Regex = Regex::new("^run ([0-9].*):").unwrap();
     static ref CURRENT_RESAVE_PORT: Mutex<u32> = Mutex::new(31498);
     static ref FACTORIO_VERSION_MATCH_PATTERN: Regex = Regex::new(r"; Factorio ([0-9]*)\.([0-9]*)\.([0-9]*) ").unwrap();
 }
@@ -55,6 +98,9 @@ pub struct SimpleBenchmarkParams {
     pub runs: u32,
     pub mod_directory: PathBuf,
     pub mods: Vec<Mod>,
+    pub scheduling: ProcessScheduling,
+    /// Factorio is killed if the benchmark has not finished by then.
+    pub timeout: Option<Duration>,
 }
 
 impl SimpleBenchmarkParams {
@@ -69,27 +115,126 @@ impl SimpleBenchmarkParams {
             runs,
             mod_directory: fbh_mod_use_dir(),
             mods: Vec::new(),
+            scheduling: ProcessScheduling::default(),
+            timeout: None,
         }
     }
 }
 
-#[derive(Debug)]
-struct BenchmarkDurationOverhead {
-    initialization_time: f64,
-    per_tick_time: f64,
-    per_run_overhead_time: f64,
-    overall_time: f64,
+/// Options controlling how benchmark sets are ran, independent of the sets
+/// themselves.
+#[derive(Debug, Clone, Default)]
+pub struct BenchmarkRunOptions {
+    /// Continue the unfinished collection of a set if there is one, skipping
+    /// maps which already have results.
+    pub resume: bool,
+    /// How to report the results once every set has finished.
+    pub report: ReportOptions,
+    /// The CPU affinity and priority Factorio is ran with.
+    pub scheduling: ProcessScheduling,
+    /// Run the maps of a set round robin, one run of each map at a time,
+    /// instead of all runs of a map back to back.
+    pub interleave: bool,
+    /// Only print what would be downloaded and ran, without launching
+    /// Factorio or writing results.
+    pub dry_run: bool,
+    /// Skip the error checking pass of maps which already ran without errors
+    /// on this cpu and Factorio version.
+    pub skip_error_check: bool,
+    /// Log failing maps and sets and continue with the next one, instead of
+    /// stopping at the first error.
+    pub keep_going: bool,
+    /// Keep adding runs to every map until its mean is precise enough,
+    /// instead of running the runs of the set.
+    pub adaptive: Option<AdaptiveRuns>,
+    /// Factorio is killed if a benchmark takes this many times longer than
+    /// expected, and the map is recorded as failed. 0 never times out. Takes
+    /// precedence over the multiplier of a set, and without either the one
+    /// in config.ini is used.
+    pub timeout_multiplier: Option<f64>,
+    /// The Factorio to benchmark with, instead of the one in config.ini.
+    pub factorio: Option<FactorioInstall>,
 }
 
-impl Default for BenchmarkDurationOverhead {
-    fn default() -> Self {
-        Self {
-            initialization_time: 0.0,
-            per_tick_time: 0.0,
-            per_run_overhead_time: 0.0,
-            overall_time: 0.0,
+impl BenchmarkRunOptions {
+    fn factorio_executable(&self) -> Result<PathBuf, FbhError> {
+        match &self.factorio {
+            Some(install) => Ok(install.executable.clone()),
+            None => factorio_executable_path(),
         }
     }
+
+    fn factorio_info(&self) -> Result<&FactorioInfo, FbhError> {
+        match &self.factorio {
+            Some(install) => Ok(&install.info),
+            None => factorio_info(),
+        }
+    }
+}
+
+/// A Factorio executable to benchmark with, and its version.
+#[derive(Debug, Clone)]
+pub struct FactorioInstall {
+    pub executable: PathBuf,
+    pub info: FactorioInfo,
+}
+
+/// Finds the Factorio installs to benchmark with, each given as the version
+/// of an unpacked headless Factorio, a Factorio directory or an executable.
+pub fn resolve_factorio_installs(
+    installs: &[String],
+) -> Result<Vec<FactorioInstall>, FbhError> {
+    let mut resolved = Vec::new();
+    for install in installs {
+        let path = PathBuf::from(install);
+        let executable = if path.is_file() {
+            path
+        } else if path.is_dir() {
+            if cfg!(target_os = "linux") {
+                path.join("bin").join("x64").join("factorio")
+            } else {
+                path.join("bin").join("x64").join("factorio.exe")
+            }
+        } else {
+            unpacked_factorio_executable(install)?
+        };
+        let info = query_factorio_info(&executable)?;
+        resolved.push(FactorioInstall { executable, info });
+    }
+    Ok(resolved)
+}
+
+#[cfg(target_os = "linux")]
+fn unpacked_factorio_executable(version: &str) -> Result<PathBuf, FbhError> {
+    let wanted: FactorioVersion = match version.try_into() {
+        Ok(version) => version,
+        Err(_) => {
+            return Err(FbhError::InvalidInput(format!(
+                "{:?} is neither a Factorio version nor an existing path",
+                version
+            )))
+        }
+    };
+    get_unpacked_executables()?
+        .into_iter()
+        .find(|(unpacked, _)| *unpacked == wanted)
+        .map(|(_, executable)| executable)
+        .ok_or_else(|| {
+            FbhError::InvalidInput(format!(
+                "Factorio {} is not unpacked, run the regression tester to \
+                fetch it",
+                version
+            ))
+        })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unpacked_factorio_executable(version: &str) -> Result<PathBuf, FbhError> {
+    Err(FbhError::InvalidInput(format!(
+        "{:?} does not exist, Factorio versions can only be looked up on \
+        Linux",
+        version
+    )))
 }
 
 fn parse_logline_time_to_f64(
@@ -122,150 +267,505 @@ fn parse_logline_time_to_f64(
     }
 }
 
-fn validate_benchmark_set_parameters(set: &BenchmarkSet) {
-    assert!(!set.maps.is_empty());
-    assert!(set.ticks > 0);
-    assert!(set.runs > 0);
+fn validate_benchmark_set_parameters(
+    name: &str,
+    set: &BenchmarkSet,
+) -> Result<(), FbhError> {
+    if set.maps.is_empty() || set.ticks == 0 || set.runs == 0 {
+        return Err(FbhError::InvalidInput(format!(
+            "Benchmark set {:?} needs at least one map, tick and run",
+            name
+        )));
+    }
+    if set.warmup_ticks >= set.ticks {
+        return Err(FbhError::InvalidInput(format!(
+            "Benchmark set {:?} discards all of its {} ticks as warm-up",
+            name, set.ticks
+        )));
+    }
+    for map in &set.maps {
+        let ticks = set.map_ticks(map);
+        if ticks == 0 || set.map_runs(map) == 0 {
+            return Err(FbhError::InvalidInput(format!(
+                "Map {} of benchmark set {:?} needs at least one tick and run",
+                map.name, name
+            )));
+        }
+        if set.map_warmup_ticks(map) >= ticks {
+            return Err(FbhError::InvalidInput(format!(
+                "Map {} of benchmark set {:?} discards all of its {} ticks \
+                as warm-up",
+                map.name, name, ticks
+            )));
+        }
+    }
+    Ok(())
 }
 
 /// Parses the stdout of a Factorio benchmark for any errors.
-fn parse_stdout_for_errors(stdout: &str) {
-    if let Some(e) = GENERIC_FACTORIO_ERROR_MATCH_PATTERN.captures(stdout) {
-        eprintln!("An error was detected when trying to run Factorio");
-        eprintln!("{:?}", &e[0]);
-        exit(1);
+pub(crate) fn parse_stdout_for_errors(stdout: &str) -> Result<(), FbhError> {
+    if let Some(error) = find_factorio_error(stdout) {
+        return Err(FbhError::Factorio(format!(
+            "an error was reported while running:\n{}",
+            error
+        )));
+    }
+    Ok(())
+}
+
+/// Finds the first error logged by Factorio, along with the lines after it
+/// which belong to the same message, such as a Lua stack trace.
+fn find_factorio_error(stdout: &str) -> Option<String> {
+    let mut lines = stdout.lines();
+    let error_line = lines
+        .by_ref()
+        .find(|line| FACTORIO_ERROR_LINE_PATTERN.is_match(line))?;
+    let mut context = vec![error_line.trim()];
+    context.extend(
+        lines
+            .take_while(|line| {
+                !line.trim().is_empty()
+                    && !FACTORIO_LOG_LINE_PATTERN.is_match(line)
+                    && !VERBOSE_DATA_ROW_MATCH_PATTERN.is_match(line)
+                    && !VERBOSE_RUN_MARKER_REGEX.is_match(line)
+                    && !line.starts_with("tick,")
+            })
+            .take(MAX_ERROR_CONTEXT_LINES)
+            .map(str::trim_end),
+    );
+    Some(context.join("\n"))
+}
+
+/// Runs a Factorio benchmark, failing if Factorio reported an error.
+fn run_checked_factorio_benchmark(
+    options: &BenchmarkRunOptions,
+    params: &SimpleBenchmarkParams,
+    progress: Option<&mut BenchmarkProgress>,
+) -> Result<String, FbhError> {
+    let stdout = run_factorio_benchmark_with_progress(
+        options.factorio_executable()?,
+        params,
+        progress,
+    )?;
+    parse_stdout_for_errors(&stdout)?;
+    Ok(stdout)
+}
+
+/// Why a map failed with `error`, if the run continues with the next map,
+/// or the error itself if the whole run stops. Timeouts never stop the run.
+fn map_failure_reason(
+    error: FbhError,
+    options: &BenchmarkRunOptions,
+) -> Result<String, FbhError> {
+    match error {
+        FbhError::Timeout(reason) => Ok(reason),
+        error if options.keep_going => Ok(error.to_string()),
+        error => Err(error),
     }
 }
 
 /// Runs multiple benchmark sets, each of which might contain different
 /// maps/mods/durations.
-pub fn run_benchmarks_multiple(sets: HashMap<String, BenchmarkSet>) {
-    download_benchmark_deps_parallel(&sets);
+pub fn run_benchmarks_multiple(
+    sets: HashMap<String, BenchmarkSet>,
+    options: &BenchmarkRunOptions,
+) -> Result<(), FbhError> {
+    if options.dry_run {
+        return print_benchmark_plan(&sets, options);
+    }
+    download_benchmark_deps_parallel(&sets)?;
+    let outcome = run_benchmark_sets(&sets, options, "")?;
+    write_collection_reports(&outcome.collection_ids(), &options.report)?;
+    outcome.finish()
+}
+
+/// Runs multiple benchmark sets with every Factorio install, storing a
+/// collection per set and install, then compares each install against the
+/// first.
+pub fn run_benchmarks_across_versions(
+    sets: HashMap<String, BenchmarkSet>,
+    installs: &[FactorioInstall],
+    options: &BenchmarkRunOptions,
+) -> Result<(), FbhError> {
+    let install_options = |install: &FactorioInstall| BenchmarkRunOptions {
+        factorio: Some(install.clone()),
+        ..options.clone()
+    };
+    if options.dry_run {
+        for install in installs {
+            println!(
+                "With Factorio {} at {:?}:",
+                install.info.version, install.executable
+            );
+            print_benchmark_plan(&sets, &install_options(install))?;
+        }
+        return Ok(());
+    }
+    download_benchmark_deps_parallel(&sets)?;
+    let mut outcome = SetsOutcome::default();
+    let mut version_collections = Vec::new();
+    for install in installs {
+        println!(
+            "Benchmarking with Factorio {} at {:?}",
+            install.info.version, install.executable
+        );
+        let label = format!(" with Factorio {}", install.info.version);
+        let version_outcome =
+            run_benchmark_sets(&sets, &install_options(install), &label)?;
+        version_collections.push(version_outcome.collections.clone());
+        outcome.extend(version_outcome);
+    }
+    write_collection_reports(&outcome.collection_ids(), &options.report)?;
+    print_version_comparisons(&version_collections);
+    outcome.finish()
+}
+
+/// Compares the collection of every set with each later install against
+/// the collection with the first install.
+fn print_version_comparisons(version_collections: &[Vec<(String, u32)>]) {
+    let (baseline, others) = match version_collections.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    for (set_name, baseline_id) in baseline {
+        for collections in others {
+            let other_id = match collections.iter().find(|(n, _)| n == set_name)
+            {
+                Some((_, id)) => *id,
+                None => continue,
+            };
+            println!();
+            println!("Set {:?}:", set_name);
+            if let Err(e) = print_collection_comparison(*baseline_id, other_id)
+            {
+                eprintln!(
+                    "Could not compare collections {} and {}: {}",
+                    baseline_id, other_id, e
+                );
+            }
+        }
+    }
+}
+
+/// The collections and failures of running benchmark sets.
+#[derive(Debug, Default)]
+struct SetsOutcome {
+    /// The set names and collection ids of every set which ran.
+    collections: Vec<(String, u32)>,
+    map_failures: Vec<(String, BenchmarkFailure)>,
+    set_failures: Vec<(String, FbhError)>,
+}
+
+impl SetsOutcome {
+    fn collection_ids(&self) -> Vec<u32> {
+        self.collections.iter().map(|(_, id)| *id).collect()
+    }
+
+    fn extend(&mut self, other: SetsOutcome) {
+        self.collections.extend(other.collections);
+        self.map_failures.extend(other.map_failures);
+        self.set_failures.extend(other.set_failures);
+    }
+
+    /// Prints a summary of every failure, returning the error of the first
+    /// set which failed, or an error if any map failed.
+    fn finish(self) -> Result<(), FbhError> {
+        if !self.map_failures.is_empty() || !self.set_failures.is_empty() {
+            eprintln!("Failures:");
+            for (set_name, failure) in &self.map_failures {
+                eprintln!(
+                    "  map {} of set {}: {}",
+                    failure.map_name, set_name, failure.reason
+                );
+            }
+            for (set_name, e) in &self.set_failures {
+                eprintln!("  set {}: {}", set_name, e);
+            }
+        }
+        match self.set_failures.into_iter().next() {
+            Some((_, e)) => Err(e),
+            None if !self.map_failures.is_empty() => {
+                Err(FbhError::Factorio(format!(
+                    "{} maps did not finish, see the failures above",
+                    self.map_failures.len()
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Runs every set, whose maps and mods were already downloaded. `label` is
+/// appended to the set names of failures.
+fn run_benchmark_sets(
+    sets: &HashMap<String, BenchmarkSet>,
+    options: &BenchmarkRunOptions,
+    label: &str,
+) -> Result<SetsOutcome, FbhError> {
+    let mut outcome = SetsOutcome::default();
     for (name, set) in sets {
-        validate_benchmark_set_parameters(&set);
+        let failure_name = format!("{:?}{}", name, label);
+        match run_benchmark_set(name, set.clone(), options) {
+            Ok((collection_id, failures)) => {
+                if let Some(collection_id) = collection_id {
+                    outcome.collections.push((name.clone(), collection_id));
+                }
+                outcome.map_failures.extend(
+                    failures.into_iter().map(|f| (failure_name.clone(), f)),
+                );
+            }
+            Err(e) if options.keep_going => {
+                eprintln!("Benchmark set {} failed: {}", failure_name, e);
+                outcome.set_failures.push((failure_name, e));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(outcome)
+}
+
+/// Checks a set and runs it, returning the id of its collection and the
+/// maps which failed.
+fn run_benchmark_set(
+    name: &str,
+    set: BenchmarkSet,
+    options: &BenchmarkRunOptions,
+) -> Result<(Option<u32>, Vec<BenchmarkFailure>), FbhError> {
+    validate_benchmark_set_parameters(name, &set)?;
+    let save_directory = if let Some(subdir) = &set.save_subdirectory {
+        fbh_save_dl_dir().join(subdir)
+    } else {
+        fbh_save_dl_dir()
+    };
+    for map in &set.maps {
+        let fpath = save_directory.join(&map.name);
+        if !fpath.exists() {
+            return Err(FbhError::MapDownload(format!(
+                "map {:?} is missing after downloading",
+                fpath
+            )));
+        }
+    }
+    let mod_directory = fbh_mod_use_dir();
+    if !mod_directory.is_dir() {
+        return Err(FbhError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("mod directory {:?} does not exist", mod_directory),
+        )));
+    }
+    run_factorio_benchmarks_from_set(name, set, options)
+}
+
+/// Prints the cache status of every map and mod of the sets, the Factorio
+/// commands that would be ran, and the total ticks to be ran.
+fn print_benchmark_plan(
+    sets: &HashMap<String, BenchmarkSet>,
+    options: &BenchmarkRunOptions,
+) -> Result<(), FbhError> {
+    let mut names: Vec<&String> = sets.keys().collect();
+    names.sort();
+    let mut total_ticks = 0u64;
+    let mut total_launches = 0u64;
+    for name in names {
+        let set = &sets[name];
+        validate_benchmark_set_parameters(name, set)?;
+        println!(
+            "Benchmark set {:?}: {} maps, {} ticks, {} runs",
+            name,
+            set.maps.len(),
+            set.ticks,
+            set.runs
+        );
         let save_directory = if let Some(subdir) = &set.save_subdirectory {
             fbh_save_dl_dir().join(subdir)
         } else {
             fbh_save_dl_dir()
         };
+        for indiv_mod in &set.mods {
+            let file_name = indiv_mod.zip_file_name();
+            let path = fbh_mod_dl_dir().join(&file_name);
+            let status = if !path.is_file() {
+                "needs download".to_owned()
+            } else if sha1sum(&path) == indiv_mod.sha1 {
+                "present, hash ok".to_owned()
+            } else {
+                "present, hash mismatch, needs download".to_owned()
+            };
+            println!("    mod {}: {}", file_name, status);
+        }
         for map in &set.maps {
-            let fpath = save_directory.join(&map.name);
-            assert!(fpath.exists());
+            let path = save_directory.join(&map.name);
+            let status = if !path.is_file() {
+                match query_map_download_size(
+                    map,
+                    set.save_subdirectory.as_deref(),
+                ) {
+                    Some(size) => format!(
+                        "needs download ({:.1} MiB)",
+                        size as f64 / 1024.0 / 1024.0
+                    ),
+                    None => "needs download (size unknown)".to_owned(),
+                }
+            } else if map.sha256.is_empty() {
+                "present, no hash to check".to_owned()
+            } else if sha256sum(&path) == map.sha256 {
+                "present, hash ok".to_owned()
+            } else {
+                "present, hash mismatch, needs download".to_owned()
+            };
+            println!("    map {}: {}", map.name, status);
+        }
+
+        for map in &set.maps {
+            let ticks = set.map_ticks(map);
+            let runs = match &options.adaptive {
+                Some(adaptive) => adaptive.first_runs(set.map_runs(map)),
+                None => set.map_runs(map),
+            };
+            let loads_per_map = if options.interleave { runs } else { 1 };
+            if map.ticks.is_some() || map.runs.is_some() {
+                println!("  map {}: {} ticks, {} runs", map.name, ticks, runs);
+            }
+            let error_check = SimpleBenchmarkParams {
+                scheduling: options.scheduling.clone(),
+                ..SimpleBenchmarkParams::new(
+                    save_directory.join(&map.name),
+                    NUMBER_ERROR_CHECKING_TICKS,
+                    NUMBER_ERROR_CHECKING_RUNS,
+                )
+            };
+            let benchmark = SimpleBenchmarkParams {
+                ticks,
+                runs: runs / loads_per_map,
+                ..error_check.clone()
+            };
+            let factorio_exe = options.factorio_executable()?;
+            println!(
+                "    error check: {:?}",
+                build_benchmark_command(&factorio_exe, &error_check)?
+            );
+            println!(
+                "    benchmark{}: {:?}",
+                if loads_per_map > 1 {
+                    ", once per run"
+                } else {
+                    ""
+                },
+                build_benchmark_command(&factorio_exe, &benchmark)?
+            );
+            total_ticks += u64::from(NUMBER_ERROR_CHECKING_TICKS)
+                * u64::from(NUMBER_ERROR_CHECKING_RUNS);
+            total_ticks += u64::from(ticks) * u64::from(runs);
+            total_launches += 1 + u64::from(loads_per_map);
         }
-        assert!(fbh_mod_use_dir().is_dir());
-        run_factorio_benchmarks_from_set(&name, set);
     }
+    println!(
+        "Would run {} ticks in total, launching Factorio {} times",
+        total_ticks, total_launches
+    );
+    if let Some(adaptive) = &options.adaptive {
+        println!(
+            "Maps would then get more runs until their mean is within \
+            ±{:.2}%, up to {} runs each",
+            adaptive.target_precision * 100.0,
+            adaptive.max_runs
+        );
+    }
+    Ok(())
 }
 
 fn parse_stdout_for_benchmark_time_breakdown(
     stdout: &str,
-) -> Option<BenchmarkDurationOverhead> {
-    let parsed = parse_stdout_into_benchmark_data(&stdout);
-    let mut benchmark_time: BenchmarkDurationOverhead =
-        BenchmarkDurationOverhead::default();
-    benchmark_time.initialization_time =
-        parse_logline_time_to_f64(stdout, &INITIALIZATION_TIME_PATTERN)?;
-    benchmark_time.per_tick_time =
-        parse_logline_time_to_f64(stdout, &PER_TICK_TIME_PATTERN)?;
-    benchmark_time.overall_time =
-        parse_logline_time_to_f64(stdout, &TOTAL_TIME_PATTERN)?;
+    params: &SimpleBenchmarkParams,
+) -> Option<MapDuration> {
+    let mut benchmark_time = MapDuration {
+        initialization_time: parse_logline_time_to_f64(
+            stdout,
+            &INITIALIZATION_TIME_PATTERN,
+        )?,
+        per_tick_time: parse_logline_time_to_f64(
+            stdout,
+            &PER_TICK_TIME_PATTERN,
+        )?,
+        ..Default::default()
+    };
+    let overall_time = parse_logline_time_to_f64(stdout, &TOTAL_TIME_PATTERN)?;
     let time_spent_in_benchmarks =
-        benchmark_time.overall_time - benchmark_time.initialization_time;
+        overall_time - benchmark_time.initialization_time;
     if time_spent_in_benchmarks <= 0.0 {
         return None;
     } else {
         //ticks are in ms, convert to sec
         let tick_cumulative_time_per_run =
-            benchmark_time.per_tick_time * f64::from(parsed.ticks) / 1000.0;
+            benchmark_time.per_tick_time * f64::from(params.ticks) / 1000.0;
         benchmark_time.per_run_overhead_time = (time_spent_in_benchmarks
-            / f64::from(parsed.runs))
+            / f64::from(params.runs))
             - tick_cumulative_time_per_run;
     }
     Some(benchmark_time)
 }
 
-/// Runs benchmarks on the saves provided in the set. First performs a short
-/// error checking pass, and then runs the set's specified parameters.
-fn run_factorio_benchmarks_from_set(set_name: &str, set: BenchmarkSet) {
-    let mut map_durations: Vec<BenchmarkDurationOverhead> = Vec::new();
-    let mut initial_error_check_params = Vec::new();
-    let mut set_params = Vec::new();
-    let save_directory = if let Some(subdir) = &set.save_subdirectory {
-        fbh_save_dl_dir().join(subdir)
-    } else {
-        fbh_save_dl_dir()
-    };
-    if let Ok(dir_list) = std::fs::read_dir(fbh_mod_use_dir()) {
-        for dir_entry_result in dir_list {
-            if let Ok(dir_entry) = dir_entry_result {
-                match std::fs::remove_file(dir_entry.path()) {
-                    Ok(_) => (),
-                    _ => {
-                        eprintln!("Failed to remove a mod from the staging directory!");
-                        exit(1);
-                    }
-                }
-            }
-        }
-    }
-    for indiv_mod in &set.mods {
-        let mod_filename = if indiv_mod.file_name.is_empty() {
-            format!("{}_{}.zip", indiv_mod.name, indiv_mod.version)
-        } else {
-            indiv_mod.file_name.clone()
-        };
-        let cached_mods_dir = fbh_mod_dl_dir().join(&mod_filename);
-        let mods_use_dir = fbh_mod_use_dir().join(&mod_filename);
-        assert!(cached_mods_dir.exists());
-        match std::fs::write(&mods_use_dir, read(&cached_mods_dir).unwrap()) {
-            Ok(_m) => (),
-            _ => {
-                eprintln!("Failed to copy mod {:?} for use.", &mod_filename);
-                exit(1);
-            }
-        }
-    }
-    for map in &set.maps {
-        initial_error_check_params.push(SimpleBenchmarkParams::new(
-            save_directory.join(&map.name),
-            NUMBER_ERROR_CHECKING_TICKS,
-            NUMBER_ERROR_CHECKING_RUNS,
-        ));
-        set_params.push(SimpleBenchmarkParams::new(
-            save_directory.join(&map.name),
-            set.ticks,
-            set.runs,
-        ));
+/// Stores how long a map took to run on this system, so later runs can
+/// estimate their duration and skip the error checking pass. A failure to
+/// store it is only reported.
+fn record_benchmark_duration(
+    duration: &MapDuration,
+    map_hash: &str,
+    factorio_version: &str,
+) {
+    if let Err(e) = record_map_duration(
+        map_hash,
+        &query_system_cpuid(),
+        factorio_version,
+        duration,
+    ) {
+        eprintln!("Failed to record the duration of a map: {}", e);
     }
-    for param in initial_error_check_params {
-        let stdout =
-            run_factorio_benchmark(&factorio_executable_path(), &param);
-        if let Some(stdout) = stdout {
-            parse_stdout_for_errors(&stdout);
-            let time_breakdown =
-                parse_stdout_for_benchmark_time_breakdown(&stdout);
-            if let Some(time) = time_breakdown {
-                map_durations.push(time);
-            }
+}
+
+/// The measured duration of a map from an earlier run on this system, if
+/// it ran without errors.
+fn previous_benchmark_duration(
+    map_hash: &str,
+    factorio_version: &str,
+) -> Option<MapDuration> {
+    match get_map_duration(map_hash, &query_system_cpuid(), factorio_version) {
+        Ok(duration) => duration,
+        Err(e) => {
+            eprintln!("Failed to read the duration of a map: {}", e);
+            None
         }
     }
+}
 
+/// Prints how long running every map is expected to take, from the
+/// duration of each map which has one.
+fn print_expected_duration<'a>(
+    maps: impl IntoIterator<Item = &'a PendingMap>,
+    interleave: bool,
+) {
     let mut expected_total_game_initialization_time = 0.0;
     let mut expected_total_tick_time = 0.0;
     let mut expected_total_benchmarking_run_overhead = 0.0;
-    for a_duration in map_durations {
-        expected_total_tick_time +=
-            a_duration.per_tick_time * f64::from(set.ticks) / 1000.0
-                * f64::from(set.runs);
+    for map in maps {
+        let a_duration = match &map.duration {
+            Some(duration) => duration,
+            None => continue,
+        };
+        let (ticks, runs) = (map.params.ticks, map.params.runs);
+        // Interleaved maps are loaded again for every run
+        let loads_per_map = if interleave { runs } else { 1 };
+        expected_total_tick_time += a_duration.per_tick_time * f64::from(ticks)
+            / 1000.0
+            * f64::from(runs);
         expected_total_benchmarking_run_overhead +=
-            a_duration.per_run_overhead_time * f64::from(set.runs);
+            a_duration.per_run_overhead_time * f64::from(runs);
         expected_total_game_initialization_time +=
-            a_duration.initialization_time;
+            a_duration.initialization_time * f64::from(loads_per_map);
     }
     let expected_total_duration = expected_total_tick_time
         + expected_total_game_initialization_time
         + expected_total_benchmarking_run_overhead;
-    let now = Instant::now();
     let hrs = (expected_total_duration / 3600.0) as u64;
     let mins = ((expected_total_duration % 3600.0) / 60.0) as u64;
     let secs = (expected_total_duration % 3600.0) % 60.0;
@@ -290,24 +790,630 @@ fn run_factorio_benchmarks_from_set(set_name: &str, set: BenchmarkSet) {
         "Expecting benchmarks to take: {}:{:02}:{:06.3}",
         hrs, mins, secs
     );
+}
+
+/// Adds runs to a map until the mean wholeUpdate is known precisely enough,
+/// or until the map used up its runs or time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveRuns {
+    /// The half width of the 95% confidence interval of the mean wholeUpdate
+    /// to reach, relative to the mean, such as 0.005 for ±0.5%.
+    pub target_precision: f64,
+    /// The most runs of a map.
+    pub max_runs: u32,
+    /// The most seconds spent benchmarking a map. Checked before launching
+    /// more runs, so a map may take a batch of runs longer.
+    pub max_seconds: Option<f64>,
+}
 
-    let mut collection_data = CollectionData::default();
-    collection_data.benchmark_name = set_name.to_string();
+impl AdaptiveRuns {
+    /// The runs to start a map with, which is at least 2 so there is a
+    /// precision to compare against the target.
+    fn first_runs(&self, runs: u32) -> u32 {
+        runs.max(2)
+    }
 
-    let info = FACTORIO_INFO.clone();
-    collection_data.factorio_version = info.version;
-    collection_data.os = info.operating_system;
-    collection_data.executable_type = info.platform;
-    collection_data.cpuid = query_system_cpuid();
+    /// Whether a map needs no more runs, given its runs so far and the
+    /// seconds spent benchmarking it.
+    fn is_done(&self, data: &BenchmarkData, seconds: f64) -> bool {
+        data.precision()
+            .is_some_and(|precision| precision <= self.target_precision)
+            || data.runs >= self.max_runs
+            || self.max_seconds.is_some_and(|max| seconds >= max)
+    }
 
-    for param in set_params {
-        let stdout =
-            run_factorio_benchmark(&factorio_executable_path(), &param)
-                .unwrap();
-        parse_stdout_for_errors(&stdout);
-        let bench_data = parse_stdout_into_benchmark_data(&stdout);
-        collection_data.benchmarks.push(bench_data);
-        collection_data.mods.extend(param.mods.clone());
+    /// How many runs to add to a map in one launch. The confidence interval
+    /// narrows with the square root of the runs, which estimates the runs
+    /// needed to reach the target.
+    fn next_batch(&self, data: &BenchmarkData) -> u32 {
+        let needed = match data.precision() {
+            Some(precision) if precision > 0.0 => {
+                let ratio = precision / self.target_precision;
+                (f64::from(data.runs) * ratio * ratio).ceil() as u32
+            }
+            _ => data.runs + 1,
+        };
+        needed
+            .saturating_sub(data.runs)
+            .max(1)
+            .min(self.max_runs.saturating_sub(data.runs))
+    }
+
+    fn print_outcome(&self, map_name: &str, data: &BenchmarkData) {
+        let precision = match data.precision() {
+            Some(precision) => precision,
+            None => return,
+        };
+        if precision <= self.target_precision {
+            println!(
+                "Map {} reached ±{:.2}% after {} runs",
+                map_name,
+                precision * 100.0,
+                data.runs
+            );
+        } else {
+            println!(
+                "Map {} stopped at ±{:.2}% after {} runs, short of the \
+                ±{:.2}% target",
+                map_name,
+                precision * 100.0,
+                data.runs,
+                self.target_precision * 100.0
+            );
+        }
+    }
+}
+
+/// Launches more runs of a map until the adaptive target or a budget is
+/// reached, appending them to the runs so far. Like the first launch and the
+/// interleaved runs, every run of a launch is kept, the warm-up of each run
+/// is left to the warmup ticks and the outlier policy. A failing launch stops
+/// adding runs, keeping the runs which succeeded.
+fn add_adaptive_runs(
+    map: &PendingMap,
+    data: &mut BenchmarkData,
+    adaptive: &AdaptiveRuns,
+    started: Instant,
+    options: &BenchmarkRunOptions,
+    progress: &mut BenchmarkProgress,
+) -> Result<(), FbhError> {
+    while !adaptive.is_done(data, started.elapsed().as_secs_f64()) {
+        let params = SimpleBenchmarkParams {
+            runs: adaptive.next_batch(data),
+            ..map.params.clone()
+        };
+        let params = SimpleBenchmarkParams {
+            timeout: map.timeout(params.ticks, params.runs),
+            ..params
+        };
+        progress.add_ticks(u64::from(params.ticks) * u64::from(params.runs));
+        match run_checked_factorio_benchmark(options, &params, Some(progress)) {
+            Ok(stdout) => {
+                append_runs(data, parse_stdout_into_benchmark_data(&stdout))
+            }
+            Err(e) => {
+                let reason = map_failure_reason(e, options)?;
+                progress.finish();
+                eprintln!(
+                    "Stopped adding runs to map {}: {}",
+                    map.name, reason
+                );
+                break;
+            }
+        }
+    }
+    progress.finish();
+    adaptive.print_outcome(&map.name, data);
+    Ok(())
+}
+
+/// A map of a set which is yet to be benchmarked.
+struct PendingMap {
+    name: String,
+    hash: String,
+    params: SimpleBenchmarkParams,
+    warmup_ticks: u32,
+    /// Whether the map passed the error checking pass, or may skip it.
+    error_checked: bool,
+    /// How long the map takes to run, from the error checking pass or an
+    /// earlier run on this system.
+    duration: Option<MapDuration>,
+    /// The timeout multiplier of the options, the set or config.ini.
+    timeout_multiplier: f64,
+    /// Why the map could not be benchmarked, once it failed.
+    failure: Option<String>,
+}
+
+impl PendingMap {
+    /// The seconds `runs` runs of `ticks` ticks are expected to take, as
+    /// long as a map which never ran if the map has no duration yet.
+    fn expected_run_time(&self, ticks: u32, runs: u32) -> f64 {
+        match self.duration {
+            Some(duration) => duration.expected_run_time(ticks, runs),
+            None => unmeasured_run_time(ticks, runs),
+        }
+    }
+
+    fn timeout(&self, ticks: u32, runs: u32) -> Option<Duration> {
+        benchmark_timeout(
+            self.expected_run_time(ticks, runs),
+            self.timeout_multiplier,
+        )
+    }
+}
+
+/// The seconds `runs` runs of `ticks` ticks of a map which never ran on this
+/// system are assumed to take, scaled from the error checking pass.
+pub(crate) fn unmeasured_run_time(ticks: u32, runs: u32) -> f64 {
+    UNMEASURED_ERROR_CHECK_TIME * f64::from(ticks) * f64::from(runs)
+        / f64::from(NUMBER_ERROR_CHECKING_TICKS * NUMBER_ERROR_CHECKING_RUNS)
+}
+
+/// How long a Factorio run expected to take `expected_seconds` may take
+/// before it is considered hung, or `None` to wait for it however long it
+/// takes.
+pub(crate) fn benchmark_timeout(
+    expected_seconds: f64,
+    multiplier: f64,
+) -> Option<Duration> {
+    if multiplier <= 0.0 {
+        return None;
+    }
+    let timeout = expected_seconds * multiplier;
+    Some(Duration::from_secs_f64(timeout.max(MINIMUM_TIMEOUT)))
+}
+
+/// Why an unfinished collection cannot be resumed with the given Factorio
+/// version, cpu and mods, if it cannot.
+fn resume_mismatch(
+    progress: &CollectionProgress,
+    factorio_version: &str,
+    cpuid: &str,
+    mods: &BTreeSet<Mod>,
+) -> Option<String> {
+    if progress.factorio_version != factorio_version {
+        return Some(format!(
+            "was ran with Factorio {}, not {}",
+            progress.factorio_version, factorio_version
+        ));
+    }
+    if progress.cpuid != cpuid {
+        return Some(format!(
+            "was ran on cpu {:?}, not {:?}",
+            progress.cpuid, cpuid
+        ));
+    }
+    let mod_hashes: BTreeSet<String> =
+        mods.iter().map(|m| m.sha1.clone()).collect();
+    if progress.mod_hashes != mod_hashes {
+        return Some("was ran with different mods".to_string());
+    }
+    None
+}
+
+/// Finds the unfinished collection of a set to resume, and the hashes of the
+/// maps already completed within it.
+fn find_resumable_collection(
+    set_name: &str,
+    mods: &BTreeSet<Mod>,
+    factorio_executable: &Path,
+    factorio_version: &str,
+    resume: bool,
+) -> Option<(u32, Vec<String>)> {
+    let collection_id =
+        get_in_progress_collection(set_name, factorio_executable)?;
+    if !resume {
+        println!(
+            "Collection {} of set {:?} was never finished, starting a new \
+            collection. Use --resume to continue it instead.",
+            collection_id, set_name
+        );
+        return None;
+    }
+    match get_collection_progress(collection_id) {
+        Ok(progress) => {
+            if let Some(mismatch) = resume_mismatch(
+                &progress,
+                factorio_version,
+                &query_system_cpuid(),
+                mods,
+            ) {
+                println!(
+                    "Collection {} of set {:?} {}, starting a new collection.",
+                    collection_id, set_name, mismatch
+                );
+                return None;
+            }
+            println!(
+                "Resuming collection {} of set {:?}, {} maps already completed",
+                collection_id,
+                set_name,
+                progress.map_hashes.len()
+            );
+            Some((collection_id, progress.map_hashes))
+        }
+        Err(e) => {
+            eprintln!(
+                "Could not resume collection {}, starting a new collection: {}",
+                collection_id, e
+            );
+            None
+        }
+    }
+}
+
+/// Runs benchmarks on the saves provided in the set. First performs a short
+/// error checking pass, and then runs the set's specified parameters. Each
+/// map's results are stored as soon as it finishes, so an interrupted set can
+/// be resumed. Returns the id of the finished collection, if there was
+/// anything to run.
+fn run_factorio_benchmarks_from_set(
+    set_name: &str,
+    set: BenchmarkSet,
+    options: &BenchmarkRunOptions,
+) -> Result<(Option<u32>, Vec<BenchmarkFailure>), FbhError> {
+    let factorio_executable = options.factorio_executable()?;
+    let factorio_info = options.factorio_info()?;
+    let timeout_multiplier = options
+        .timeout_multiplier
+        .or(set.timeout_multiplier)
+        .unwrap_or(CONFIG_FILE_SETTINGS.timeout_multiplier);
+    let resumable = find_resumable_collection(
+        set_name,
+        &set.mods,
+        &factorio_executable,
+        &factorio_info.version,
+        options.resume,
+    );
+    let completed_map_hashes = resumable
+        .as_ref()
+        .map(|(_id, hashes)| hashes.clone())
+        .unwrap_or_default();
+    let mut pending_maps = Vec::new();
+    let save_directory = if let Some(subdir) = &set.save_subdirectory {
+        fbh_save_dl_dir().join(subdir)
+    } else {
+        fbh_save_dl_dir()
+    };
+    if let Ok(dir_list) = std::fs::read_dir(fbh_mod_use_dir()) {
+        for dir_entry in dir_list.flatten() {
+            if let Err(e) = std::fs::remove_file(dir_entry.path()) {
+                eprintln!("Failed to remove a mod from the staging directory!");
+                return Err(e.into());
+            }
+        }
+    }
+    for indiv_mod in &set.mods {
+        let mod_filename = indiv_mod.zip_file_name();
+        let cached_mods_dir = fbh_mod_dl_dir().join(&mod_filename);
+        let mods_use_dir = fbh_mod_use_dir().join(&mod_filename);
+        if !cached_mods_dir.exists() {
+            return Err(FbhError::ModPortal(format!(
+                "mod {:?} is missing after downloading",
+                cached_mods_dir
+            )));
+        }
+        if let Err(e) = std::fs::write(&mods_use_dir, read(&cached_mods_dir)?) {
+            eprintln!("Failed to copy mod {:?} for use.", &mod_filename);
+            return Err(e.into());
+        }
+    }
+    for map in &set.maps {
+        let map_hash = sha256sum(save_directory.join(&map.name));
+        if completed_map_hashes.contains(&map_hash) {
+            println!("Skipping already completed map {}", map.name);
+            continue;
+        }
+        let previous_duration =
+            previous_benchmark_duration(&map_hash, &factorio_info.version);
+        pending_maps.push(PendingMap {
+            name: map.name.clone(),
+            hash: map_hash,
+            params: SimpleBenchmarkParams {
+                scheduling: options.scheduling.clone(),
+                ..SimpleBenchmarkParams::new(
+                    save_directory.join(&map.name),
+                    set.map_ticks(map),
+                    match &options.adaptive {
+                        Some(adaptive) => {
+                            adaptive.first_runs(set.map_runs(map))
+                        }
+                        None => set.map_runs(map),
+                    },
+                )
+            },
+            warmup_ticks: set.map_warmup_ticks(map),
+            error_checked: previous_duration.is_some()
+                && options.skip_error_check,
+            duration: previous_duration,
+            timeout_multiplier,
+            failure: None,
+        });
+    }
+    if pending_maps.is_empty() {
+        if let Some((collection_id, _)) = resumable {
+            println!("All maps of set {:?} were already completed", set_name);
+            clear_in_progress_collection(set_name, &factorio_executable);
+            return Ok((Some(collection_id), Vec::new()));
+        }
+        return Ok((None, Vec::new()));
+    }
+    if pending_maps.iter().all(|m| m.duration.is_some())
+        && pending_maps.iter().any(|m| !m.error_checked)
+    {
+        println!("From earlier runs of these maps on this system:");
+        print_expected_duration(&pending_maps, options.interleave);
+    }
+    for map in &mut pending_maps {
+        if map.error_checked {
+            println!(
+                "Skipping error checking of map {}, it ran cleanly before",
+                map.name
+            );
+            continue;
+        }
+        let param = SimpleBenchmarkParams {
+            ticks: NUMBER_ERROR_CHECKING_TICKS,
+            runs: NUMBER_ERROR_CHECKING_RUNS,
+            timeout: map.timeout(
+                NUMBER_ERROR_CHECKING_TICKS,
+                NUMBER_ERROR_CHECKING_RUNS,
+            ),
+            ..map.params.clone()
+        };
+        let stdout = match run_checked_factorio_benchmark(options, &param, None)
+        {
+            Ok(stdout) => stdout,
+            Err(e) => {
+                let reason = map_failure_reason(e, options)?;
+                eprintln!(
+                    "Error checking of map {} failed: {}",
+                    map.name, reason
+                );
+                map.failure = Some(reason);
+                continue;
+            }
+        };
+        map.error_checked = true;
+        let time_breakdown =
+            parse_stdout_for_benchmark_time_breakdown(&stdout, &param);
+        if let Some(time) = time_breakdown {
+            record_benchmark_duration(&time, &map.hash, &factorio_info.version);
+            map.duration = Some(time);
+        }
+    }
+
+    print_expected_duration(
+        pending_maps.iter().filter(|m| m.failure.is_none()),
+        options.interleave,
+    );
+    let now = Instant::now();
+
+    let info = factorio_info.clone();
+    let mut collection_data = CollectionData {
+        benchmark_name: set_name.to_string(),
+        factorio_version: info.version,
+        os: info.operating_system,
+        executable_type: info.platform,
+        cpuid: query_system_cpuid(),
+        environment: Some(query_system_environment()),
+        mods: set.mods.clone(),
+        cpu_affinity: options
+            .scheduling
+            .cpu_affinity
+            .as_ref()
+            .map(|cpus| cpus.to_string())
+            .unwrap_or_default(),
+        nice: options.scheduling.nice,
+        ..Default::default()
+    };
+
+    let collection_id = if let Some((collection_id, _)) = resumable {
+        collection_id
+    } else {
+        insert_collection(&collection_data)?
+    };
+    set_in_progress_collection(set_name, &factorio_executable, collection_id);
+
+    let mut progress = BenchmarkProgress::new(
+        pending_maps
+            .iter()
+            .filter(|m| m.failure.is_none())
+            .map(|m| u64::from(m.params.ticks) * u64::from(m.params.runs))
+            .sum(),
+    );
+    if options.interleave {
+        let mut merged_data: Vec<BenchmarkData> = pending_maps
+            .iter()
+            .map(|map| BenchmarkData {
+                warmup_ticks: map.warmup_ticks,
+                outlier_policy: set.outlier_policy,
+                ..BenchmarkData::default()
+            })
+            .collect();
+        let mut benchmark_seconds = vec![0.0; pending_maps.len()];
+        let mut stored = vec![false; pending_maps.len()];
+        // Maps with fewer runs than others sit out the last interleaved runs,
+        // and adaptive maps keep going until they are precise enough
+        let last_run = pending_maps
+            .iter()
+            .map(|m| m.params.runs)
+            .chain(options.adaptive.map(|adaptive| adaptive.max_runs))
+            .max()
+            .unwrap_or(0);
+        let wants_run =
+            |map: &PendingMap, merged: &BenchmarkData, seconds, run_index| {
+                map.failure.is_none()
+                    && run_index <= last_run
+                    && (run_index <= map.params.runs
+                        || options.adaptive.is_some_and(|adaptive| {
+                            !adaptive.is_done(merged, seconds)
+                        }))
+            };
+        for run_index in 1..=last_run {
+            let wanted: Vec<bool> = pending_maps
+                .iter()
+                .zip(&merged_data)
+                .zip(&benchmark_seconds)
+                .map(|((map, merged), seconds)| {
+                    wants_run(map, merged, *seconds, run_index)
+                })
+                .collect();
+            if !wanted.contains(&true) {
+                break;
+            }
+            progress.finish();
+            if options.adaptive.is_some() {
+                println!(
+                    "Interleaved run {} of at most {}",
+                    run_index, last_run
+                );
+            } else {
+                println!("Interleaved run {} of {}", run_index, last_run);
+            }
+            for (index, map) in pending_maps.iter_mut().enumerate() {
+                if !wanted[index] {
+                    continue;
+                }
+                let mut single_run = SimpleBenchmarkParams {
+                    runs: 1,
+                    ..map.params.clone()
+                };
+                single_run.timeout = map.timeout(single_run.ticks, 1);
+                if run_index > map.params.runs {
+                    progress.add_ticks(u64::from(single_run.ticks));
+                }
+                let launched = Instant::now();
+                let stdout = match run_checked_factorio_benchmark(
+                    options,
+                    &single_run,
+                    Some(&mut progress),
+                ) {
+                    Ok(stdout) => stdout,
+                    Err(e) => {
+                        let reason = map_failure_reason(e, options)?;
+                        progress.finish();
+                        eprintln!("Map {} failed: {}", map.name, reason);
+                        map.failure = Some(reason);
+                        continue;
+                    }
+                };
+                benchmark_seconds[index] += launched.elapsed().as_secs_f64();
+                if let Some(duration) =
+                    parse_stdout_for_benchmark_time_breakdown(
+                        &stdout,
+                        &single_run,
+                    )
+                {
+                    record_benchmark_duration(
+                        &duration,
+                        &map.hash,
+                        &factorio_info.version,
+                    );
+                }
+                merge_interleaved_run(
+                    &mut merged_data[index],
+                    parse_stdout_into_benchmark_data(&stdout),
+                    run_index,
+                );
+            }
+            // Store every map which has all its runs, so an interrupted set
+            // can be resumed without running them again
+            for (index, map) in pending_maps.iter().enumerate() {
+                if stored[index]
+                    || map.failure.is_some()
+                    || wants_run(
+                        map,
+                        &merged_data[index],
+                        benchmark_seconds[index],
+                        run_index + 1,
+                    )
+                {
+                    continue;
+                }
+                let mut bench_data = std::mem::take(&mut merged_data[index]);
+                bench_data.achieved_precision = bench_data.precision();
+                if let Some(adaptive) = &options.adaptive {
+                    progress.finish();
+                    adaptive.print_outcome(&map.name, &bench_data);
+                }
+                insert_benchmark(collection_id, &bench_data)?;
+                collection_data.benchmarks.push(bench_data);
+                stored[index] = true;
+            }
+        }
+    } else {
+        for map in &mut pending_maps {
+            if map.failure.is_some() {
+                continue;
+            }
+            let param = SimpleBenchmarkParams {
+                timeout: map.timeout(map.params.ticks, map.params.runs),
+                ..map.params.clone()
+            };
+            let started = Instant::now();
+            let stdout = match run_checked_factorio_benchmark(
+                options,
+                &param,
+                Some(&mut progress),
+            ) {
+                Ok(stdout) => stdout,
+                Err(e) => {
+                    let reason = map_failure_reason(e, options)?;
+                    progress.finish();
+                    eprintln!("Map {} failed: {}", map.name, reason);
+                    map.failure = Some(reason);
+                    continue;
+                }
+            };
+            if let Some(duration) =
+                parse_stdout_for_benchmark_time_breakdown(&stdout, &param)
+            {
+                record_benchmark_duration(
+                    &duration,
+                    &map.hash,
+                    &factorio_info.version,
+                );
+            }
+            let mut bench_data = parse_stdout_into_benchmark_data(&stdout);
+            bench_data.warmup_ticks = map.warmup_ticks;
+            bench_data.outlier_policy = set.outlier_policy;
+            if let Some(adaptive) = &options.adaptive {
+                add_adaptive_runs(
+                    map,
+                    &mut bench_data,
+                    adaptive,
+                    started,
+                    options,
+                    &mut progress,
+                )?;
+            }
+            bench_data.achieved_precision = bench_data.precision();
+            insert_benchmark(collection_id, &bench_data)?;
+            collection_data.benchmarks.push(bench_data);
+        }
+    }
+
+    progress.finish();
+
+    for map in &pending_maps {
+        if let Some(reason) = &map.failure {
+            let failure = BenchmarkFailure {
+                map_name: map.name.clone(),
+                map_hash: map.hash.clone(),
+                reason: reason.clone(),
+            };
+            insert_benchmark_failure(collection_id, &failure)?;
+            collection_data.failures.push(failure);
+        }
+    }
+    if !collection_data.failures.is_empty() {
+        eprintln!(
+            "{} of {} maps of set {:?} failed and have no results",
+            collection_data.failures.len(),
+            pending_maps.len(),
+            set_name
+        );
     }
 
     let total_duration = now.elapsed().as_secs_f64();
@@ -315,7 +1421,8 @@ fn run_factorio_benchmarks_from_set(set_name: &str, set: BenchmarkSet) {
     let mins = ((total_duration % 3600.0) / 60.0) as u64;
     let secs = (total_duration % 3600.0) % 60.0;
     println!("Benchmarks took: {}:{:02}:{:06.3}", hrs, mins, secs);
-    upload_to_db(collection_data);
+    clear_in_progress_collection(set_name, &factorio_executable);
+    Ok((Some(collection_id), collection_data.failures))
 }
 
 pub fn parse_stdout_for_verbose_data(stdout: &str) -> Vec<String> {
@@ -343,10 +1450,44 @@ pub fn parse_stdout_for_verbose_data(stdout: &str) -> Vec<String> {
     verbose_data
 }
 
+/// Appends the data of a single run benchmark to the runs of the same map
+/// merged so far, renumbering its verbose rows as run `run_index`.
+fn merge_interleaved_run(
+    merged: &mut BenchmarkData,
+    run: BenchmarkData,
+    run_index: u32,
+) {
+    if merged.runs == 0 {
+        merged.map_name = run.map_name;
+        merged.map_hash = run.map_hash;
+        merged.ticks = run.ticks;
+    }
+    for row in run.verbose_data {
+        let timings = row.rsplit_once(',').map_or("", |(t, _)| t);
+        merged
+            .verbose_data
+            .push(format!("{},{}", timings, run_index));
+    }
+    merged.runs = run_index;
+}
+
+/// Appends every run of `batch` to the runs of the same map so far,
+/// renumbering them to follow the last run.
+fn append_runs(data: &mut BenchmarkData, batch: BenchmarkData) {
+    for row in batch.verbose_data {
+        if let Some((timings, run)) = row.rsplit_once(',') {
+            let run = run.trim().parse::<u32>().unwrap_or(1);
+            data.verbose_data
+                .push(format!("{},{}", timings, data.runs + run));
+        }
+    }
+    data.runs += batch.runs;
+}
+
 /// Parses stdout and structures it into a BenchmarkData
-fn parse_stdout_into_benchmark_data(stdout: &str) -> BenchmarkData {
+pub fn parse_stdout_into_benchmark_data(stdout: &str) -> BenchmarkData {
     trace!("stdout: {}", stdout);
-    let verbose_data = parse_stdout_for_verbose_data(&stdout);
+    let verbose_data = parse_stdout_for_verbose_data(stdout);
     let mut ticks = 0;
     let mut runs = 0;
     let mut map_path = PathBuf::new();
@@ -398,20 +1539,21 @@ fn parse_stdout_into_benchmark_data(stdout: &str) -> BenchmarkData {
         runs,
         ticks,
         verbose_data,
+        ..Default::default()
     }
 }
 
 pub fn parse_stdout_for_execution_time(stdout: &str) -> Option<f64> {
     let start =
-        parse_logline_time_to_f64(&stdout, &INITIALIZATION_TIME_PATTERN)?;
-    let end = parse_logline_time_to_f64(&stdout, &TOTAL_TIME_PATTERN)?;
+        parse_logline_time_to_f64(stdout, &INITIALIZATION_TIME_PATTERN)?;
+    let end = parse_logline_time_to_f64(stdout, &TOTAL_TIME_PATTERN)?;
     Some(end - start)
 }
 
 fn setup_mod_directory(
     mod_list: &[Mod],
     mod_dir: &Path,
-) -> std::io::Result<()> {
+) -> Result<(), FbhError> {
     let _ignore_err = std::fs::remove_dir_all(fbh_mod_use_dir());
     for indiv_mod in mod_list {
         let p = mod_dir.join(&indiv_mod.file_name);
@@ -420,7 +1562,11 @@ fn setup_mod_directory(
             if computed_sha1 == indiv_mod.sha1 {
                 std::fs::copy(p, mod_dir.join(&indiv_mod.file_name))?;
             } else {
-                panic!("Mod {:?} had a mismatched checksum!", indiv_mod);
+                return Err(FbhError::HashMismatch {
+                    name: indiv_mod.name.clone(),
+                    expected: indiv_mod.sha1.clone(),
+                    found: computed_sha1,
+                });
             }
         }
     }
@@ -469,31 +1615,15 @@ pub fn parse_stdout_for_factorio_version(
     None
 }
 
-fn parse_stdout_for_save_version(stdout: &str) -> Option<FactorioVersion> {
-    for line in stdout.lines().rev() {
-        if line.contains("Map version ") {
-            // get rid of everything before the version
-            let trim_begin = line.split("Map version ").nth(1)?;
-            let version_str = trim_begin.split('-').next()?;
-            let version = version_str.try_into().ok();
-            return version;
-        }
-    }
-    eprintln!("Failure parsing for save version");
-    None
-}
-
-/// Given a path to a Factorio excutable and a path to a map, runs a Factorio
-/// benchmark, optionally returning STDOUT.
-pub fn run_factorio_benchmark<P: AsRef<std::ffi::OsStr>>(
+/// Builds the command that runs a Factorio benchmark with `params`, without
+/// running it.
+fn build_benchmark_command<P: AsRef<std::ffi::OsStr>>(
     factorio_exe: P,
     params: &SimpleBenchmarkParams,
-) -> Option<String> {
-    if let Err(e) = setup_mod_directory(&params.mods, &params.mod_directory) {
-        eprintln!("Failed to setup mod directory {}", e);
-        return None;
-    };
-    let run_bench_cmd = Command::new(factorio_exe)
+) -> Result<Command, FbhError> {
+    let mut command = Command::new(&factorio_exe);
+    params.scheduling.apply(&mut command)?;
+    command
         .arg("--benchmark")
         .arg(&params.map_path)
         .arg("--benchmark-ticks")
@@ -503,18 +1633,110 @@ pub fn run_factorio_benchmark<P: AsRef<std::ffi::OsStr>>(
         .arg("--benchmark-verbose")
         .arg(STANDARD_VERBOSE_TIMINGS)
         .arg("--mod-directory")
-        .arg(&params.mod_directory)
-        .output();
-    if run_bench_cmd.is_err() {
-        eprintln!(
-            "An error occurred when attempting to run Factorio: {:?}",
-            run_bench_cmd
+        .arg(&params.mod_directory);
+    Ok(command)
+}
+
+/// Given a path to a Factorio excutable and a path to a map, runs a Factorio
+/// benchmark, returning STDOUT.
+pub fn run_factorio_benchmark<P: AsRef<std::ffi::OsStr>>(
+    factorio_exe: P,
+    params: &SimpleBenchmarkParams,
+) -> Result<String, FbhError> {
+    run_factorio_benchmark_with_progress(factorio_exe, params, None)
+}
+
+/// Runs a Factorio benchmark like `run_factorio_benchmark`, updating
+/// `progress` as Factorio reports each tick.
+pub fn run_factorio_benchmark_with_progress<P: AsRef<std::ffi::OsStr>>(
+    factorio_exe: P,
+    params: &SimpleBenchmarkParams,
+    mut progress: Option<&mut BenchmarkProgress>,
+) -> Result<String, FbhError> {
+    if let Err(e) = setup_mod_directory(&params.mods, &params.mod_directory) {
+        eprintln!("Failed to setup mod directory");
+        return Err(e);
+    };
+    let mut child = match build_benchmark_command(&factorio_exe, params)?
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            return Err(FbhError::Factorio(format!(
+                "could not launch {:?}: {}",
+                factorio_exe.as_ref(),
+                e
+            )))
+        }
+    };
+    if let Some(progress) = progress.as_mut() {
+        let map_name = params.map_path.file_name().unwrap_or_default();
+        progress.start_map(
+            &map_name.to_string_lossy(),
+            params.ticks,
+            params.runs,
         );
-        None
-    } else {
-        let run_bench_cmd = run_bench_cmd.ok()?;
-        Some(String::from_utf8_lossy(&run_bench_cmd.stdout).replace("\r", ""))
     }
+    let reader = BufReader::new(child.stdout.take().unwrap());
+    let child = Arc::new(Mutex::new(child));
+    // Factorio closes stdout when it exits, so a hung Factorio is killed by
+    // a watchdog to end the read loop below
+    let (finished_sender, finished_receiver) = mpsc::channel::<()>();
+    let watchdog = params.timeout.map(|timeout| {
+        let child = Arc::clone(&child);
+        thread::spawn(move || {
+            if finished_receiver.recv_timeout(timeout)
+                == Err(RecvTimeoutError::Timeout)
+            {
+                let _ = child.lock().unwrap().kill();
+                true
+            } else {
+                false
+            }
+        })
+    });
+    // Read line by line so progress can be shown while Factorio runs
+    let mut stdout = String::new();
+    for line in reader.split(b'\n') {
+        let line = String::from_utf8_lossy(&line?).replace('\r', "");
+        if let Some(progress) = progress.as_mut() {
+            progress.observe_line(&line);
+        }
+        stdout.push_str(&line);
+        stdout.push('\n');
+    }
+    drop(finished_sender);
+    let timed_out = match watchdog {
+        Some(watchdog) => watchdog.join().unwrap_or(false),
+        None => false,
+    };
+    let status = child.lock().unwrap().wait()?;
+    if let Some(progress) = progress {
+        progress.finish_map();
+    }
+    if timed_out {
+        return Err(FbhError::Timeout(format!(
+            "{} did not finish within {}s and was killed",
+            params.map_path.display(),
+            params.timeout.unwrap_or_default().as_secs()
+        )));
+    }
+    check_exit_status(status, &stdout)?;
+    Ok(stdout)
+}
+
+/// Fails a benchmark whose Factorio exited unsuccessfully, with the error
+/// Factorio logged if there is one.
+fn check_exit_status(status: ExitStatus, stdout: &str) -> Result<(), FbhError> {
+    if status.success() {
+        return Ok(());
+    }
+    Err(FbhError::Factorio(match find_factorio_error(stdout) {
+        Some(error) => format!("exited with {}:\n{}", status, error),
+        None => format!("exited with {}", status),
+    }))
 }
 
 #[cfg(test)]
@@ -533,4 +1755,239 @@ mod tests {
         );
         assert_eq!(sv, FactorioVersion::new(1, 1, 107));
     }
+
+    #[test]
+    fn test_find_factorio_error() {
+        let clean = "   0.512 Loading map /saves/error-test.zip: 1234 bytes.\n\
+            \x20  0.900 Script @__error-log__/control.lua:3: no errors here\n\
+            run 1:\n\
+            tick,wholeUpdate\n\
+            t0,1000\n";
+        assert_eq!(find_factorio_error(clean), None);
+        assert!(parse_stdout_for_errors(clean).is_ok());
+
+        let failed = "   0.512 Loading map /saves/base.zip: 1234 bytes.\n\
+            \x20  1.204 Error MainLoop.cpp:1285: Failed to load map:\n\
+            Error while running event mod::on_load()\n\
+            stack traceback:\n\
+            \x20  1.300 Goodbye\n";
+        assert_eq!(
+            find_factorio_error(failed).unwrap(),
+            "1.204 Error MainLoop.cpp:1285: Failed to load map:\n\
+            Error while running event mod::on_load()\n\
+            stack traceback:"
+        );
+        assert!(parse_stdout_for_errors(failed).is_err());
+    }
+
+    #[test]
+    fn test_adaptive_runs() {
+        let runs = |means: &[u32]| BenchmarkData {
+            runs: means.len() as u32,
+            verbose_data: means
+                .iter()
+                .enumerate()
+                .map(|(run, mean)| format!("1,{},0,{}", mean, run + 1))
+                .collect(),
+            ..Default::default()
+        };
+        let adaptive = AdaptiveRuns {
+            target_precision: 0.05,
+            max_runs: 10,
+            max_seconds: Some(60.0),
+        };
+        assert_eq!((adaptive.first_runs(1), adaptive.first_runs(20)), (2, 20));
+
+        // ±12.7% needs 6.5 times the runs for ±5%, capped at 10 runs
+        let mut data = runs(&[99, 101]);
+        assert!(!adaptive.is_done(&data, 0.0));
+        assert!(adaptive.is_done(&data, 60.0));
+        assert_eq!(adaptive.next_batch(&data), 8);
+        append_runs(&mut data, runs(&[100, 100, 99, 101, 100]));
+        assert_eq!(data.runs, 7);
+        assert_eq!(data.verbose_data[6], "1,100,0,7");
+        assert!(adaptive.is_done(&data, 0.0));
+
+        // The cold first run of the first launch and of later launches are
+        // all kept alike
+        let mut launches = runs(&[300, 100]);
+        append_runs(&mut launches, runs(&[300, 101]));
+        assert_eq!(launches.runs, 4);
+        assert_eq!(
+            launches.verbose_data,
+            vec!["1,300,0,1", "1,100,0,2", "1,300,0,3", "1,101,0,4"]
+        );
+
+        let noisy = runs(&[50, 150, 60, 140, 100, 70, 130, 90, 110]);
+        assert_eq!(adaptive.next_batch(&noisy), 1);
+        assert!(adaptive.is_done(&runs(&[50; 10]), 0.0));
+    }
+
+    #[test]
+    fn test_resume_mismatch() {
+        let mods: BTreeSet<Mod> = vec![Mod::new("rso-mod", "", "6.2.3", "abc")]
+            .into_iter()
+            .collect();
+        let progress = CollectionProgress {
+            factorio_version: "1.1.107".to_string(),
+            cpuid: "cpu".to_string(),
+            mod_hashes: vec!["abc".to_string()].into_iter().collect(),
+            map_hashes: vec![],
+        };
+        assert_eq!(resume_mismatch(&progress, "1.1.107", "cpu", &mods), None);
+        assert!(resume_mismatch(&progress, "1.1.109", "cpu", &mods).is_some());
+        assert!(
+            resume_mismatch(&progress, "1.1.107", "other cpu", &mods).is_some()
+        );
+        assert!(
+            resume_mismatch(&progress, "1.1.107", "cpu", &BTreeSet::new())
+                .is_some()
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_resolve_factorio_installs() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir()
+            .join(format!("fbh-install-test-{}", std::process::id()));
+        let executable = dir.join("bin").join("x64").join("factorio");
+        std::fs::create_dir_all(executable.parent().unwrap()).unwrap();
+        std::fs::write(
+            &executable,
+            "#!/bin/sh\n\
+            echo 'Version: 1.1.107 (build 60183, linux64, headless)'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(
+            &executable,
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+
+        let installs = resolve_factorio_installs(&[
+            dir.to_string_lossy().to_string(),
+            executable.to_string_lossy().to_string(),
+        ])
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(installs.len(), 2);
+        assert_eq!(installs[0].executable, executable);
+        assert_eq!(installs[1].info.version, "1.1.107");
+        assert_eq!(installs[1].info.operating_system, "linux64");
+        assert_eq!(installs[1].info.platform, "headless");
+        assert!(matches!(
+            resolve_factorio_installs(&["not-a-version".to_owned()]),
+            Err(FbhError::InvalidInput(_))
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_check_exit_status() {
+        use std::os::unix::process::ExitStatusExt;
+        let stdout = "   1.000 Error Util.cpp:83: Map is corrupted\n";
+        assert!(check_exit_status(ExitStatus::from_raw(0), stdout).is_ok());
+        match check_exit_status(ExitStatus::from_raw(1 << 8), stdout) {
+            Err(FbhError::Factorio(msg)) => {
+                assert!(msg.starts_with("exited with exit status: 1"));
+                assert!(msg.ends_with("Map is corrupted"));
+            }
+            other => panic!("expected a Factorio error, got {:?}", other),
+        }
+        assert!(check_exit_status(ExitStatus::from_raw(1 << 8), "").is_err());
+    }
+
+    #[test]
+    fn test_sets_outcome_finish() {
+        assert!(SetsOutcome::default().finish().is_ok());
+        let mut outcome = SetsOutcome::default();
+        outcome.collections.push(("set".to_owned(), 1));
+        outcome.map_failures.push((
+            "set".to_owned(),
+            BenchmarkFailure {
+                map_name: "map.zip".to_owned(),
+                map_hash: String::new(),
+                reason: "killed".to_owned(),
+            },
+        ));
+        assert!(matches!(outcome.finish(), Err(FbhError::Factorio(_))));
+    }
+
+    #[test]
+    fn test_benchmark_timeout() {
+        let duration = MapDuration {
+            initialization_time: 10.0,
+            per_tick_time: 2.0,
+            per_run_overhead_time: 1.0,
+        };
+        // 10s to load, then 2 runs of 1s overhead and 1000 ticks of 2ms
+        assert_eq!(duration.expected_run_time(1000, 2), 16.0);
+        assert_eq!(benchmark_timeout(16.0, 0.0), None);
+        assert_eq!(
+            unmeasured_run_time(
+                NUMBER_ERROR_CHECKING_TICKS * 2,
+                NUMBER_ERROR_CHECKING_RUNS
+            ),
+            UNMEASURED_ERROR_CHECK_TIME * 2.0
+        );
+        assert_eq!(
+            benchmark_timeout(16.0, 3.0),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            benchmark_timeout(1000.0, 3.0),
+            Some(Duration::from_secs(3000))
+        );
+    }
+
+    #[test]
+    fn test_merge_interleaved_run() {
+        let run = |timing: &str| BenchmarkData {
+            map_name: "TEST".to_owned(),
+            map_hash: "0".repeat(64),
+            runs: 1,
+            ticks: 2,
+            verbose_data: vec![
+                format!("1,{},0,1", timing),
+                format!("2,{},0,1", timing),
+            ],
+            ..Default::default()
+        };
+        let mut merged = BenchmarkData::default();
+        merge_interleaved_run(&mut merged, run("5"), 1);
+        merge_interleaved_run(&mut merged, run("7"), 2);
+        assert_eq!(merged.map_name, "TEST");
+        assert_eq!((merged.runs, merged.ticks), (2, 2));
+        assert_eq!(
+            merged.verbose_data,
+            vec!["1,5,0,1", "2,5,0,1", "1,7,0,2", "2,7,0,2"]
+        );
+    }
+
+    #[test]
+    fn test_build_benchmark_command() {
+        let params = SimpleBenchmarkParams {
+            mod_directory: PathBuf::from("mods"),
+            ..SimpleBenchmarkParams::new(PathBuf::from("map.zip"), 600, 3)
+        };
+        let command = build_benchmark_command("factorio", &params).unwrap();
+        assert_eq!(command.get_program(), "factorio");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            vec![
+                "--benchmark",
+                "map.zip",
+                "--benchmark-ticks",
+                "600",
+                "--benchmark-runs",
+                "3",
+                "--benchmark-verbose",
+                STANDARD_VERBOSE_TIMINGS,
+                "--mod-directory",
+                "mods",
+            ]
+        );
+    }
 }
@@ -0,0 +1,478 @@
+//! Bundles of benchmark sets with every map and mod they need, for sharing
+//! sets without master.json, Backblaze, or the mod portal.
+//!
+//! A bundle is a tar archive of a `manifest.json` and the maps and mods laid
+//! out the same as a mirror, so an extracted bundle can also be used as a
+//! mirror root.
+
+use crate::error::FbhError;
+use crate::procedure_file::check_procedures_writable;
+use crate::procedure_file::get_metas_from_meta;
+use crate::procedure_file::get_sets_from_meta;
+use crate::procedure_file::read_benchmark_set_from_file;
+use crate::procedure_file::read_meta_from_file;
+use crate::procedure_file::write_benchmark_set_to_file;
+use crate::procedure_file::write_meta_to_file;
+use crate::procedure_file::ProcedureInteractive;
+use crate::procedure_file::ProcedureOverwrite;
+use crate::util::common::FACTORIO_BENCHMARK_HELPER_VERSION;
+use crate::util::download_benchmark_deps_parallel;
+use crate::util::fbh_cache_path;
+use crate::util::fbh_mod_dl_dir;
+use crate::util::fbh_save_dl_dir;
+use crate::util::mirror::mirror_mod_path;
+use crate::util::mirror::mirror_save_path;
+use crate::util::sha1sum;
+use crate::util::sha256sum;
+use crate::util::BenchmarkSet;
+use crate::util::ProcedureFileKind;
+use serde::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+const MANIFEST_NAME: &str = "manifest.json";
+
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct BundleManifest {
+    /// The version of the benchmark helper that exported the bundle.
+    pub helper_version: String,
+    pub benchmark_sets: BTreeMap<String, BenchmarkSet>,
+    pub meta_sets: BTreeMap<String, BTreeSet<String>>,
+    pub files: Vec<BundleFile>,
+}
+
+/// A map or mod within a bundle. Maps are hashed with sha256 and mods with
+/// sha1, the same as in benchmark sets.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BundleFile {
+    /// The path within the bundle, such as saves/map.zip or mods/mod.zip.
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+}
+
+impl BundleFile {
+    fn verify(&self, file: &Path) -> Result<(), FbhError> {
+        let (expected, found) = match (&self.sha256, &self.sha1) {
+            (Some(sha256), _) => (sha256, sha256sum(file)),
+            (None, Some(sha1)) => (sha1, sha1sum(&file)),
+            (None, None) => {
+                return Err(FbhError::InvalidInput(format!(
+                    "{} has no hash in the bundle manifest",
+                    self.path
+                )))
+            }
+        };
+        if *expected != found {
+            return Err(FbhError::HashMismatch {
+                name: self.path.clone(),
+                expected: expected.clone(),
+                found,
+            });
+        }
+        Ok(())
+    }
+
+    /// Where the file is installed to from a bundle.
+    fn install_path(&self) -> PathBuf {
+        if let Some(save) = self.path.strip_prefix("saves/") {
+            fbh_save_dl_dir().join(save)
+        } else {
+            fbh_mod_dl_dir().join(self.path.trim_start_matches("mods/"))
+        }
+    }
+}
+
+/// Exports the benchmark set or meta set `name`, along with every set it
+/// contains, to a bundle at `path`. Any missing maps or mods are downloaded
+/// first.
+pub fn export_bundle(name: &str, path: &Path) -> Result<(), FbhError> {
+    let mut manifest = BundleManifest {
+        helper_version: FACTORIO_BENCHMARK_HELPER_VERSION.to_owned(),
+        ..BundleManifest::default()
+    };
+    let kinds = [ProcedureFileKind::Master, ProcedureFileKind::Local];
+    if let Some(kind) = kinds
+        .iter()
+        .find(|&kind| read_meta_from_file(name, kind.clone()).is_some())
+    {
+        manifest.benchmark_sets =
+            get_sets_from_meta(name.to_owned(), kind.clone())
+                .into_iter()
+                .collect();
+        let mut meta_names = get_metas_from_meta(name.to_owned(), kind.clone());
+        meta_names.push(name.to_owned());
+        for meta_name in meta_names {
+            if let Some(members) = read_meta_from_file(&meta_name, kind.clone())
+            {
+                manifest.meta_sets.insert(meta_name, members);
+            }
+        }
+    } else if let Some(set) = kinds
+        .iter()
+        .find_map(|kind| read_benchmark_set_from_file(name, kind.clone()))
+    {
+        manifest.benchmark_sets.insert(name.to_owned(), set);
+    } else {
+        return Err(FbhError::InvalidInput(format!(
+            "Could not find a benchmark set or meta set named {:?}",
+            name
+        )));
+    }
+
+    let sets: HashMap<String, BenchmarkSet> = manifest
+        .benchmark_sets
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    download_benchmark_deps_parallel(&sets)?;
+
+    let mut sources: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for set in manifest.benchmark_sets.values() {
+        let subdir = set.save_subdirectory.as_deref();
+        for map in &set.maps {
+            let source = fbh_save_dl_dir()
+                .join(subdir.unwrap_or_else(|| Path::new("")))
+                .join(&map.name);
+            let bundle_path = mirror_save_path(subdir, &map.name);
+            if let Entry::Vacant(entry) = sources.entry(bundle_path.clone()) {
+                manifest.files.push(BundleFile {
+                    path: bundle_path,
+                    sha256: Some(sha256sum(&source)),
+                    sha1: None,
+                });
+                entry.insert(source);
+            }
+        }
+        for indiv_mod in &set.mods {
+            let file_name = indiv_mod.zip_file_name();
+            let source = fbh_mod_dl_dir().join(&file_name);
+            let bundle_path = mirror_mod_path(&file_name);
+            if let Entry::Vacant(entry) = sources.entry(bundle_path.clone()) {
+                manifest.files.push(BundleFile {
+                    path: bundle_path,
+                    sha256: None,
+                    sha1: Some(sha1sum(&source)),
+                });
+                entry.insert(source);
+            }
+        }
+    }
+    write_bundle(File::create(path)?, &manifest, &sources)?;
+    println!(
+        "Exported {} benchmark sets and {} files to {:?}",
+        manifest.benchmark_sets.len(),
+        manifest.files.len(),
+        path
+    );
+    Ok(())
+}
+
+fn write_bundle<W: Write>(
+    writer: W,
+    manifest: &BundleManifest,
+    sources: &BTreeMap<String, PathBuf>,
+) -> Result<(), FbhError> {
+    let mut builder = tar::Builder::new(writer);
+    let manifest_json = serde_json::to_vec_pretty(manifest).unwrap();
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST_NAME, &*manifest_json)?;
+    for (bundle_path, source) in sources {
+        builder.append_path_with_name(source, bundle_path)?;
+    }
+    builder.into_inner()?.flush()?;
+    Ok(())
+}
+
+/// Imports a bundle, verifying every file against the manifest before
+/// installing the maps and mods into the caches and the sets into local.json.
+pub fn import_bundle(
+    path: &Path,
+    overwrite: ProcedureOverwrite,
+    interactive: ProcedureInteractive,
+) -> Result<(), FbhError> {
+    let staging = fbh_cache_path().join("bundle-import");
+    let _ignore_err = std::fs::remove_dir_all(&staging);
+    let result =
+        unpack_bundle(File::open(path)?, &staging).and_then(|manifest| {
+            // Refuse the import before any cached file is replaced
+            check_procedures_writable(
+                manifest.benchmark_sets.keys(),
+                manifest.meta_sets.keys(),
+                overwrite,
+                &ProcedureFileKind::Local,
+                interactive,
+            )?;
+            install_bundle(&manifest, &staging).map(|_| manifest)
+        });
+    let _ignore_err = std::fs::remove_dir_all(&staging);
+    let manifest = result?;
+    for (name, set) in manifest.benchmark_sets.clone() {
+        write_benchmark_set_to_file(
+            &name,
+            set,
+            overwrite,
+            ProcedureFileKind::Local,
+            interactive,
+        )?;
+    }
+    for (name, members) in manifest.meta_sets.clone() {
+        write_meta_to_file(
+            &name,
+            members,
+            overwrite,
+            ProcedureFileKind::Local,
+        )?;
+    }
+    println!(
+        "Imported {} benchmark sets and {} files from {:?}",
+        manifest.benchmark_sets.len(),
+        manifest.files.len(),
+        path
+    );
+    Ok(())
+}
+
+/// Extracts a bundle to `staging` and verifies its files against the
+/// manifest.
+fn unpack_bundle<R: Read>(
+    reader: R,
+    staging: &Path,
+) -> Result<BundleManifest, FbhError> {
+    let mut archive = tar::Archive::new(reader);
+    let mut manifest = None;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        if entry_path == Path::new(MANIFEST_NAME) {
+            let mut buf = Vec::new();
+            entry.read_to_end(&mut buf)?;
+            manifest =
+                Some(serde_json::from_slice::<BundleManifest>(&buf).map_err(
+                    |e| {
+                        FbhError::InvalidInput(format!(
+                            "Invalid bundle manifest: {}",
+                            e
+                        ))
+                    },
+                )?);
+        } else if is_bundle_file_path(&entry_path) {
+            let destination = staging.join(&entry_path);
+            std::fs::create_dir_all(destination.parent().unwrap())?;
+            entry.unpack(&destination)?;
+        } else {
+            warn!("Ignoring unexpected bundle entry {:?}", entry_path);
+        }
+    }
+    let manifest = manifest.ok_or_else(|| {
+        FbhError::InvalidInput("The bundle has no manifest".to_owned())
+    })?;
+    verify_manifest_hashes(&manifest)?;
+    for file in &manifest.files {
+        if !is_bundle_file_path(Path::new(&file.path)) {
+            return Err(FbhError::InvalidInput(format!(
+                "Invalid path {:?} in the bundle manifest",
+                file.path
+            )));
+        }
+        let staged = staging.join(&file.path);
+        if !staged.is_file() {
+            return Err(FbhError::InvalidInput(format!(
+                "{} is listed in the bundle manifest but missing",
+                file.path
+            )));
+        }
+        file.verify(&staged)?;
+    }
+    Ok(manifest)
+}
+
+/// Checks that every file of a bundle is a map or mod of one of its sets,
+/// listed with the hash the set records for it, so that importing a bundle
+/// cannot replace a cached map or mod with a different file.
+fn verify_manifest_hashes(manifest: &BundleManifest) -> Result<(), FbhError> {
+    // The sha256 of every map and sha1 of every mod, by path in the bundle
+    let mut expected: BTreeMap<String, (Option<&str>, Option<&str>)> =
+        BTreeMap::new();
+    for set in manifest.benchmark_sets.values() {
+        let subdir = set.save_subdirectory.as_deref();
+        let maps = set.maps.iter().map(|map| {
+            (
+                mirror_save_path(subdir, &map.name),
+                (Some(&*map.sha256), None),
+            )
+        });
+        let mods = set.mods.iter().map(|indiv_mod| {
+            (
+                mirror_mod_path(&indiv_mod.zip_file_name()),
+                (None, Some(&*indiv_mod.sha1)),
+            )
+        });
+        for (path, hashes) in maps.chain(mods) {
+            match expected.entry(path) {
+                Entry::Vacant(entry) => {
+                    entry.insert(hashes);
+                }
+                Entry::Occupied(entry) if *entry.get() != hashes => {
+                    return Err(FbhError::InvalidInput(format!(
+                        "The sets of the bundle need different files at {}",
+                        entry.key()
+                    )));
+                }
+                Entry::Occupied(_) => (),
+            }
+        }
+    }
+    for file in &manifest.files {
+        let (sha256, sha1) = match expected.get(&file.path) {
+            Some(hashes) => *hashes,
+            None => {
+                return Err(FbhError::InvalidInput(format!(
+                    "{} is not a map or mod of any set in the bundle",
+                    file.path
+                )))
+            }
+        };
+        let listed = (file.sha256.as_deref(), file.sha1.as_deref());
+        if listed != (sha256, sha1) {
+            return Err(FbhError::HashMismatch {
+                name: file.path.clone(),
+                expected: sha256.or(sha1).unwrap_or_default().to_owned(),
+                found: listed.0.or(listed.1).unwrap_or("none").to_owned(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn install_bundle(
+    manifest: &BundleManifest,
+    staging: &Path,
+) -> Result<(), FbhError> {
+    for file in &manifest.files {
+        let destination = file.install_path();
+        std::fs::create_dir_all(destination.parent().unwrap())?;
+        std::fs::copy(staging.join(&file.path), &destination)?;
+    }
+    Ok(())
+}
+
+/// Whether a path within a bundle is a map or mod that stays within the
+/// bundle once extracted.
+fn is_bundle_file_path(path: &Path) -> bool {
+    let mut components = path.components();
+    let top_level = match components.next() {
+        Some(Component::Normal(c)) => c,
+        _ => return false,
+    };
+    (top_level == "saves" || top_level == "mods")
+        && components.clone().next().is_some()
+        && components.all(|c| matches!(c, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Map;
+
+    #[test]
+    fn test_bundle_file_paths() {
+        assert!(is_bundle_file_path(Path::new("saves/map.zip")));
+        assert!(is_bundle_file_path(Path::new("saves/sub/map.zip")));
+        assert!(is_bundle_file_path(Path::new("mods/mod_1.0.0.zip")));
+        assert!(!is_bundle_file_path(Path::new("saves")));
+        assert!(!is_bundle_file_path(Path::new("saves/../../etc/passwd")));
+        assert!(!is_bundle_file_path(Path::new("/saves/map.zip")));
+        assert!(!is_bundle_file_path(Path::new("other/map.zip")));
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let dir = std::env::temp_dir()
+            .join(format!("fbh-bundle-test-{}", std::process::id()));
+        let _ignore_err = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let map = dir.join("map.zip");
+        std::fs::write(&map, b"not really a map").unwrap();
+
+        let mut set = BenchmarkSet {
+            save_subdirectory: Some(PathBuf::from("sub")),
+            ..BenchmarkSet::default()
+        };
+        set.maps.insert(Map::new(&map, &sha256sum(&map), ""));
+        let mut manifest = BundleManifest {
+            helper_version: FACTORIO_BENCHMARK_HELPER_VERSION.to_owned(),
+            files: vec![BundleFile {
+                path: "saves/sub/map.zip".to_owned(),
+                sha256: Some(sha256sum(&map)),
+                sha1: None,
+            }],
+            ..BundleManifest::default()
+        };
+        manifest.benchmark_sets.insert("set".to_owned(), set);
+        let mut sources = BTreeMap::new();
+        sources.insert("saves/sub/map.zip".to_owned(), map);
+        let mut bundle = Vec::new();
+        write_bundle(&mut bundle, &manifest, &sources).unwrap();
+
+        let staging = dir.join("staging");
+        let unpacked = unpack_bundle(&*bundle, &staging).unwrap();
+        assert_eq!(unpacked, manifest);
+        assert!(staging.join("saves/sub/map.zip").is_file());
+
+        // The manifest must list the hash the set records for the map
+        manifest.files[0].sha256 = Some("0".repeat(64));
+        let mut bundle = Vec::new();
+        write_bundle(&mut bundle, &manifest, &sources).unwrap();
+        match unpack_bundle(&*bundle, &dir.join("staging2")) {
+            Err(FbhError::HashMismatch { .. }) => (),
+            other => panic!("Expected a hash mismatch, got {:?}", other),
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_manifest_hashes() {
+        let mut set = BenchmarkSet::default();
+        let map_sha256 = "a".repeat(64);
+        set.maps
+            .insert(Map::new(&PathBuf::from("map.zip"), &map_sha256, ""));
+        let mut manifest = BundleManifest::default();
+        manifest.benchmark_sets.insert("set".to_owned(), set);
+        manifest.files.push(BundleFile {
+            path: "saves/map.zip".to_owned(),
+            sha256: Some(map_sha256),
+            sha1: None,
+        });
+        assert!(verify_manifest_hashes(&manifest).is_ok());
+
+        // A file without a hash is never installed
+        manifest.files[0].sha256 = None;
+        assert!(verify_manifest_hashes(&manifest).is_err());
+        assert!(manifest.files[0].verify(Path::new("map.zip")).is_err());
+
+        // Nor is a file none of the sets need
+        manifest.files[0] = BundleFile {
+            path: "mods/other_1.0.0.zip".to_owned(),
+            sha256: None,
+            sha1: Some("b".repeat(40)),
+        };
+        assert!(matches!(
+            verify_manifest_hashes(&manifest),
+            Err(FbhError::InvalidInput(_))
+        ));
+    }
+}
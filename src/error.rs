@@ -0,0 +1,160 @@
+//! The error type returned by the entry points of the benchmark helper.
+//!
+//! Each kind of failure maps to its own process exit code so that callers
+//! embedding the helper in larger automation can tell them apart:
+//!
+//! | code | error               |
+//! |------|---------------------|
+//! | 2    | `Factorio`          |
+//! | 3    | `HashMismatch`      |
+//! | 4    | `ModPortal`         |
+//! | 5    | `MapDownload`       |
+//! | 6    | `Database`          |
+//! | 7    | `Io`                |
+//! | 8    | `InvalidInput`      |
+//! | 9    | `MissingFromMirror` |
+//! | 10   | `Timeout`           |
+//! | 11   | `HeadlessDownload`  |
+//!
+//! An exit code of 1 is left for command line usage errors.
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum FbhError {
+    /// Factorio could not be launched, exited unexpectedly, or reported an
+    /// error while running a benchmark.
+    Factorio(String),
+    /// A map or mod did not match the hash on record.
+    HashMismatch {
+        name: String,
+        expected: String,
+        found: String,
+    },
+    /// Querying or downloading from the mod portal failed.
+    ModPortal(String),
+    /// Downloading a map failed.
+    MapDownload(String),
+    /// Reading or writing a results database failed.
+    Database(rusqlite::Error),
+    /// A filesystem operation failed.
+    Io(std::io::Error),
+    /// A benchmark set, collection, or other input was missing or invalid.
+    InvalidInput(String),
+    /// Files needed in offline mode were not found in the mirror.
+    MissingFromMirror {
+        mirror: String,
+        artifacts: Vec<String>,
+    },
+    /// Factorio did not finish a benchmark in time and was killed.
+    Timeout(String),
+    /// Downloading a headless Factorio version failed.
+    HeadlessDownload(String),
+}
+
+impl FbhError {
+    /// The process exit code for this kind of error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FbhError::Factorio(_) => 2,
+            FbhError::HashMismatch { .. } => 3,
+            FbhError::ModPortal(_) => 4,
+            FbhError::MapDownload(_) => 5,
+            FbhError::Database(_) => 6,
+            FbhError::Io(_) => 7,
+            FbhError::InvalidInput(_) => 8,
+            FbhError::MissingFromMirror { .. } => 9,
+            FbhError::Timeout(_) => 10,
+            FbhError::HeadlessDownload(_) => 11,
+        }
+    }
+}
+
+impl fmt::Display for FbhError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FbhError::Factorio(msg) => write!(f, "Factorio failed: {}", msg),
+            FbhError::HashMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Hash mismatch for {}, expected {} but found {}",
+                name, expected, found
+            ),
+            FbhError::ModPortal(msg) => write!(f, "Mod portal: {}", msg),
+            FbhError::MapDownload(msg) => write!(f, "Map download: {}", msg),
+            FbhError::Database(e) => write!(f, "Database: {}", e),
+            FbhError::Io(e) => write!(f, "IO: {}", e),
+            FbhError::InvalidInput(msg) => write!(f, "{}", msg),
+            FbhError::MissingFromMirror { mirror, artifacts } => {
+                write!(f, "Offline, but missing from the mirror {}:", mirror)?;
+                for artifact in artifacts {
+                    write!(f, "\n    {}", artifact)?;
+                }
+                Ok(())
+            }
+            FbhError::Timeout(msg) => write!(f, "Factorio timed out: {}", msg),
+            FbhError::HeadlessDownload(msg) => {
+                write!(f, "Headless download: {}", msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FbhError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FbhError::Database(e) => Some(e),
+            FbhError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for FbhError {
+    fn from(e: std::io::Error) -> FbhError {
+        FbhError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for FbhError {
+    fn from(e: rusqlite::Error) -> FbhError {
+        FbhError::Database(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            FbhError::Factorio(String::new()),
+            FbhError::HashMismatch {
+                name: String::new(),
+                expected: String::new(),
+                found: String::new(),
+            },
+            FbhError::ModPortal(String::new()),
+            FbhError::MapDownload(String::new()),
+            FbhError::Database(rusqlite::Error::InvalidQuery),
+            FbhError::Io(std::io::Error::from(std::io::ErrorKind::Other)),
+            FbhError::InvalidInput(String::new()),
+            FbhError::MissingFromMirror {
+                mirror: String::new(),
+                artifacts: Vec::new(),
+            },
+            FbhError::Timeout(String::new()),
+            FbhError::HeadlessDownload(String::new()),
+        ];
+        let mut codes: Vec<i32> =
+            errors.iter().map(|e| e.exit_code()).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&0) && !codes.contains(&1));
+    }
+}
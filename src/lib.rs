@@ -0,0 +1,74 @@
+//! The benchmark engine behind the factorio-benchmark-helper command line
+//! tool, usable from other Rust tooling.
+//!
+//! The most commonly needed pieces are re-exported at the crate root:
+//! loading benchmark and meta sets from the procedure files, running a single
+//! Factorio benchmark with [`SimpleBenchmarkParams`], parsing its stdout into
+//! [`BenchmarkData`], reading or writing the results database, and creating
+//! or committing benchmark and meta sets.
+//!
+//! ```no_run
+//! use factorio_benchmark_helper::{
+//!     factorio_executable_path, parse_stdout_into_benchmark_data,
+//!     run_factorio_benchmark, SimpleBenchmarkParams,
+//! };
+//! use std::path::PathBuf;
+//!
+//! factorio_benchmark_helper::initialize().unwrap();
+//! let params = SimpleBenchmarkParams::new(PathBuf::from("map.zip"), 1000, 5);
+//! let stdout = run_factorio_benchmark(factorio_executable_path()?, &params)?;
+//! let data = parse_stdout_into_benchmark_data(&stdout);
+//! println!("{} ticks of {}", data.ticks, data.map_name);
+//! # Ok::<(), factorio_benchmark_helper::FbhError>(())
+//! ```
+
+#[macro_use]
+extern crate lazy_static;
+extern crate bincode;
+extern crate directories;
+#[macro_use]
+extern crate log;
+extern crate percent_encoding;
+extern crate regex;
+extern crate serde;
+extern crate serde_json;
+extern crate sha2;
+
+pub mod backblaze;
+pub mod benchmark_runner;
+pub mod bundle;
+pub mod error;
+pub mod performance_results;
+pub mod procedure_file;
+#[cfg(target_os = "linux")]
+pub mod regression_tester;
+pub mod set_creation;
+pub mod util;
+
+pub use benchmark_runner::{
+    parse_stdout_for_execution_time, parse_stdout_for_factorio_version,
+    parse_stdout_for_verbose_data, parse_stdout_into_benchmark_data,
+    run_benchmarks_multiple, run_factorio_benchmark, BenchmarkRunOptions,
+    SimpleBenchmarkParams,
+};
+pub use error::FbhError;
+pub use performance_results::collection_data::{
+    BenchmarkData, CollectionData, Mod,
+};
+pub use performance_results::database::{
+    get_benchmark, get_collection_benchmarks, get_collection_header,
+    get_verbose_column, insert_benchmark, insert_collection, setup_database,
+    BenchmarkRecord,
+};
+pub use procedure_file::{
+    find_benchmark_set, find_meta_set_benchmarks, get_metas_from_meta,
+    get_sets_from_meta, read_benchmark_set_from_file, read_meta_from_file,
+    BenchmarkSet, ProcedureFileKind,
+};
+pub use set_creation::{
+    build_benchmark_set, commit_benchmark_set, commit_meta_set,
+    create_meta_set, BenchmarkSetOptions,
+};
+pub use util::{
+    factorio_executable_path, fbh_results_database, initialize, Map,
+};
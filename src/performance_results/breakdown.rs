@@ -0,0 +1,180 @@
+//! Per-subsystem timing breakdown of benchmarks from their verbose data.
+
+use crate::error::FbhError;
+use crate::performance_results::database::get_benchmark;
+use crate::performance_results::database::get_collection_benchmarks;
+use crate::performance_results::database::get_collection_header;
+use crate::performance_results::database::get_verbose_columns;
+use crate::performance_results::database::lock_database;
+use crate::performance_results::database::BenchmarkRecord;
+use crate::performance_results::statistics::summarize;
+use rusqlite::Connection;
+
+/// The columns which contain every other subsystem, and are shown first.
+const TOTAL_COLUMNS: [&str; 2] = ["wholeUpdate", "gameUpdate"];
+
+/// What to produce a breakdown of.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakdownScope {
+    Collection(u32),
+    Benchmark(u32),
+}
+
+impl BreakdownScope {
+    /// Creates a scope from a kind of "collection" or "benchmark" and an id.
+    pub fn from_kind_and_id(kind: &str, id: u32) -> Result<Self, String> {
+        match kind.to_lowercase().as_str() {
+            "collection" => Ok(BreakdownScope::Collection(id)),
+            "benchmark" => Ok(BreakdownScope::Benchmark(id)),
+            _ => Err(format!(
+                "Unknown breakdown type {:?}, expected collection or benchmark",
+                kind
+            )),
+        }
+    }
+}
+
+/// The mean time spent per tick in a single verbose timing column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubsystemTiming {
+    pub subsystem: String,
+    pub mean_ms: f64,
+    /// The share of gameUpdate this subsystem makes up, in percent.
+    pub game_update_share: f64,
+}
+
+/// Computes the mean time per tick of every verbose timing column of a
+/// benchmark. The totals come first, followed by the subsystems sorted by
+/// descending time.
+pub fn subsystem_breakdown(
+    database: &Connection,
+    benchmark_id: u32,
+) -> rusqlite::Result<Vec<SubsystemTiming>> {
+    let mut means = Vec::new();
+    for (column, samples) in get_verbose_columns(database, benchmark_id)? {
+        let mean = summarize(&samples).map(|s| s.mean).unwrap_or_default();
+        means.push((column, mean));
+    }
+    let game_update = means
+        .iter()
+        .find(|(column, _)| *column == "gameUpdate")
+        .map(|(_, mean)| *mean)
+        .unwrap_or_default();
+    let mut timings: Vec<SubsystemTiming> = means
+        .into_iter()
+        .map(|(column, mean)| SubsystemTiming {
+            subsystem: column.to_string(),
+            mean_ms: mean,
+            game_update_share: if game_update > 0.0 {
+                mean / game_update * 100.0
+            } else {
+                0.0
+            },
+        })
+        .collect();
+    timings.sort_by(|a, b| {
+        let a_total = TOTAL_COLUMNS.iter().position(|c| *c == a.subsystem);
+        let b_total = TOTAL_COLUMNS.iter().position(|c| *c == b.subsystem);
+        match (a_total, b_total) {
+            (Some(a_pos), Some(b_pos)) => a_pos.cmp(&b_pos),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => b.mean_ms.partial_cmp(&a.mean_ms).unwrap(),
+        }
+    });
+    Ok(timings)
+}
+
+/// Prints how much of each tick is spent in each subsystem, for every
+/// benchmark within the scope.
+pub fn print_subsystem_breakdown(
+    scope: BreakdownScope,
+) -> Result<(), FbhError> {
+    let database = lock_database()?;
+    let benchmarks = match scope {
+        BreakdownScope::Collection(collection_id) => {
+            let (name, factorio_version, cpuid) =
+                get_collection_header(&database, collection_id)?;
+            println!(
+                "Collection {} {:?}, Factorio {}, {}",
+                collection_id, name, factorio_version, cpuid
+            );
+            get_collection_benchmarks(&database, collection_id)?
+        }
+        BreakdownScope::Benchmark(benchmark_id) => {
+            vec![get_benchmark(&database, benchmark_id)?]
+        }
+    };
+    if benchmarks.is_empty() {
+        return Err(FbhError::InvalidInput(format!(
+            "No benchmarks found for {:?}",
+            scope
+        )));
+    }
+    for benchmark in &benchmarks {
+        print_benchmark_breakdown(&database, benchmark)?;
+    }
+    Ok(())
+}
+
+fn print_benchmark_breakdown(
+    database: &Connection,
+    benchmark: &BenchmarkRecord,
+) -> rusqlite::Result<()> {
+    println!();
+    println!(
+        "{} (benchmark {}, {} runs x {} ticks)",
+        benchmark.map_name,
+        benchmark.benchmark_id,
+        benchmark.runs,
+        benchmark.ticks
+    );
+    println!(
+        "    {:<26} {:>10} {:>12}",
+        "subsystem", "mean ms", "% gameUpdate"
+    );
+    for timing in subsystem_breakdown(database, benchmark.benchmark_id)? {
+        println!(
+            "    {:<26} {:>10.3} {:>11.2}%",
+            timing.subsystem, timing.mean_ms, timing.game_update_share
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance_results::database::CREATE_SQL;
+
+    #[test]
+    fn test_subsystem_breakdown() {
+        let database = Connection::open_in_memory().unwrap();
+        database.execute_batch(CREATE_SQL).unwrap();
+        database
+            .execute_batch(
+                "INSERT INTO collection(name,factorio_version,platform,\
+                executable_type,cpuid) VALUES ('TEST','0.0.0','','','');
+                INSERT INTO benchmark(map_name,runs,ticks,map_hash,\
+                collection_id) VALUES ('TEST',1,2,hex(zeroblob(32)),1);
+                INSERT INTO verbose(tick_number,wholeUpdate,gameUpdate,\
+                circuitNetworkUpdate,transportLinesUpdate,fluidsUpdate,\
+                entityUpdate,mapGenerator,electricNetworkUpdate,\
+                logisticManagerUpdate,constructionManagerUpdate,pathFinder,\
+                trains,trainPathFinder,commander,chartRefresh,\
+                luaGarbageIncremental,chartUpdate,scriptUpdate,run_index,\
+                benchmark_id) VALUES \
+                (1,5000000,4000000,0,1000000,0,2000000,0,0,0,0,0,0,0,0,0,0,0,0,1,1),\
+                (2,5000000,4000000,0,1000000,0,2000000,0,0,0,0,0,0,0,0,0,0,0,0,1,1);",
+            )
+            .unwrap();
+        let timings = subsystem_breakdown(&database, 1).unwrap();
+        assert_eq!(timings[0].subsystem, "wholeUpdate");
+        assert_eq!(timings[1].subsystem, "gameUpdate");
+        assert_eq!(timings[2].subsystem, "entityUpdate");
+        assert!((timings[2].mean_ms - 2.0).abs() < 1e-9);
+        assert!((timings[2].game_update_share - 50.0).abs() < 1e-9);
+        assert_eq!(timings[3].subsystem, "transportLinesUpdate");
+        assert!((timings[3].game_update_share - 25.0).abs() < 1e-9);
+    }
+}
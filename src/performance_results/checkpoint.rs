@@ -0,0 +1,141 @@
+//! Tracking of collections which were started but not yet finished, so an
+//! interrupted benchmark set can be resumed.
+
+use crate::util::fbh_checkpoint_file;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct Checkpoint {
+    /// The collection id currently being filled, keyed by benchmark set name
+    /// and the Factorio executable the set is ran with, as a set can be ran
+    /// with several Factorio installs at once.
+    in_progress: BTreeMap<String, BTreeMap<PathBuf, u32>>,
+}
+
+fn read_checkpoint(path: &Path) -> Checkpoint {
+    std::fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn write_checkpoint(path: &Path, checkpoint: &Checkpoint) {
+    let j = serde_json::to_string_pretty(checkpoint).unwrap();
+    if let Err(e) = std::fs::write(path, j) {
+        eprintln!("Failed to write checkpoint file: {}", e);
+    }
+}
+
+/// Gets the id of the unfinished collection of a benchmark set ran with the
+/// given Factorio executable, if any.
+pub fn get_in_progress_collection(
+    set_name: &str,
+    executable: &Path,
+) -> Option<u32> {
+    get_in_progress(&fbh_checkpoint_file(), set_name, executable)
+}
+
+/// Marks a collection as the unfinished collection of a benchmark set ran
+/// with the given Factorio executable.
+pub fn set_in_progress_collection(
+    set_name: &str,
+    executable: &Path,
+    collection_id: u32,
+) {
+    set_in_progress(&fbh_checkpoint_file(), set_name, executable, collection_id)
+}
+
+/// Marks the collection of a benchmark set ran with the given Factorio
+/// executable as finished.
+pub fn clear_in_progress_collection(set_name: &str, executable: &Path) {
+    clear_in_progress(&fbh_checkpoint_file(), set_name, executable)
+}
+
+fn get_in_progress(
+    path: &Path,
+    set_name: &str,
+    executable: &Path,
+) -> Option<u32> {
+    read_checkpoint(path)
+        .in_progress
+        .get(set_name)?
+        .get(executable)
+        .copied()
+}
+
+fn set_in_progress(
+    path: &Path,
+    set_name: &str,
+    executable: &Path,
+    collection_id: u32,
+) {
+    let mut checkpoint = read_checkpoint(path);
+    checkpoint
+        .in_progress
+        .entry(set_name.to_string())
+        .or_default()
+        .insert(executable.to_path_buf(), collection_id);
+    write_checkpoint(path, &checkpoint);
+}
+
+fn clear_in_progress(path: &Path, set_name: &str, executable: &Path) {
+    let mut checkpoint = read_checkpoint(path);
+    let installs = match checkpoint.in_progress.get_mut(set_name) {
+        Some(installs) => installs,
+        None => return,
+    };
+    if installs.remove(executable).is_some() {
+        if installs.is_empty() {
+            checkpoint.in_progress.remove(set_name);
+        }
+        write_checkpoint(path, &checkpoint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let dir = std::env::temp_dir()
+            .join(format!("fbh-checkpoint-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("checkpoint.json");
+        let factorio = Path::new("factorio/bin/x64/factorio");
+        set_in_progress(&path, "set", factorio, 42);
+        set_in_progress(&path, "other set", factorio, 7);
+        assert_eq!(get_in_progress(&path, "set", factorio), Some(42));
+        clear_in_progress(&path, "set", factorio);
+        assert_eq!(get_in_progress(&path, "set", factorio), None);
+        assert_eq!(get_in_progress(&path, "other set", factorio), Some(7));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_across_installs() {
+        let dir = std::env::temp_dir().join(format!(
+            "fbh-checkpoint-installs-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("checkpoint.json");
+        let old = Path::new("1.1.104/bin/x64/factorio");
+        let new = Path::new("1.1.107/bin/x64/factorio");
+        // Both installs are interrupted while running the same set
+        set_in_progress(&path, "set", old, 1);
+        set_in_progress(&path, "set", new, 2);
+        assert_eq!(get_in_progress(&path, "set", old), Some(1));
+        assert_eq!(get_in_progress(&path, "set", new), Some(2));
+        // Resuming and finishing with one install leaves the other resumable
+        clear_in_progress(&path, "set", old);
+        assert_eq!(get_in_progress(&path, "set", old), None);
+        assert_eq!(get_in_progress(&path, "set", new), Some(2));
+        clear_in_progress(&path, "set", new);
+        assert!(read_checkpoint(&path).in_progress.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
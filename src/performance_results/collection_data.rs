@@ -1,3 +1,7 @@
+use crate::performance_results::statistics::relative_precision;
+use crate::performance_results::statistics::OutlierPolicy;
+use crate::performance_results::statistics::TickSample;
+use crate::util::SystemEnvironment;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 
@@ -20,6 +24,15 @@ impl Mod {
             sha1: hash.to_string(),
         }
     }
+
+    /// The file name of the mod's zip, within the mod directory.
+    pub fn zip_file_name(&self) -> String {
+        if self.file_name.is_empty() {
+            format!("{}_{}.zip", self.name, self.version)
+        } else {
+            self.file_name.clone()
+        }
+    }
 }
 
 impl PartialEq for Mod {
@@ -31,7 +44,7 @@ impl PartialEq for Mod {
     }
 }
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct CollectionData {
     pub benchmark_name: String,
     pub factorio_version: String,
@@ -40,6 +53,50 @@ pub struct CollectionData {
     pub cpuid: String,
     pub benchmarks: Vec<BenchmarkData>,
     pub mods: BTreeSet<Mod>,
+    /// The CPUs Factorio was pinned to, such as 0-3,6. Empty if not pinned.
+    #[serde(default)]
+    pub cpu_affinity: String,
+    /// The nice value Factorio ran with, if it was changed.
+    #[serde(default)]
+    pub nice: Option<i32>,
+    /// The system the collection was ran on. Not recorded for collections
+    /// from older versions.
+    #[serde(default)]
+    pub environment: Option<SystemEnvironment>,
+    /// The maps of the set which could not be benchmarked.
+    #[serde(default)]
+    pub failures: Vec<BenchmarkFailure>,
+}
+
+/// A map of a collection which failed to benchmark, and why.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct BenchmarkFailure {
+    pub map_name: String,
+    pub map_hash: String,
+    pub reason: String,
+}
+
+/// How long running a map takes, measured from Factorio's log timestamps.
+/// Used to estimate how long a benchmark set will take.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MapDuration {
+    /// Seconds spent starting Factorio and loading the map.
+    pub initialization_time: f64,
+    /// Milliseconds per tick, as Factorio reports it.
+    pub per_tick_time: f64,
+    /// Seconds spent in a run besides its ticks, such as reloading the map.
+    pub per_run_overhead_time: f64,
+}
+
+impl MapDuration {
+    /// The seconds a single launch of Factorio running `runs` runs of `ticks`
+    /// ticks is expected to take.
+    pub fn expected_run_time(&self, ticks: u32, runs: u32) -> f64 {
+        self.initialization_time
+            + f64::from(runs)
+                * (self.per_run_overhead_time
+                    + self.per_tick_time * f64::from(ticks) / 1000.0)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -55,4 +112,41 @@ pub struct BenchmarkData {
     ///     constructionManagerUpdate,pathFinder,trains,trainPathFinder,commander,chartRefresh,
     ///     luaGarbageIncremental,chartUpdate,scriptUpdate,run_index
     pub verbose_data: Vec<String>,
+    /// The ticks at the start of every run discarded when summarizing
+    #[serde(default)]
+    pub warmup_ticks: u32,
+    #[serde(default)]
+    pub outlier_policy: OutlierPolicy,
+    /// The half width of the 95% confidence interval of the mean
+    /// wholeUpdate, relative to the mean. Not recorded for benchmarks from
+    /// older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub achieved_precision: Option<f64>,
+}
+
+impl BenchmarkData {
+    /// The wholeUpdate of every tick in the verbose data, in nanoseconds.
+    pub fn whole_update_samples(&self) -> Vec<TickSample> {
+        self.verbose_data
+            .iter()
+            .filter_map(|row| {
+                let values: Vec<&str> = row.split(',').collect();
+                Some(TickSample {
+                    tick: values.first()?.trim().parse().ok()?,
+                    value: values.get(1)?.trim().parse().ok()?,
+                    run: values.last()?.trim().parse().ok()?,
+                })
+            })
+            .collect()
+    }
+
+    /// The precision of the mean wholeUpdate of the runs so far, see
+    /// `relative_precision`.
+    pub fn precision(&self) -> Option<f64> {
+        relative_precision(
+            &self.whole_update_samples(),
+            self.warmup_ticks,
+            &self.outlier_policy,
+        )
+    }
 }
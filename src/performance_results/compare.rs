@@ -0,0 +1,183 @@
+//! Statistical comparison of the results of two collections.
+
+use crate::error::FbhError;
+use crate::performance_results::database::get_collection_benchmarks;
+use crate::performance_results::database::get_collection_environment;
+use crate::performance_results::database::get_collection_header;
+use crate::performance_results::database::get_verbose_column;
+use crate::performance_results::database::get_verbose_run_means;
+use crate::performance_results::database::lock_database;
+use crate::performance_results::database::BenchmarkRecord;
+use crate::performance_results::statistics::summarize;
+use crate::performance_results::statistics::welch_t_test;
+use crate::performance_results::statistics::Summary;
+use rusqlite::Connection;
+
+/// Compares the wholeUpdate timings of every map present in both collections,
+/// matched by map hash, and prints the percent change from `collection_a` to
+/// `collection_b` along with a 95% confidence interval from Welch's t-test.
+/// The test treats the mean of each run as one sample, since the ticks
+/// within a run are not independent.
+pub fn print_collection_comparison(
+    collection_a: u32,
+    collection_b: u32,
+) -> Result<(), FbhError> {
+    let database = lock_database()?;
+    let benchmarks_a = get_collection_benchmarks(&database, collection_a)?;
+    let benchmarks_b = get_collection_benchmarks(&database, collection_b)?;
+    if benchmarks_a.is_empty() || benchmarks_b.is_empty() {
+        return Err(FbhError::InvalidInput(format!(
+            "Collection {} or {} has no benchmarks to compare",
+            collection_a, collection_b
+        )));
+    }
+    print_collection_header(&database, "A", collection_a)?;
+    print_collection_header(&database, "B", collection_b)?;
+    print_environment_differences(&database, collection_a, collection_b)?;
+
+    let mut matched = 0;
+    for benchmark_a in &benchmarks_a {
+        let benchmark_b = match benchmarks_b
+            .iter()
+            .find(|b| b.map_hash == benchmark_a.map_hash)
+        {
+            Some(b) => b,
+            None => continue,
+        };
+        matched += 1;
+        print_map_comparison(&database, benchmark_a, benchmark_b)?;
+    }
+    for (label, benchmarks, others) in &[
+        ("A", &benchmarks_a, &benchmarks_b),
+        ("B", &benchmarks_b, &benchmarks_a),
+    ] {
+        for benchmark in benchmarks.iter() {
+            if !others.iter().any(|o| o.map_hash == benchmark.map_hash) {
+                println!(
+                    "Map {:?} is only present in collection {}",
+                    benchmark.map_name, label
+                );
+            }
+        }
+    }
+    if matched == 0 {
+        return Err(FbhError::InvalidInput(
+            "No maps with matching hashes in both collections".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn print_collection_header(
+    database: &Connection,
+    label: &str,
+    collection_id: u32,
+) -> rusqlite::Result<()> {
+    let (name, factorio_version, cpuid) =
+        get_collection_header(database, collection_id)?;
+    println!(
+        "{}: collection {} {:?}, Factorio {}, {}",
+        label, collection_id, name, factorio_version, cpuid
+    );
+    Ok(())
+}
+
+fn print_environment_differences(
+    database: &Connection,
+    collection_a: u32,
+    collection_b: u32,
+) -> rusqlite::Result<()> {
+    let environment_a = get_collection_environment(database, collection_a)?;
+    let environment_b = get_collection_environment(database, collection_b)?;
+    if let (Some(a), Some(b)) = (&environment_a, &environment_b) {
+        for (field, value_a, value_b) in a.differences(b) {
+            println!(
+                "Environment differs in {}: A {}, B {}",
+                field, value_a, value_b
+            );
+        }
+    }
+    Ok(())
+}
+
+fn print_map_comparison(
+    database: &Connection,
+    benchmark_a: &BenchmarkRecord,
+    benchmark_b: &BenchmarkRecord,
+) -> Result<(), FbhError> {
+    let samples_a =
+        get_verbose_column(database, benchmark_a.benchmark_id, "wholeUpdate")?;
+    let samples_b =
+        get_verbose_column(database, benchmark_b.benchmark_id, "wholeUpdate")?;
+    println!();
+    println!(
+        "{} (A: {} runs x {} ticks, B: {} runs x {} ticks)",
+        benchmark_a.map_name,
+        benchmark_a.runs,
+        benchmark_a.ticks,
+        benchmark_b.runs,
+        benchmark_b.ticks
+    );
+    println!(
+        "    {:<3} {:>8} {:>10} {:>10} {:>10} {:>10}",
+        "", "samples", "mean ms", "median ms", "stddev ms", "p95 ms"
+    );
+    let (summary_a, summary_b) =
+        match (summarize(&samples_a), summarize(&samples_b)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => {
+                println!("    No verbose data to compare");
+                return Ok(());
+            }
+        };
+    print_summary_row("A", &summary_a);
+    print_summary_row("B", &summary_b);
+    let runs_a = summarize(&get_verbose_run_means(
+        database,
+        benchmark_a.benchmark_id,
+        "wholeUpdate",
+    )?);
+    let runs_b = summarize(&get_verbose_run_means(
+        database,
+        benchmark_b.benchmark_id,
+        "wholeUpdate",
+    )?);
+    let percent = |x: f64, base: f64| x / base * 100.0;
+    let test = match (&runs_a, &runs_b) {
+        (Some(a), Some(b)) => welch_t_test(a, b).map(|test| (test, a.mean)),
+        _ => None,
+    };
+    match test {
+        Some((test, base)) => println!(
+            "    change {:+.3}% (95% CI {:+.3}% to {:+.3}%), t = {:.3}, \
+            df = {:.1}, {}",
+            percent(test.mean_difference, base),
+            percent(test.ci_low, base),
+            percent(test.ci_high, base),
+            test.t,
+            test.degrees_of_freedom,
+            if test.is_significant() {
+                "significant"
+            } else {
+                "within noise"
+            }
+        ),
+        None => println!(
+            "    change {:+.3}%, too few runs for a confidence interval",
+            percent(summary_b.mean - summary_a.mean, summary_a.mean)
+        ),
+    }
+    Ok(())
+}
+
+fn print_summary_row(label: &str, summary: &Summary) {
+    println!(
+        "    {:<3} {:>8} {:>10.3} {:>10.3} {:>10.3} {:>10.3}",
+        label,
+        summary.count,
+        summary.mean,
+        summary.median,
+        summary.stddev,
+        summary.p95
+    );
+}
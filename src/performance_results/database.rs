@@ -1,13 +1,30 @@
+use crate::benchmark_runner::STANDARD_VERBOSE_TIMINGS;
+use crate::error::FbhError;
+use crate::performance_results::collection_data::BenchmarkData;
+use crate::performance_results::collection_data::BenchmarkFailure;
 use crate::performance_results::collection_data::CollectionData;
+use crate::performance_results::collection_data::MapDuration;
+use crate::performance_results::collection_data::Mod;
+use crate::performance_results::statistics::discard_warmup;
+use crate::performance_results::statistics::filter_samples;
+use crate::performance_results::statistics::retained_runs;
+use crate::performance_results::statistics::run_means;
+use crate::performance_results::statistics::OutlierPolicy;
+use crate::performance_results::statistics::TickSample;
 use crate::util::fbh_results_database;
+use crate::util::SystemEnvironment;
 use rusqlite::Connection;
+use rusqlite::OptionalExtension;
 use rusqlite::NO_PARAMS;
+use std::collections::BTreeSet;
 use std::fs;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::path::Path;
 use std::path::PathBuf;
-use std::process::exit;
 use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::OnceLock;
 
 pub const CREATE_SQL: &str = "
 BEGIN TRANSACTION;
@@ -18,6 +35,8 @@ CREATE TABLE IF NOT EXISTS `collection` (
 ,  `platform` varchar(100)  NOT NULL
 ,  `executable_type` varchar(100)  NOT NULL
 ,  `cpuid` text NULL
+,  `cpu_affinity` text  NOT NULL DEFAULT ''
+,  `nice` integer NULL
 );
 
 CREATE TABLE IF NOT EXISTS `benchmark` (
@@ -27,6 +46,9 @@ CREATE TABLE IF NOT EXISTS `benchmark` (
 ,  `ticks` integer  NOT NULL
 ,  `map_hash` char(64)  NOT NULL
 ,  `collection_id` integer  NOT NULL
+,  `warmup_ticks` integer  NOT NULL DEFAULT 0
+,  `outlier_policy` text  NOT NULL DEFAULT 'none'
+,  `achieved_precision` real  NULL
 ,  CONSTRAINT `benchmark_base_ibfk_1` FOREIGN KEY (`collection_id`) REFERENCES `collection` (`collection_id`)
 ,  CONSTRAINT `hash_length_check` CHECK (length(`map_hash`) = 64)
 );
@@ -71,6 +93,37 @@ CREATE TABLE IF NOT EXISTS `collection_mods` (
 ,  CONSTRAINT `collection_mods_ibfk_1` FOREIGN KEY (`collection_id`) REFERENCES `collection` (`collection_id`)
 );
 
+CREATE TABLE IF NOT EXISTS `collection_environment` (
+  `collection_id` integer NOT NULL PRIMARY KEY
+,  `logical_cores` integer NULL
+,  `physical_cores` integer NULL
+,  `cpu_governor` text NULL
+,  `total_memory_kb` integer NULL
+,  `kernel_version` text NULL
+,  `turbo_enabled` integer NULL
+,  `hostname` text NULL
+,  `helper_version` text NOT NULL
+,  CONSTRAINT `collection_environment_ibfk_1` FOREIGN KEY (`collection_id`) REFERENCES `collection` (`collection_id`)
+);
+
+CREATE TABLE IF NOT EXISTS `benchmark_failure` (
+  `collection_id` integer  NOT NULL
+,  `map_name` varchar(100)  NOT NULL
+,  `map_hash` char(64)  NOT NULL
+,  `reason` text  NOT NULL
+,  CONSTRAINT `benchmark_failure_ibfk_1` FOREIGN KEY (`collection_id`) REFERENCES `collection` (`collection_id`)
+);
+
+CREATE TABLE IF NOT EXISTS `map_duration` (
+  `map_hash` char(64)  NOT NULL
+,  `cpuid` text  NOT NULL
+,  `factorio_version` varchar(10)  NOT NULL
+,  `initialization_time` real  NOT NULL
+,  `per_tick_time` real  NOT NULL
+,  `per_run_overhead_time` real  NOT NULL
+,  PRIMARY KEY (`map_hash`, `cpuid`, `factorio_version`)
+);
+
 CREATE VIEW IF NOT EXISTS `v_collection` AS
 SELECT collection.collection_id,collection.name,factorio_version,platform,executable_type,cpuid,mods.name,mods.version,mods.sha1
 from collection
@@ -81,12 +134,25 @@ join mods on collection_mods.sha1 = mods.sha1
 COMMIT;
 ";
 
-lazy_static! {
-    static ref DB_CONNECTION: Mutex<Connection> =
-        Mutex::new(setup_database(false, &fbh_results_database()));
+static DB_CONNECTION: OnceLock<Mutex<Connection>> = OnceLock::new();
+
+/// Locks the results database, opening it on first use.
+pub(crate) fn lock_database(
+) -> Result<MutexGuard<'static, Connection>, FbhError> {
+    let connection = match DB_CONNECTION.get() {
+        Some(connection) => connection,
+        None => {
+            let database = setup_database(false, &fbh_results_database())?;
+            DB_CONNECTION.get_or_init(|| Mutex::new(database))
+        }
+    };
+    Ok(connection.lock().unwrap())
 }
 
-pub fn setup_database(create_new_db: bool, db_path: &PathBuf) -> Connection {
+pub fn setup_database(
+    create_new_db: bool,
+    db_path: &PathBuf,
+) -> Result<Connection, FbhError> {
     if create_new_db {
         fs::remove_file(db_path).ok();
         OpenOptions::new()
@@ -95,13 +161,220 @@ pub fn setup_database(create_new_db: bool, db_path: &PathBuf) -> Connection {
             .open(db_path)
             .ok();
     }
-    let database = rusqlite::Connection::open(db_path).unwrap();
-    create_tables_in_db(&database);
+    let database = rusqlite::Connection::open(db_path)?;
+    create_tables_in_db(&database)?;
+    Ok(database)
+}
+
+/// Inserts a collection and its mods without any benchmarks, returning the id
+/// of the new collection. Benchmarks can then be added one at a time with
+/// `insert_benchmark` as they finish.
+pub fn insert_collection(
+    collection_data: &CollectionData,
+) -> Result<u32, FbhError> {
+    let mut database = lock_database()?;
+    insert_collection_rows(&mut database, collection_data)
+}
+
+/// Inserts a single benchmark and its verbose data into an existing
+/// collection.
+pub fn insert_benchmark(
+    collection_id: u32,
+    benchmark: &BenchmarkData,
+) -> Result<(), FbhError> {
+    let mut database = lock_database()?;
+    let transacter = database.transaction()?;
+    insert_benchmark_rows(&transacter, collection_id, benchmark)?;
+    transacter.commit()?;
+    Ok(())
+}
+
+/// Records a map of a collection which could not be benchmarked, replacing
+/// the failure of an earlier attempt at the map when resuming.
+pub fn insert_benchmark_failure(
+    collection_id: u32,
+    failure: &BenchmarkFailure,
+) -> Result<(), FbhError> {
+    let database = lock_database()?;
+    insert_benchmark_failure_row(&database, collection_id, failure)?;
+    Ok(())
+}
+
+fn insert_benchmark_failure_row(
+    database: &Connection,
+    collection_id: u32,
+    failure: &BenchmarkFailure,
+) -> rusqlite::Result<()> {
+    delete_benchmark_failure_row(database, collection_id, &failure.map_hash)?;
+    database.execute_named(
+        "INSERT INTO benchmark_failure (collection_id, map_name, map_hash, \
+        reason) VALUES (:collection_id, :map_name, :map_hash, :reason)",
+        &[
+            (":collection_id", &collection_id),
+            (":map_name", &failure.map_name),
+            (":map_hash", &failure.map_hash),
+            (":reason", &failure.reason),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Removes the failure of a map from a collection, once the map is retried
+/// when resuming.
+fn delete_benchmark_failure_row(
+    database: &Connection,
+    collection_id: u32,
+    map_hash: &str,
+) -> rusqlite::Result<()> {
+    database.execute_named(
+        "DELETE FROM benchmark_failure WHERE collection_id = :collection_id \
+        AND map_hash = :map_hash",
+        &[(":collection_id", &collection_id), (":map_hash", &map_hash)],
+    )?;
+    Ok(())
+}
+
+fn get_benchmark_failures(
+    database: &Connection,
+    collection_id: u32,
+) -> rusqlite::Result<Vec<BenchmarkFailure>> {
+    let mut statement = database.prepare(
+        "SELECT map_name, map_hash, reason FROM benchmark_failure \
+        WHERE collection_id = ?",
+    )?;
+    let rows = statement.query_map([collection_id], |row| {
+        Ok(BenchmarkFailure {
+            map_name: row.get(0)?,
+            map_hash: row.get(1)?,
+            reason: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// What an unfinished collection was ran with, and the maps already
+/// completed within it.
+#[derive(Debug, Clone)]
+pub struct CollectionProgress {
+    pub factorio_version: String,
+    pub cpuid: String,
+    /// The sha1 of every mod the collection was ran with.
+    pub mod_hashes: BTreeSet<String>,
+    /// The hashes of the maps which already have a benchmark.
+    pub map_hashes: Vec<String>,
+}
+
+/// Gets what a collection was ran with and the hashes of the maps which
+/// already have a benchmark within it.
+pub fn get_collection_progress(
+    collection_id: u32,
+) -> Result<CollectionProgress, FbhError> {
+    let database = lock_database()?;
+    let (_name, factorio_version, cpuid) =
+        get_collection_header(&database, collection_id)?;
+    let mut statement = database
+        .prepare("SELECT sha1 FROM collection_mods WHERE collection_id = ?")?;
+    let mod_hashes = statement
+        .query_map([collection_id], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    let map_hashes = get_collection_benchmarks(&database, collection_id)?
+        .into_iter()
+        .map(|benchmark| benchmark.map_hash)
+        .collect();
+    Ok(CollectionProgress {
+        factorio_version,
+        cpuid,
+        mod_hashes,
+        map_hashes,
+    })
+}
+
+/// Stores how long a map took to run without errors on this cpu and Factorio
+/// version, replacing any earlier measurement.
+pub fn record_map_duration(
+    map_hash: &str,
+    cpuid: &str,
+    factorio_version: &str,
+    duration: &MapDuration,
+) -> Result<(), FbhError> {
+    let database = lock_database()?;
+    insert_map_duration_row(
+        &database,
+        map_hash,
+        cpuid,
+        factorio_version,
+        duration,
+    )?;
+    Ok(())
+}
+
+/// Gets the last measured duration of a map on this cpu and Factorio version,
+/// if it has ran without errors before.
+pub fn get_map_duration(
+    map_hash: &str,
+    cpuid: &str,
+    factorio_version: &str,
+) -> Result<Option<MapDuration>, FbhError> {
+    let database = lock_database()?;
+    Ok(get_map_duration_row(
+        &database,
+        map_hash,
+        cpuid,
+        factorio_version,
+    )?)
+}
+
+fn insert_map_duration_row(
+    database: &Connection,
+    map_hash: &str,
+    cpuid: &str,
+    factorio_version: &str,
+    duration: &MapDuration,
+) -> rusqlite::Result<()> {
+    database.execute_named(
+        "INSERT OR REPLACE INTO map_duration (map_hash, cpuid, \
+        factorio_version, initialization_time, per_tick_time, \
+        per_run_overhead_time) VALUES (:map_hash, :cpuid, :factorio_version, \
+        :initialization_time, :per_tick_time, :per_run_overhead_time)",
+        &[
+            (":map_hash", &map_hash),
+            (":cpuid", &cpuid),
+            (":factorio_version", &factorio_version),
+            (":initialization_time", &duration.initialization_time),
+            (":per_tick_time", &duration.per_tick_time),
+            (":per_run_overhead_time", &duration.per_run_overhead_time),
+        ],
+    )?;
+    Ok(())
+}
+
+fn get_map_duration_row(
+    database: &Connection,
+    map_hash: &str,
+    cpuid: &str,
+    factorio_version: &str,
+) -> rusqlite::Result<Option<MapDuration>> {
     database
+        .query_row(
+            "SELECT initialization_time, per_tick_time, \
+            per_run_overhead_time FROM map_duration WHERE map_hash = ? \
+            AND cpuid = ? AND factorio_version = ?",
+            [map_hash, cpuid, factorio_version],
+            |row| {
+                Ok(MapDuration {
+                    initialization_time: row.get(0)?,
+                    per_tick_time: row.get(1)?,
+                    per_run_overhead_time: row.get(2)?,
+                })
+            },
+        )
+        .optional()
 }
 
-pub fn upload_to_db(collection_data: CollectionData) {
-    let mut database = DB_CONNECTION.lock().unwrap();
+fn insert_collection_rows(
+    database: &mut Connection,
+    collection_data: &CollectionData,
+) -> Result<u32, FbhError> {
     {
         // Not a transaction, but perf should be ok since we're not inserting a
         // LOT of mods
@@ -112,138 +385,492 @@ pub fn upload_to_db(collection_data: CollectionData) {
                 "INSERT OR IGNORE INTO mods({}) VALUES ({:?},{:?},{:?});",
                 mods_header, indiv_mod.name, indiv_mod.version, indiv_mod.sha1,
             );
-            match database.execute_batch(&combined_sql) {
-                Ok(_) => (),
-                Err(e) => {
-                    eprintln!("Failed to insert mods data to database!");
-                    eprintln!("{}", e);
-                    eprintln!("{:?}", combined_sql);
-                    exit(1);
-                }
+            if let Err(e) = database.execute_batch(&combined_sql) {
+                eprintln!("Failed to insert mods data to database!");
+                eprintln!("{:?}", combined_sql);
+                return Err(e.into());
             }
         }
     }
-    let mut transacter = database.transaction().unwrap();
+    let mut transacter = database.transaction()?;
 
     let collection_header =
-        "name,factorio_version,platform,executable_type,cpuid";
+        "name,factorio_version,platform,executable_type,cpuid,cpu_affinity,nice";
     let csv_collection = format!(
-        "{:?},{:?},{:?},{:?},{:?}",
+        "{:?},{:?},{:?},{:?},{:?},{:?},{}",
         collection_data.benchmark_name,
         collection_data.factorio_version,
         collection_data.os,
         collection_data.executable_type,
         collection_data.cpuid,
+        collection_data.cpu_affinity,
+        collection_data
+            .nice
+            .map(|nice| nice.to_string())
+            .unwrap_or_else(|| "NULL".to_owned()),
     );
 
     let combined_sql = format!(
         "INSERT INTO collection({}) VALUES ({});",
         collection_header, csv_collection
     );
-    match transacter.execute_batch(&combined_sql) {
-        Ok(_) => (),
-        Err(e) => {
-            eprintln!("Failed to insert collection data to database!");
-            eprintln!("{}", e);
-            eprintln!("{:?}", combined_sql);
-            exit(1);
-        }
+    if let Err(e) = transacter.execute_batch(&combined_sql) {
+        eprintln!("Failed to insert collection data to database!");
+        eprintln!("{:?}", combined_sql);
+        return Err(e.into());
     }
     let collection_id = transacter.last_insert_rowid() as u32;
 
     for indiv_mod in &collection_data.mods {
-        let save_point2 = transacter.savepoint().unwrap();
-        match save_point2.execute_named(
+        let save_point2 = transacter.savepoint()?;
+        if let Err(e) = save_point2.execute_named(
             "INSERT INTO collection_mods (collection_id, sha1) VALUES (:collection_id,:sha1)",
             &[
                 (":collection_id", &collection_id),
                 (":sha1", &indiv_mod.sha1),
             ],
         ) {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("Failed to insert collection_mods data to database!");
-                eprintln!("{}", e);
-                exit(1);
-            }
+            eprintln!("Failed to insert collection_mods data to database!");
+            return Err(e.into());
         }
-        save_point2.commit().unwrap();
+        save_point2.commit()?;
     }
-
-    let benchmark_header = "map_name,runs,ticks,map_hash,collection_id";
-    for benchmark in collection_data.benchmarks {
-        let save_point = transacter.savepoint().unwrap();
-        let csv_benchmark = format!(
-            "{:?},{:?},{:?},{:?},{:?}",
-            benchmark.map_name,
-            benchmark.runs,
-            benchmark.ticks,
-            benchmark.map_hash,
-            collection_id,
-        );
-        let combined_sql = format!(
-            "INSERT INTO benchmark({}) VALUES ({});",
-            benchmark_header, csv_benchmark
-        );
-        match save_point.execute_batch(&combined_sql) {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("Failed to insert benchmark data to database!");
-                eprintln!("{}", e);
-                eprintln!("{:?}", combined_sql);
-                exit(1);
-            }
-        }
-        let benchmark_id = save_point.last_insert_rowid() as u32;
-        let verbose_header =
-            "tick_number,wholeUpdate,gameUpdate,circuitNetworkUpdate,transportLinesUpdate,\
-             fluidsUpdate,entityUpdate,mapGenerator,electricNetworkUpdate,logisticManagerUpdate,\
-             constructionManagerUpdate,pathFinder,trains,trainPathFinder,commander,chartRefresh,\
-             luaGarbageIncremental,chartUpdate,scriptUpdate,run_index,benchmark_id";
-        let mut combined_sql = String::new();
-        for line in benchmark.verbose_data {
-            combined_sql.push_str(&format!(
-                "INSERT INTO verbose({}) VALUES ({},{});\n",
-                verbose_header, line, benchmark_id
-            ));
-        }
-        match save_point.execute_batch(&combined_sql) {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("Failed to insert verbose data to database!");
-                eprintln!("{}", e);
-                exit(1);
-            }
+    if let Some(environment) = &collection_data.environment {
+        if let Err(e) =
+            insert_environment_row(&transacter, collection_id, environment)
+        {
+            eprintln!(
+                "Failed to insert collection_environment data to database!"
+            );
+            return Err(e.into());
         }
-        save_point.commit().unwrap();
     }
-    transacter.commit().unwrap();
-    print_results(&database, collection_id).unwrap();
+    transacter.commit()?;
+    Ok(collection_id)
 }
 
-fn print_results(
+fn insert_environment_row(
     database: &Connection,
     collection_id: u32,
-) -> Result<(), Box<dyn std::error::Error>> {
-    eprintln!("Collection id {}", collection_id);
-    let mut ids_to_collect: Vec<(u32, String)> = Vec::new();
+    environment: &SystemEnvironment,
+) -> rusqlite::Result<()> {
+    database.execute_named(
+        "INSERT INTO collection_environment (collection_id, logical_cores, \
+        physical_cores, cpu_governor, total_memory_kb, kernel_version, \
+        turbo_enabled, hostname, helper_version) VALUES (:collection_id, \
+        :logical_cores, :physical_cores, :cpu_governor, :total_memory_kb, \
+        :kernel_version, :turbo_enabled, :hostname, :helper_version)",
+        &[
+            (":collection_id", &collection_id),
+            (":logical_cores", &environment.logical_cores),
+            (":physical_cores", &environment.physical_cores),
+            (":cpu_governor", &environment.cpu_governor),
+            (
+                ":total_memory_kb",
+                &environment.total_memory_kb.map(|kb| kb as i64),
+            ),
+            (":kernel_version", &environment.kernel_version),
+            (":turbo_enabled", &environment.turbo_enabled),
+            (":hostname", &environment.hostname),
+            (":helper_version", &environment.helper_version),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Gets the environment a collection was ran in, if it was recorded.
+pub fn get_collection_environment(
+    database: &Connection,
+    collection_id: u32,
+) -> rusqlite::Result<Option<SystemEnvironment>> {
+    database
+        .query_row(
+            "SELECT logical_cores, physical_cores, cpu_governor, \
+            total_memory_kb, kernel_version, turbo_enabled, hostname, \
+            helper_version FROM collection_environment \
+            WHERE collection_id = ?",
+            [collection_id],
+            |row| {
+                Ok(SystemEnvironment {
+                    logical_cores: row.get(0)?,
+                    physical_cores: row.get(1)?,
+                    cpu_governor: row.get(2)?,
+                    total_memory_kb: row
+                        .get::<_, Option<i64>>(3)?
+                        .map(|kb| kb as u64),
+                    kernel_version: row.get(4)?,
+                    turbo_enabled: row.get(5)?,
+                    hostname: row.get(6)?,
+                    helper_version: row.get(7)?,
+                })
+            },
+        )
+        .optional()
+}
+
+fn insert_benchmark_rows(
+    database: &Connection,
+    collection_id: u32,
+    benchmark: &BenchmarkData,
+) -> Result<(), FbhError> {
+    let benchmark_header = "map_name,runs,ticks,map_hash,collection_id,\
+        warmup_ticks,outlier_policy,achieved_precision";
+    let csv_benchmark = format!(
+        "{:?},{:?},{:?},{:?},{:?},{:?},{:?},{}",
+        benchmark.map_name,
+        benchmark.runs,
+        benchmark.ticks,
+        benchmark.map_hash,
+        collection_id,
+        benchmark.warmup_ticks,
+        benchmark.outlier_policy.to_string(),
+        match benchmark.achieved_precision {
+            Some(precision) if precision.is_finite() => precision.to_string(),
+            _ => "NULL".to_owned(),
+        },
+    );
+    let combined_sql = format!(
+        "INSERT INTO benchmark({}) VALUES ({});",
+        benchmark_header, csv_benchmark
+    );
+    if let Err(e) = database.execute_batch(&combined_sql) {
+        eprintln!("Failed to insert benchmark data to database!");
+        eprintln!("{:?}", combined_sql);
+        return Err(e.into());
+    }
+    let benchmark_id = database.last_insert_rowid() as u32;
+    let verbose_header =
+        "tick_number,wholeUpdate,gameUpdate,circuitNetworkUpdate,transportLinesUpdate,\
+         fluidsUpdate,entityUpdate,mapGenerator,electricNetworkUpdate,logisticManagerUpdate,\
+         constructionManagerUpdate,pathFinder,trains,trainPathFinder,commander,chartRefresh,\
+         luaGarbageIncremental,chartUpdate,scriptUpdate,run_index,benchmark_id";
+    let mut combined_sql = String::new();
+    for line in &benchmark.verbose_data {
+        combined_sql.push_str(&format!(
+            "INSERT INTO verbose({}) VALUES ({},{});\n",
+            verbose_header, line, benchmark_id
+        ));
+    }
+    if let Err(e) = database.execute_batch(&combined_sql) {
+        eprintln!("Failed to insert verbose data to database!");
+        return Err(e.into());
+    }
+    delete_benchmark_failure_row(database, collection_id, &benchmark.map_hash)?;
+    Ok(())
+}
+
+/// A benchmark as stored within a collection in the results database.
+#[derive(Debug, Clone)]
+pub struct BenchmarkRecord {
+    pub benchmark_id: u32,
+    pub map_name: String,
+    pub map_hash: String,
+    pub runs: u32,
+    pub ticks: u32,
+    pub warmup_ticks: u32,
+    pub outlier_policy: OutlierPolicy,
+    pub achieved_precision: Option<f64>,
+}
+
+/// The columns of the benchmark table read into a `BenchmarkRecord`.
+const BENCHMARK_RECORD_COLUMNS: &str =
+    "benchmark_id, map_name, map_hash, runs, ticks, warmup_ticks, \
+    outlier_policy, achieved_precision";
+
+/// Gets the name, Factorio version and cpuid of a collection.
+pub fn get_collection_header(
+    database: &Connection,
+    collection_id: u32,
+) -> rusqlite::Result<(String, String, String)> {
+    database.query_row(
+        "SELECT name, factorio_version, cpuid FROM collection \
+        WHERE collection_id = ?",
+        [collection_id],
+        |row| {
+            let cpuid: Option<String> = row.get(2)?;
+            Ok((row.get(0)?, row.get(1)?, cpuid.unwrap_or_default()))
+        },
+    )
+}
+
+/// Gets a single benchmark by its id.
+pub fn get_benchmark(
+    database: &Connection,
+    benchmark_id: u32,
+) -> rusqlite::Result<BenchmarkRecord> {
+    database.query_row(
+        &format!(
+            "SELECT {} FROM benchmark WHERE benchmark_id = ?",
+            BENCHMARK_RECORD_COLUMNS
+        ),
+        [benchmark_id],
+        benchmark_record_from_row,
+    )
+}
+
+/// Gets all benchmarks which are part of a collection.
+pub fn get_collection_benchmarks(
+    database: &Connection,
+    collection_id: u32,
+) -> rusqlite::Result<Vec<BenchmarkRecord>> {
+    let mut statement = database.prepare(&format!(
+        "SELECT {} FROM benchmark WHERE collection_id = ? \
+        ORDER BY benchmark_id",
+        BENCHMARK_RECORD_COLUMNS
+    ))?;
+    let rows =
+        statement.query_map([collection_id], benchmark_record_from_row)?;
+    rows.collect()
+}
+
+fn benchmark_record_from_row(
+    row: &rusqlite::Row,
+) -> rusqlite::Result<BenchmarkRecord> {
+    Ok(BenchmarkRecord {
+        benchmark_id: row.get(0)?,
+        map_name: row.get(1)?,
+        map_hash: row.get(2)?,
+        runs: row.get(3)?,
+        ticks: row.get(4)?,
+        warmup_ticks: row.get(5)?,
+        outlier_policy: parse_stored_policy(&row.get::<_, String>(6)?),
+        achieved_precision: row.get(7)?,
+    })
+}
+
+fn parse_stored_policy(stored: &str) -> OutlierPolicy {
+    stored.parse().unwrap_or_else(|e| {
+        warn!("Ignoring stored outlier policy: {}", e);
+        OutlierPolicy::None
+    })
+}
+
+/// Gets every value of a verbose timing column for a benchmark, converted
+/// from nanoseconds to milliseconds. The warm-up ticks and outlier runs of
+/// the benchmark are left out.
+pub fn get_verbose_column(
+    database: &Connection,
+    benchmark_id: u32,
+    column: &str,
+) -> rusqlite::Result<Vec<f64>> {
+    let benchmark = get_benchmark(database, benchmark_id)?;
+    let samples = get_verbose_samples(database, benchmark_id, column)?;
+    let whole_update = if column == "wholeUpdate" {
+        samples.clone()
+    } else {
+        get_verbose_samples(database, benchmark_id, "wholeUpdate")?
+    };
+    Ok(filter_samples(
+        &samples,
+        &whole_update,
+        benchmark.warmup_ticks,
+        &benchmark.outlier_policy,
+    ))
+}
+
+/// Gets every value of every standard verbose timing column for a benchmark
+/// with a single query, like `get_verbose_column` does for one column.
+pub fn get_verbose_columns(
+    database: &Connection,
+    benchmark_id: u32,
+) -> rusqlite::Result<Vec<(&'static str, Vec<f64>)>> {
+    let benchmark = get_benchmark(database, benchmark_id)?;
+    let columns: Vec<&'static str> =
+        STANDARD_VERBOSE_TIMINGS.split(',').collect();
+    let values = columns
+        .iter()
+        .map(|column| format!("{} / 1000000.0", column))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut statement = database.prepare(&format!(
+        "SELECT run_index, tick_number, {} FROM verbose \
+        WHERE benchmark_id = ? ORDER BY run_index, tick_number",
+        values
+    ))?;
+    let mut samples: Vec<Vec<TickSample>> =
+        columns.iter().map(|_| Vec::new()).collect();
+    let mut rows = statement.query([benchmark_id])?;
+    while let Some(row) = rows.next()? {
+        let (run, tick) = (row.get(0)?, row.get(1)?);
+        for (i, column_samples) in samples.iter_mut().enumerate() {
+            column_samples.push(TickSample {
+                run,
+                tick,
+                value: row.get(i + 2)?,
+            });
+        }
+    }
+    let whole_update = columns
+        .iter()
+        .position(|column| *column == "wholeUpdate")
+        .map(|i| samples[i].clone())
+        .unwrap_or_default();
+    Ok(columns
+        .into_iter()
+        .zip(samples)
+        .map(|(column, column_samples)| {
+            let filtered = filter_samples(
+                &column_samples,
+                &whole_update,
+                benchmark.warmup_ticks,
+                &benchmark.outlier_policy,
+            );
+            (column, filtered)
+        })
+        .collect())
+}
+
+/// Gets the mean in milliseconds of a verbose timing column for every run of
+/// a benchmark, leaving out the warm-up ticks and outlier runs.
+pub fn get_verbose_run_means(
+    database: &Connection,
+    benchmark_id: u32,
+    column: &str,
+) -> rusqlite::Result<Vec<f64>> {
+    let benchmark = get_benchmark(database, benchmark_id)?;
+    let samples = get_verbose_samples(database, benchmark_id, column)?;
+    let whole_update = if column == "wholeUpdate" {
+        samples.clone()
+    } else {
+        get_verbose_samples(database, benchmark_id, "wholeUpdate")?
+    };
+    Ok(run_means(
+        &samples,
+        &whole_update,
+        benchmark.warmup_ticks,
+        &benchmark.outlier_policy,
+    ))
+}
+
+/// Gets every value of a verbose timing column for a benchmark in
+/// milliseconds, with the run and tick it belongs to.
+fn get_verbose_samples(
+    database: &Connection,
+    benchmark_id: u32,
+    column: &str,
+) -> rusqlite::Result<Vec<TickSample>> {
+    // The column is put into the query as is
+    if !column.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(rusqlite::Error::InvalidColumnName(column.to_string()));
+    }
     let mut statement = database.prepare(&format!(
-        "SELECT benchmark_id, map_name from benchmark where collection_id = {}",
-        collection_id
+        "SELECT run_index, tick_number, {} / 1000000.0 FROM verbose \
+        WHERE benchmark_id = ? ORDER BY run_index, tick_number",
+        column
     ))?;
-    let rows = statement
-        .query_map(NO_PARAMS, |row| {
-            let bench_id = row.get(0)?;
-            let map_name = row.get(1)?;
-            Ok((bench_id, map_name))
+    let rows = statement.query_map([benchmark_id], |row| {
+        Ok(TickSample {
+            run: row.get(0)?,
+            tick: row.get(1)?,
+            value: row.get(2)?,
         })
-        .unwrap();
+    })?;
+    rows.collect()
+}
 
+/// Loads a collection with its mods, benchmarks and their verbose data.
+pub fn get_collection_data(
+    database: &Connection,
+    collection_id: u32,
+) -> rusqlite::Result<CollectionData> {
+    let mut collection_data = database.query_row(
+        "SELECT name, factorio_version, platform, executable_type, cpuid, \
+        cpu_affinity, nice FROM collection WHERE collection_id = ?",
+        [collection_id],
+        |row| {
+            let cpuid: Option<String> = row.get(4)?;
+            Ok(CollectionData {
+                benchmark_name: row.get(0)?,
+                factorio_version: row.get(1)?,
+                os: row.get(2)?,
+                executable_type: row.get(3)?,
+                cpuid: cpuid.unwrap_or_default(),
+                cpu_affinity: row.get(5)?,
+                nice: row.get(6)?,
+                ..CollectionData::default()
+            })
+        },
+    )?;
+    let mut statement = database.prepare(
+        "SELECT mods.name, mods.version, mods.sha1 FROM collection_mods \
+        JOIN mods ON collection_mods.sha1 = mods.sha1 \
+        WHERE collection_mods.collection_id = ?",
+    )?;
+    let rows = statement.query_map([collection_id], |row| {
+        Ok(Mod {
+            name: row.get(0)?,
+            file_name: String::new(),
+            version: row.get(1)?,
+            sha1: row.get(2)?,
+        })
+    })?;
     for r in rows {
-        ids_to_collect.push(r.unwrap());
+        collection_data.mods.insert(r?);
+    }
+    collection_data.environment =
+        get_collection_environment(database, collection_id)?;
+    collection_data.failures = get_benchmark_failures(database, collection_id)?;
+    for benchmark in get_collection_benchmarks(database, collection_id)? {
+        collection_data.benchmarks.push(BenchmarkData {
+            verbose_data: get_verbose_rows(database, benchmark.benchmark_id)?,
+            map_name: benchmark.map_name,
+            map_hash: benchmark.map_hash,
+            runs: benchmark.runs,
+            ticks: benchmark.ticks,
+            warmup_ticks: benchmark.warmup_ticks,
+            outlier_policy: benchmark.outlier_policy,
+            achieved_precision: benchmark.achieved_precision,
+        });
+    }
+    Ok(collection_data)
+}
+
+/// Gets the verbose data of a benchmark as CSV rows, in the same layout as
+/// `BenchmarkData::verbose_data`.
+fn get_verbose_rows(
+    database: &Connection,
+    benchmark_id: u32,
+) -> rusqlite::Result<Vec<String>> {
+    let columns = format!("tick_number,{},run_index", STANDARD_VERBOSE_TIMINGS);
+    let column_count = columns.split(',').count();
+    let mut statement = database.prepare(&format!(
+        "SELECT {} FROM verbose WHERE benchmark_id = ? \
+        ORDER BY run_index, tick_number",
+        columns
+    ))?;
+    let rows = statement.query_map([benchmark_id], |row| {
+        let mut values = Vec::with_capacity(column_count);
+        for i in 0..column_count {
+            values.push(row.get::<_, i64>(i)?.to_string());
+        }
+        Ok(values.join(","))
+    })?;
+    rows.collect()
+}
+
+/// Writes the per tick wholeUpdate of every benchmark in the collections to
+/// a CSV file, one column per benchmark. Each tick is the fastest of the
+/// runs kept by the benchmark's outlier policy, after its warm-up.
+pub fn write_results_csv(
+    database: &Connection,
+    collection_ids: &[u32],
+    path: &Path,
+) -> Result<(), FbhError> {
+    let mut ids_to_collect: Vec<(u32, String)> = Vec::new();
+    let mut sample_filters: Vec<String> = Vec::new();
+    for collection_id in collection_ids {
+        for benchmark in get_collection_benchmarks(database, *collection_id)? {
+            sample_filters.push(csv_sample_filter(database, &benchmark)?);
+            ids_to_collect.push((benchmark.benchmark_id, benchmark.map_name));
+        }
     }
 
-    assert!(!ids_to_collect.is_empty());
+    if ids_to_collect.is_empty() {
+        return Err(FbhError::InvalidInput(format!(
+            "Collections {:?} have no benchmarks",
+            collection_ids
+        )));
+    }
 
     let mut pivot_statement = String::new();
     pivot_statement
@@ -256,12 +883,12 @@ fn print_results(
     }
     pivot_statement = pivot_statement.trim_end_matches(",\n").to_string();
     pivot_statement.push_str(" FROM \n");
-    for id in &ids_to_collect {
+    for (id, sample_filter) in ids_to_collect.iter().zip(&sample_filters) {
         pivot_statement
             .push_str("(SELECT tick_number, min(wholeUpdate) / 1000000.0 as wholeUpdate \n");
         pivot_statement.push_str(&format!(
-            "from verbose where benchmark_id = {} group by tick_number) as id{},\n",
-            id.0, id.0
+            "from verbose where benchmark_id = {} {} group by tick_number) as id{},\n",
+            id.0, sample_filter, id.0
         ));
     }
     pivot_statement = pivot_statement.trim_end_matches(",\n").to_string();
@@ -281,7 +908,7 @@ fn print_results(
         }
     }
 
-    let mut csv_file = std::fs::File::create("data.csv")?;
+    let mut csv_file = std::fs::File::create(path)?;
 
     let mut csv_header = String::from("tick_number,");
     for i in &ids_to_collect {
@@ -291,50 +918,123 @@ fn print_results(
     writeln!(csv_file, "{}", csv_header)?;
 
     let mut c = database.prepare(&pivot_statement)?;
-    let rows = c
-        .query_map(NO_PARAMS, |row| {
-            assert!(row.column_count() > 0);
-            let mut row_writer = String::new();
-            let tick_number: u32 = row.get(0)?;
-            row_writer.push_str(&format!("{}", tick_number));
-            for i in 1..row.column_count() {
-                row_writer.push_str(",");
-                row_writer.push_str(&format!("{:.3}", row.get::<_, f64>(i)?));
-            }
-            Ok(row_writer)
-        })
-        .unwrap();
+    let rows = c.query_map(NO_PARAMS, |row| {
+        let mut row_writer = String::new();
+        let tick_number: u32 = row.get(0)?;
+        row_writer.push_str(&format!("{}", tick_number));
+        for i in 1..row.column_count() {
+            row_writer.push(',');
+            row_writer.push_str(&format!("{:.3}", row.get::<_, f64>(i)?));
+        }
+        Ok(row_writer)
+    })?;
 
     for r in rows {
-        writeln!(csv_file, "{}", r.unwrap())?;
+        writeln!(csv_file, "{}", r?)?;
     }
     Ok(())
 }
 
-fn create_tables_in_db(database: &Connection) {
-    match database.execute_batch(CREATE_SQL) {
-        Ok(_) => (),
-        Err(e) => {
-            eprintln!("Couldn't create sql in db?");
-            eprintln!("{}", e);
-            exit(1);
-        }
+/// Builds the SQL conditions restricting the verbose rows of a benchmark to
+/// its runs kept by the outlier policy, after the warm-up ticks.
+fn csv_sample_filter(
+    database: &Connection,
+    benchmark: &BenchmarkRecord,
+) -> rusqlite::Result<String> {
+    if benchmark.warmup_ticks == 0 && benchmark.outlier_policy.is_none() {
+        return Ok(String::new());
     }
+    let whole_update = discard_warmup(
+        &get_verbose_samples(database, benchmark.benchmark_id, "wholeUpdate")?,
+        benchmark.warmup_ticks,
+    );
+    let runs: Vec<String> =
+        retained_runs(&whole_update, &benchmark.outlier_policy)
+            .iter()
+            .map(|run| run.to_string())
+            .collect();
+    let first_tick = whole_update.iter().map(|s| s.tick).min().unwrap_or(0);
+    Ok(format!(
+        "and run_index in ({}) and tick_number >= {}",
+        runs.join(","),
+        first_tick
+    ))
+}
+
+fn create_tables_in_db(database: &Connection) -> rusqlite::Result<()> {
+    database.execute_batch(CREATE_SQL)?;
+    migrate_tables_in_db(database)
+}
+
+/// Adds the columns introduced since a results database was created.
+fn migrate_tables_in_db(database: &Connection) -> rusqlite::Result<()> {
+    add_missing_column(
+        database,
+        "collection",
+        "cpu_affinity",
+        "text NOT NULL DEFAULT ''",
+    )?;
+    add_missing_column(database, "collection", "nice", "integer NULL")?;
+    add_missing_column(
+        database,
+        "benchmark",
+        "warmup_ticks",
+        "integer NOT NULL DEFAULT 0",
+    )?;
+    add_missing_column(
+        database,
+        "benchmark",
+        "outlier_policy",
+        "text NOT NULL DEFAULT 'none'",
+    )?;
+    add_missing_column(database, "benchmark", "achieved_precision", "real NULL")
+}
+
+fn add_missing_column(
+    database: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let mut statement =
+        database.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = statement
+        .query_map(NO_PARAMS, |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    if !columns.iter().any(|c| c == column) {
+        database.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {};",
+            table, column, definition
+        ))?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
-    use super::print_results;
+    use super::get_collection_data;
+    use super::get_map_duration_row;
+    use super::insert_benchmark_failure_row;
+    use super::insert_benchmark_rows;
+    use super::insert_collection_rows;
+    use super::insert_map_duration_row;
+    use super::migrate_tables_in_db;
+    use super::write_results_csv;
+    use super::CREATE_SQL;
     use crate::performance_results::collection_data::BenchmarkData;
+    use crate::performance_results::collection_data::BenchmarkFailure;
     use crate::performance_results::collection_data::CollectionData;
-    use crate::performance_results::database::upload_to_db;
-    use crate::performance_results::database::DB_CONNECTION;
+    use crate::performance_results::collection_data::MapDuration;
+    use crate::performance_results::collection_data::Mod;
+    use crate::performance_results::statistics::OutlierPolicy;
     use crate::util::query_system_cpuid;
+    use crate::util::query_system_environment;
+    use rusqlite::Connection;
+    use rusqlite::NO_PARAMS;
     use std::collections::BTreeSet;
 
-    #[test]
-    fn test_collection() {
-        let data = CollectionData {
+    fn test_data() -> CollectionData {
+        CollectionData {
             benchmark_name: String::from("TEST"),
             cpuid: query_system_cpuid(),
             executable_type: "TEST".to_owned(),
@@ -342,20 +1042,156 @@ mod test {
             os: "TEST".to_owned(),
             mods: BTreeSet::new(),
             benchmarks: vec![BenchmarkData {
-                map_hash: "".to_owned(),
+                map_hash: "0".repeat(64),
                 map_name: "TEST".to_owned(),
                 runs: 1,
                 ticks: 1,
                 verbose_data: vec![
                     "1,5000,3000,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1".to_owned(),
                 ],
+                ..Default::default()
             }],
-        };
-        let database = DB_CONNECTION.lock().unwrap();
-        let collection_id = 1;
-        if print_results(&database, collection_id).is_err() {
-            upload_to_db(data);
-            print_results(&database, collection_id).unwrap();
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_collection() {
+        let mut database = Connection::open_in_memory().unwrap();
+        database.execute_batch(CREATE_SQL).unwrap();
+        let data = test_data();
+        let path = std::env::temp_dir()
+            .join(format!("fbh-results-test-{}.csv", std::process::id()));
+        assert!(write_results_csv(&database, &[1], &path).is_err());
+        let collection_id =
+            insert_collection_rows(&mut database, &data).unwrap();
+        for benchmark in &data.benchmarks {
+            insert_benchmark_rows(&database, collection_id, benchmark).unwrap();
         }
+        write_results_csv(&database, &[collection_id], &path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(csv.contains("TEST.wholeUpdate"), "{}", csv);
+    }
+
+    #[test]
+    fn test_collection_data_round_trip() {
+        let mut database = Connection::open_in_memory().unwrap();
+        database.execute_batch(CREATE_SQL).unwrap();
+        let mut data = test_data();
+        data.mods
+            .insert(Mod::new("TEST", "", "1.0.0", &"1".repeat(40)));
+        data.benchmarks[0].outlier_policy = OutlierPolicy::Mad(3.0);
+        data.benchmarks[0].achieved_precision = Some(0.0045);
+        data.cpu_affinity = "0-3".to_owned();
+        data.nice = Some(-5);
+        data.environment = Some(query_system_environment());
+        let collection_id =
+            insert_collection_rows(&mut database, &data).unwrap();
+        // A map which failed before succeeds when retried on resume
+        let retried = BenchmarkFailure {
+            map_name: data.benchmarks[0].map_name.clone(),
+            map_hash: data.benchmarks[0].map_hash.clone(),
+            reason: "Factorio exited with an error".to_owned(),
+        };
+        insert_benchmark_failure_row(&database, collection_id, &retried)
+            .unwrap();
+        insert_benchmark_rows(&database, collection_id, &data.benchmarks[0])
+            .unwrap();
+        // A map which fails again replaces its earlier failure
+        let failure = BenchmarkFailure {
+            map_name: "HUNG".to_owned(),
+            map_hash: "1".repeat(64),
+            reason: "Factorio timed out".to_owned(),
+        };
+        insert_benchmark_failure_row(&database, collection_id, &failure)
+            .unwrap();
+        insert_benchmark_failure_row(&database, collection_id, &failure)
+            .unwrap();
+
+        let loaded = get_collection_data(&database, collection_id).unwrap();
+        assert_eq!(loaded.benchmark_name, data.benchmark_name);
+        assert_eq!(loaded.mods, data.mods);
+        assert_eq!(loaded.cpu_affinity, data.cpu_affinity);
+        assert_eq!(loaded.nice, data.nice);
+        assert_eq!(loaded.environment, data.environment);
+        assert_eq!(loaded.failures, vec![failure]);
+        assert_eq!(loaded.benchmarks.len(), 1);
+        assert_eq!(loaded.benchmarks[0].map_hash, data.benchmarks[0].map_hash);
+        assert_eq!(
+            loaded.benchmarks[0].verbose_data,
+            data.benchmarks[0].verbose_data
+        );
+        assert_eq!(
+            loaded.benchmarks[0].outlier_policy,
+            OutlierPolicy::Mad(3.0)
+        );
+        assert_eq!(loaded.benchmarks[0].achieved_precision, Some(0.0045));
+    }
+
+    #[test]
+    fn test_map_duration() {
+        let database = Connection::open_in_memory().unwrap();
+        database.execute_batch(CREATE_SQL).unwrap();
+        let hash = "0".repeat(64);
+        let duration = MapDuration {
+            initialization_time: 2.5,
+            per_tick_time: 1.25,
+            per_run_overhead_time: 0.5,
+        };
+        assert_eq!(
+            get_map_duration_row(&database, &hash, "cpu", "1.1.0").unwrap(),
+            None
+        );
+        insert_map_duration_row(&database, &hash, "cpu", "1.1.0", &duration)
+            .unwrap();
+        let faster = MapDuration {
+            per_tick_time: 1.0,
+            ..duration
+        };
+        insert_map_duration_row(&database, &hash, "cpu", "1.1.0", &faster)
+            .unwrap();
+        assert_eq!(
+            get_map_duration_row(&database, &hash, "cpu", "1.1.0").unwrap(),
+            Some(faster)
+        );
+        assert_eq!(
+            get_map_duration_row(&database, &hash, "cpu", "1.1.1").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_migrate_tables() {
+        let database = Connection::open_in_memory().unwrap();
+        database
+            .execute_batch(
+                "CREATE TABLE collection (collection_id integer);\
+                CREATE TABLE benchmark (benchmark_id integer);",
+            )
+            .unwrap();
+        migrate_tables_in_db(&database).unwrap();
+        migrate_tables_in_db(&database).unwrap();
+        database
+            .execute(
+                "INSERT INTO benchmark (benchmark_id) VALUES (1)",
+                NO_PARAMS,
+            )
+            .unwrap();
+        let (warmup_ticks, outlier_policy, achieved_precision): (
+            u32,
+            String,
+            Option<f64>,
+        ) = database
+            .query_row(
+                "SELECT warmup_ticks, outlier_policy, achieved_precision \
+                FROM benchmark",
+                NO_PARAMS,
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(warmup_ticks, 0);
+        assert_eq!(outlier_policy, "none");
+        assert_eq!(achieved_precision, None);
     }
 }
@@ -1,2 +1,7 @@
+pub mod breakdown;
+pub mod checkpoint;
 pub mod collection_data;
+pub mod compare;
 pub mod database;
+pub mod report;
+pub mod statistics;
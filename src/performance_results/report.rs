@@ -0,0 +1,374 @@
+//! Reports of collection results, written after a run in the format chosen
+//! with --output-format.
+
+use crate::benchmark_runner::STANDARD_VERBOSE_TIMINGS;
+use crate::error::FbhError;
+use crate::performance_results::collection_data::BenchmarkData;
+use crate::performance_results::collection_data::BenchmarkFailure;
+use crate::performance_results::collection_data::CollectionData;
+use crate::performance_results::collection_data::Mod;
+use crate::performance_results::database::get_collection_data;
+use crate::performance_results::database::lock_database;
+use crate::performance_results::database::write_results_csv;
+use crate::performance_results::statistics::filter_samples;
+use crate::performance_results::statistics::summarize;
+use crate::performance_results::statistics::Summary;
+use crate::performance_results::statistics::TickSample;
+use crate::util::SystemEnvironment;
+use core::str::FromStr;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The CSV file written when no --output is given.
+const DEFAULT_CSV_PATH: &str = "data.csv";
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    /// A summary table per collection.
+    Table,
+    /// The per tick wholeUpdate of every map, one column per map.
+    #[default]
+    Csv,
+    /// A JSON document of every collection, see `CollectionReport`.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<OutputFormat, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!(
+                "Unknown output format {:?}, expected json, csv or table",
+                s
+            )),
+        }
+    }
+}
+
+/// How the results of a run are reported once all of its sets finish.
+#[derive(Debug, Clone, Default)]
+pub struct ReportOptions {
+    pub format: OutputFormat,
+    /// Where to write the report. Table and JSON reports go to stdout if not
+    /// set, CSV reports to data.csv in the working directory.
+    pub output: Option<PathBuf>,
+    /// Include every tick of every run in JSON reports.
+    pub per_tick: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ReportDocument {
+    collections: Vec<CollectionReport>,
+}
+
+/// The results of a collection, as written in JSON reports.
+#[derive(Debug, Serialize)]
+pub struct CollectionReport {
+    pub collection_id: u32,
+    pub set_name: String,
+    pub factorio_version: String,
+    pub os: String,
+    pub executable_type: String,
+    pub cpuid: String,
+    /// The CPUs Factorio was pinned to, empty if not pinned.
+    pub cpu_affinity: String,
+    pub nice: Option<i32>,
+    pub environment: Option<SystemEnvironment>,
+    pub mods: Vec<Mod>,
+    pub maps: Vec<MapReport>,
+    /// The maps which could not be benchmarked.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<BenchmarkFailure>,
+}
+
+/// The results of a single map within a collection. Timings are in
+/// milliseconds. The summaries leave out warm-up ticks and outlier runs, the
+/// tick rows do not.
+#[derive(Debug, Serialize)]
+pub struct MapReport {
+    pub map_name: String,
+    pub map_hash: String,
+    pub runs: u32,
+    pub ticks: u32,
+    pub whole_update: Option<Summary>,
+    pub game_update: Option<Summary>,
+    /// See `BenchmarkData::achieved_precision`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub achieved_precision: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tick_rows: Option<Vec<TickRow>>,
+}
+
+/// The timings of a single tick of a run, keyed by verbose column.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TickRow {
+    pub run: u32,
+    pub tick: u32,
+    #[serde(flatten)]
+    pub timings: BTreeMap<String, f64>,
+}
+
+impl CollectionReport {
+    pub fn from_collection_data(
+        collection_id: u32,
+        data: &CollectionData,
+        per_tick: bool,
+    ) -> CollectionReport {
+        CollectionReport {
+            collection_id,
+            set_name: data.benchmark_name.clone(),
+            factorio_version: data.factorio_version.clone(),
+            os: data.os.clone(),
+            executable_type: data.executable_type.clone(),
+            cpuid: data.cpuid.clone(),
+            cpu_affinity: data.cpu_affinity.clone(),
+            nice: data.nice,
+            environment: data.environment.clone(),
+            mods: data.mods.iter().cloned().collect(),
+            maps: data
+                .benchmarks
+                .iter()
+                .map(|b| MapReport::from_benchmark_data(b, per_tick))
+                .collect(),
+            failures: data.failures.clone(),
+        }
+    }
+}
+
+impl MapReport {
+    pub fn from_benchmark_data(
+        benchmark: &BenchmarkData,
+        per_tick: bool,
+    ) -> MapReport {
+        let rows = parse_verbose_rows(&benchmark.verbose_data);
+        let samples = |name: &str| -> Vec<TickSample> {
+            rows.iter()
+                .filter_map(|r| {
+                    r.timings.get(name).map(|value| TickSample {
+                        run: r.run,
+                        tick: r.tick,
+                        value: *value,
+                    })
+                })
+                .collect()
+        };
+        let whole_update = samples("wholeUpdate");
+        let column = |name: &str| -> Vec<f64> {
+            filter_samples(
+                &samples(name),
+                &whole_update,
+                benchmark.warmup_ticks,
+                &benchmark.outlier_policy,
+            )
+        };
+        MapReport {
+            map_name: benchmark.map_name.clone(),
+            map_hash: benchmark.map_hash.clone(),
+            runs: benchmark.runs,
+            ticks: benchmark.ticks,
+            whole_update: summarize(&column("wholeUpdate")),
+            game_update: summarize(&column("gameUpdate")),
+            achieved_precision: benchmark.achieved_precision,
+            tick_rows: if per_tick { Some(rows) } else { None },
+        }
+    }
+}
+
+/// Parses verbose CSV rows of tick, timings in nanoseconds, and run index
+/// into rows with timings in milliseconds.
+fn parse_verbose_rows(verbose_data: &[String]) -> Vec<TickRow> {
+    let columns: Vec<&str> = STANDARD_VERBOSE_TIMINGS.split(',').collect();
+    let mut rows = Vec::with_capacity(verbose_data.len());
+    for line in verbose_data {
+        let values: Vec<&str> = line.split(',').collect();
+        if values.len() != columns.len() + 2 {
+            warn!("Skipping malformed verbose row {:?}", line);
+            continue;
+        }
+        let timings = columns
+            .iter()
+            .zip(&values[1..=columns.len()])
+            .map(|(column, value)| {
+                let ns = value.trim().parse::<f64>().unwrap_or_default();
+                (column.to_string(), ns / 1_000_000.0)
+            })
+            .collect();
+        rows.push(TickRow {
+            tick: values[0].trim().parse().unwrap_or_default(),
+            run: values[columns.len() + 1].trim().parse().unwrap_or_default(),
+            timings,
+        });
+    }
+    rows
+}
+
+/// Writes the report of the collections finished during a run.
+pub fn write_collection_reports(
+    collection_ids: &[u32],
+    options: &ReportOptions,
+) -> Result<(), FbhError> {
+    if collection_ids.is_empty() {
+        return Ok(());
+    }
+    for collection_id in collection_ids {
+        eprintln!("Collection id {}", collection_id);
+    }
+    let database = lock_database()?;
+    if options.format == OutputFormat::Csv {
+        let path = options
+            .output
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CSV_PATH));
+        return write_results_csv(&database, collection_ids, &path);
+    }
+    let mut reports = Vec::new();
+    for collection_id in collection_ids {
+        let data = get_collection_data(&database, *collection_id)?;
+        reports.push(CollectionReport::from_collection_data(
+            *collection_id,
+            &data,
+            options.per_tick,
+        ));
+    }
+    let rendered = match options.format {
+        OutputFormat::Json => {
+            let document = ReportDocument {
+                collections: reports,
+            };
+            serde_json::to_string_pretty(&document).unwrap()
+        }
+        _ => render_table(&reports),
+    };
+    match &options.output {
+        Some(path) => write_report_file(path, &rendered),
+        None => {
+            println!("{}", rendered);
+            Ok(())
+        }
+    }
+}
+
+fn write_report_file(path: &Path, rendered: &str) -> Result<(), FbhError> {
+    std::fs::write(path, rendered)?;
+    println!("Wrote results to {:?}", path);
+    Ok(())
+}
+
+fn render_table(reports: &[CollectionReport]) -> String {
+    let mut table = String::new();
+    for report in reports {
+        write!(
+            table,
+            "Collection {} {:?}, Factorio {}, {}",
+            report.collection_id,
+            report.set_name,
+            report.factorio_version,
+            report.cpuid
+        )
+        .unwrap();
+        if !report.cpu_affinity.is_empty() {
+            write!(table, ", pinned to CPUs {}", report.cpu_affinity).unwrap();
+        }
+        if let Some(nice) = report.nice {
+            write!(table, ", nice {}", nice).unwrap();
+        }
+        writeln!(table).unwrap();
+        writeln!(
+            table,
+            "    {:<40} {:>12} {:>10} {:>10} {:>10} {:>10} {:>9}",
+            "map",
+            "runs x ticks",
+            "mean ms",
+            "median ms",
+            "stddev ms",
+            "p95 ms",
+            "95% ci"
+        )
+        .unwrap();
+        for map in &report.maps {
+            let runs_ticks = format!("{} x {}", map.runs, map.ticks);
+            match &map.whole_update {
+                Some(s) => writeln!(
+                    table,
+                    "    {:<40} {:>12} {:>10.3} {:>10.3} {:>10.3} {:>10.3} {:>9}",
+                    map.map_name,
+                    runs_ticks,
+                    s.mean,
+                    s.median,
+                    s.stddev,
+                    s.p95,
+                    map.achieved_precision
+                        .map(|p| format!("±{:.2}%", p * 100.0))
+                        .unwrap_or_else(|| "-".to_owned())
+                ),
+                None => writeln!(
+                    table,
+                    "    {:<40} {:>12} {:>10}",
+                    map.map_name, runs_ticks, "no data"
+                ),
+            }
+            .unwrap();
+        }
+        for failure in &report.failures {
+            writeln!(
+                table,
+                "    {:<40} failed: {}",
+                failure.map_name, failure.reason
+            )
+            .unwrap();
+        }
+    }
+    table.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_report() {
+        let benchmark = BenchmarkData {
+            map_name: "TEST".to_owned(),
+            map_hash: "0".repeat(64),
+            runs: 1,
+            ticks: 2,
+            verbose_data: vec![
+                "1,4000000,3000000,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1"
+                    .to_owned(),
+                "2,2000000,1000000,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1"
+                    .to_owned(),
+            ],
+            ..Default::default()
+        };
+        let report = MapReport::from_benchmark_data(&benchmark, true);
+        let whole_update = report.whole_update.unwrap();
+        assert_eq!(whole_update.count, 2);
+        assert!((whole_update.mean - 3.0).abs() < 1e-9);
+        assert!((report.game_update.unwrap().mean - 2.0).abs() < 1e-9);
+        let rows = report.tick_rows.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[1].run, rows[1].tick), (1, 2));
+        assert!((rows[1].timings["wholeUpdate"] - 2.0).abs() < 1e-9);
+
+        let report = MapReport::from_benchmark_data(&benchmark, false);
+        let json = serde_json::to_value(&report).unwrap();
+        assert!(json.get("tick_rows").is_none());
+        assert_eq!(json["whole_update"]["count"], 2);
+
+        let benchmark = BenchmarkData {
+            warmup_ticks: 1,
+            ..benchmark
+        };
+        let report = MapReport::from_benchmark_data(&benchmark, true);
+        let whole_update = report.whole_update.unwrap();
+        assert_eq!(whole_update.count, 1);
+        assert!((whole_update.mean - 2.0).abs() < 1e-9);
+        assert_eq!(report.tick_rows.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,524 @@
+//! Summary statistics and significance testing for benchmark timings.
+
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Two sided 95% critical values of Student's t distribution, indexed by
+/// degrees of freedom - 1.
+const T_CRITICAL_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+];
+
+/// Critical values beyond the table above, as (degrees of freedom, value).
+const T_CRITICAL_95_TAIL: [(f64, f64); 4] =
+    [(30.0, 2.042), (40.0, 2.021), (60.0, 2.000), (120.0, 1.980)];
+
+const Z_CRITICAL_95: f64 = 1.960;
+
+/// Descriptive statistics of a series of samples.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// The sample standard deviation
+    pub stddev: f64,
+    pub p95: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// The result of comparing the means of two samples with Welch's t-test.
+/// The difference and confidence interval are of `b - a`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WelchTest {
+    pub t: f64,
+    pub degrees_of_freedom: f64,
+    pub mean_difference: f64,
+    pub ci_low: f64,
+    pub ci_high: f64,
+}
+
+impl WelchTest {
+    /// Whether the 95% confidence interval of the difference excludes zero.
+    pub fn is_significant(&self) -> bool {
+        self.ci_low > 0.0 || self.ci_high < 0.0
+    }
+}
+
+/// How runs and samples of a benchmark are rejected before its verbose data
+/// is summarized. Written as "none", "drop-first-run", "mad:N" or
+/// "trimmed:FRACTION".
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum OutlierPolicy {
+    /// Every run counts.
+    #[default]
+    None,
+    /// The first run is discarded, as it starts with cold caches.
+    DropFirstRun,
+    /// Runs whose mean wholeUpdate is further than this many median absolute
+    /// deviations from the median run are discarded.
+    Mad(f64),
+    /// This fraction of the lowest and of the highest samples is discarded,
+    /// so the mean becomes a trimmed mean.
+    Trimmed(f64),
+}
+
+impl OutlierPolicy {
+    pub fn is_none(&self) -> bool {
+        *self == OutlierPolicy::None
+    }
+}
+
+impl fmt::Display for OutlierPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutlierPolicy::None => write!(f, "none"),
+            OutlierPolicy::DropFirstRun => write!(f, "drop-first-run"),
+            OutlierPolicy::Mad(threshold) => write!(f, "mad:{}", threshold),
+            OutlierPolicy::Trimmed(fraction) => {
+                write!(f, "trimmed:{}", fraction)
+            }
+        }
+    }
+}
+
+impl FromStr for OutlierPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<OutlierPolicy, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let mut parts = s.splitn(2, ':');
+        let kind = parts.next().unwrap_or_default();
+        let value = parts.next().map(|v| v.parse::<f64>());
+        match (kind, value) {
+            ("none", None) => Ok(OutlierPolicy::None),
+            ("drop-first-run", None) => Ok(OutlierPolicy::DropFirstRun),
+            ("mad", Some(Ok(threshold))) if threshold > 0.0 => {
+                Ok(OutlierPolicy::Mad(threshold))
+            }
+            ("trimmed", Some(Ok(fraction)))
+                if (0.0..0.5).contains(&fraction) =>
+            {
+                Ok(OutlierPolicy::Trimmed(fraction))
+            }
+            _ => Err(format!(
+                "Unknown outlier policy {:?}, expected none, drop-first-run, \
+                mad:N with N > 0, or trimmed:FRACTION with FRACTION below 0.5",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for OutlierPolicy {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<OutlierPolicy> for String {
+    fn from(policy: OutlierPolicy) -> String {
+        policy.to_string()
+    }
+}
+
+/// A single verbose timing of a tick within a run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickSample {
+    pub run: u32,
+    pub tick: u32,
+    pub value: f64,
+}
+
+/// Discards the first `warmup_ticks` ticks of every run. Tick numbers are
+/// those of the save, so the warm-up is counted from the first tick each run
+/// has.
+pub fn discard_warmup(
+    samples: &[TickSample],
+    warmup_ticks: u32,
+) -> Vec<TickSample> {
+    let mut runs: BTreeMap<u32, Vec<TickSample>> = BTreeMap::new();
+    for sample in samples {
+        runs.entry(sample.run).or_default().push(*sample);
+    }
+    let mut kept = Vec::with_capacity(samples.len());
+    for (_run, mut run_samples) in runs {
+        run_samples.sort_by_key(|sample| sample.tick);
+        kept.extend(run_samples.into_iter().skip(warmup_ticks as usize));
+    }
+    kept
+}
+
+/// Gets the runs which the outlier policy keeps, judged by the wholeUpdate
+/// samples of a benchmark with its warm-up already discarded.
+pub fn retained_runs(
+    whole_update: &[TickSample],
+    policy: &OutlierPolicy,
+) -> Vec<u32> {
+    let mut runs: BTreeMap<u32, Vec<f64>> = BTreeMap::new();
+    for sample in whole_update {
+        runs.entry(sample.run).or_default().push(sample.value);
+    }
+    match policy {
+        OutlierPolicy::DropFirstRun if runs.len() > 1 => {
+            runs.keys().skip(1).copied().collect()
+        }
+        OutlierPolicy::Mad(threshold) if runs.len() > 2 => {
+            let means: Vec<(u32, f64)> = runs
+                .iter()
+                .map(|(run, values)| {
+                    (*run, values.iter().sum::<f64>() / values.len() as f64)
+                })
+                .collect();
+            let mut sorted: Vec<f64> = means.iter().map(|(_, m)| *m).collect();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let median = percentile(&sorted, 50.0);
+            let mut deviations: Vec<f64> =
+                sorted.iter().map(|m| (m - median).abs()).collect();
+            deviations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let mad = percentile(&deviations, 50.0);
+            if mad == 0.0 {
+                return runs.keys().copied().collect();
+            }
+            means
+                .into_iter()
+                .filter(|(_, mean)| (mean - median).abs() <= threshold * mad)
+                .map(|(run, _)| run)
+                .collect()
+        }
+        _ => runs.keys().copied().collect(),
+    }
+}
+
+/// Applies the warm-up and outlier policy of a benchmark to the samples of
+/// one of its verbose columns. Runs are rejected based on `whole_update`, so
+/// every column of a benchmark keeps the same runs.
+pub fn filter_samples(
+    samples: &[TickSample],
+    whole_update: &[TickSample],
+    warmup_ticks: u32,
+    policy: &OutlierPolicy,
+) -> Vec<f64> {
+    let runs =
+        retained_runs(&discard_warmup(whole_update, warmup_ticks), policy);
+    let mut values: Vec<f64> = discard_warmup(samples, warmup_ticks)
+        .into_iter()
+        .filter(|sample| runs.contains(&sample.run))
+        .map(|sample| sample.value)
+        .collect();
+    if let OutlierPolicy::Trimmed(fraction) = policy {
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let trim = (values.len() as f64 * fraction).floor() as usize;
+        values = values[trim..values.len() - trim].to_vec();
+    }
+    values
+}
+
+/// The mean of every run of `samples` kept by the warm-up and outlier
+/// policy, in run order. Runs are rejected based on `whole_update`, as in
+/// `filter_samples`.
+pub fn run_means(
+    samples: &[TickSample],
+    whole_update: &[TickSample],
+    warmup_ticks: u32,
+    policy: &OutlierPolicy,
+) -> Vec<f64> {
+    let kept =
+        retained_runs(&discard_warmup(whole_update, warmup_ticks), policy);
+    let mut runs: BTreeMap<u32, Vec<f64>> = BTreeMap::new();
+    for sample in discard_warmup(samples, warmup_ticks)
+        .iter()
+        .filter(|s| kept.contains(&s.run))
+    {
+        runs.entry(sample.run).or_default().push(sample.value);
+    }
+    runs.values()
+        .map(|values| values.iter().sum::<f64>() / values.len() as f64)
+        .collect()
+}
+
+/// The half width of the 95% confidence interval of the mean of the runs
+/// kept by the warm-up and outlier policy, relative to that mean. Each run
+/// counts as one sample, since the ticks within a run are not independent.
+/// Returns None if fewer than 2 runs are kept.
+pub fn relative_precision(
+    whole_update: &[TickSample],
+    warmup_ticks: u32,
+    policy: &OutlierPolicy,
+) -> Option<f64> {
+    let summary = summarize(&run_means(
+        whole_update,
+        whole_update,
+        warmup_ticks,
+        policy,
+    ))?;
+    if summary.count < 2 || summary.mean == 0.0 {
+        return None;
+    }
+    let half_width = t_critical_95((summary.count - 1) as f64) * summary.stddev
+        / (summary.count as f64).sqrt();
+    Some(half_width / summary.mean.abs())
+}
+
+/// Summarizes the provided samples. Returns None if there are no samples.
+pub fn summarize(samples: &[f64]) -> Option<Summary> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let count = sorted.len();
+    let mean = sorted.iter().sum::<f64>() / count as f64;
+    let stddev = if count > 1 {
+        let sum_sq = sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>();
+        (sum_sq / (count - 1) as f64).sqrt()
+    } else {
+        0.0
+    };
+    Some(Summary {
+        count,
+        mean,
+        median: percentile(&sorted, 50.0),
+        stddev,
+        p95: percentile(&sorted, 95.0),
+        min: sorted[0],
+        max: sorted[count - 1],
+    })
+}
+
+/// Gets the p-th percentile of already sorted samples, linearly interpolating
+/// between the closest ranks.
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    assert!(!sorted.is_empty());
+    let rank = (p / 100.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let fraction = rank - lower as f64;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * fraction
+}
+
+/// The two sided 95% critical value of Student's t distribution. Fractional
+/// degrees of freedom are rounded down within the table, which is slightly
+/// conservative.
+pub fn t_critical_95(degrees_of_freedom: f64) -> f64 {
+    if degrees_of_freedom < 1.0 {
+        return T_CRITICAL_95[0];
+    }
+    if degrees_of_freedom <= 30.0 {
+        return T_CRITICAL_95[degrees_of_freedom as usize - 1];
+    }
+    for pair in T_CRITICAL_95_TAIL.windows(2) {
+        let (df_low, t_low) = pair[0];
+        let (df_high, t_high) = pair[1];
+        if degrees_of_freedom <= df_high {
+            let fraction = (degrees_of_freedom - df_low) / (df_high - df_low);
+            return t_low + (t_high - t_low) * fraction;
+        }
+    }
+    Z_CRITICAL_95
+}
+
+/// Performs Welch's unequal variances t-test on two summaries. Returns None
+/// if either summary has fewer than 2 samples.
+pub fn welch_t_test(a: &Summary, b: &Summary) -> Option<WelchTest> {
+    if a.count < 2 || b.count < 2 {
+        return None;
+    }
+    let var_a = a.stddev.powi(2) / a.count as f64;
+    let var_b = b.stddev.powi(2) / b.count as f64;
+    let mean_difference = b.mean - a.mean;
+    let standard_error = (var_a + var_b).sqrt();
+    if standard_error == 0.0 {
+        return Some(WelchTest {
+            t: 0.0,
+            degrees_of_freedom: (a.count + b.count - 2) as f64,
+            mean_difference,
+            ci_low: mean_difference,
+            ci_high: mean_difference,
+        });
+    }
+    let degrees_of_freedom = (var_a + var_b).powi(2)
+        / (var_a.powi(2) / (a.count - 1) as f64
+            + var_b.powi(2) / (b.count - 1) as f64);
+    let margin = t_critical_95(degrees_of_freedom) * standard_error;
+    Some(WelchTest {
+        t: mean_difference / standard_error,
+        degrees_of_freedom,
+        mean_difference,
+        ci_low: mean_difference - margin,
+        ci_high: mean_difference + margin,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let summary = summarize(&[4.0, 1.0, 3.0, 2.0, 5.0]).unwrap();
+        assert_eq!(summary.count, 5);
+        assert!((summary.mean - 3.0).abs() < 1e-9);
+        assert!((summary.median - 3.0).abs() < 1e-9);
+        assert!((summary.stddev - 2.5f64.sqrt()).abs() < 1e-9);
+        assert!((summary.p95 - 4.8).abs() < 1e-9);
+        assert_eq!(summary.min, 1.0);
+        assert_eq!(summary.max, 5.0);
+        assert!(summarize(&[]).is_none());
+    }
+
+    #[test]
+    fn test_t_critical() {
+        assert_eq!(t_critical_95(1.0), 12.706);
+        assert_eq!(t_critical_95(10.7), 2.228);
+        assert!((t_critical_95(50.0) - 2.0105).abs() < 1e-9);
+        assert_eq!(t_critical_95(10_000.0), Z_CRITICAL_95);
+    }
+
+    #[test]
+    fn test_welch_t_test() {
+        let a = summarize(&[10.0, 10.1, 9.9, 10.0, 10.05, 9.95]).unwrap();
+        let b = summarize(&[11.0, 11.1, 10.9, 11.0, 11.05, 10.95]).unwrap();
+        let test = welch_t_test(&a, &b).unwrap();
+        assert!((test.mean_difference - 1.0).abs() < 1e-9);
+        assert!(test.is_significant());
+
+        let c = summarize(&[10.0, 12.0, 8.0, 11.0, 9.0]).unwrap();
+        let d = summarize(&[10.5, 11.5, 8.5, 10.0, 9.5]).unwrap();
+        assert!(!welch_t_test(&c, &d).unwrap().is_significant());
+    }
+
+    fn samples(runs: &[&[f64]]) -> Vec<TickSample> {
+        let mut samples = Vec::new();
+        for (run, values) in runs.iter().enumerate() {
+            for (i, value) in values.iter().enumerate() {
+                samples.push(TickSample {
+                    run: run as u32 + 1,
+                    tick: 1000 + i as u32,
+                    value: *value,
+                });
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn test_outlier_policies() {
+        let whole_update = samples(&[
+            &[50.0, 9.0, 9.0],
+            &[50.0, 10.0, 10.0],
+            &[50.0, 11.0, 11.0],
+            &[50.0, 30.0, 30.0],
+        ]);
+        let all = filter_samples(
+            &whole_update,
+            &whole_update,
+            1,
+            &OutlierPolicy::None,
+        );
+        assert_eq!(all, vec![9.0, 9.0, 10.0, 10.0, 11.0, 11.0, 30.0, 30.0]);
+
+        let first = filter_samples(
+            &whole_update,
+            &whole_update,
+            1,
+            &OutlierPolicy::DropFirstRun,
+        );
+        assert_eq!(first, vec![10.0, 10.0, 11.0, 11.0, 30.0, 30.0]);
+
+        let mad = filter_samples(
+            &whole_update,
+            &whole_update,
+            1,
+            &OutlierPolicy::Mad(3.0),
+        );
+        assert_eq!(mad, vec![9.0, 9.0, 10.0, 10.0, 11.0, 11.0]);
+
+        let trimmed = filter_samples(
+            &whole_update,
+            &whole_update,
+            1,
+            &OutlierPolicy::Trimmed(0.25),
+        );
+        assert_eq!(trimmed, vec![10.0, 10.0, 11.0, 11.0]);
+    }
+
+    #[test]
+    fn test_relative_precision() {
+        let whole_update = samples(&[
+            &[50.0, 9.0, 11.0],
+            &[50.0, 10.0, 10.0],
+            &[50.0, 11.0, 9.0],
+        ]);
+        // Every run has a mean of 10 once the warm-up is discarded
+        assert_eq!(
+            relative_precision(&whole_update, 1, &OutlierPolicy::None),
+            Some(0.0)
+        );
+        let whole_update = samples(&[&[9.0], &[11.0], &[10.0]]);
+        let precision =
+            relative_precision(&whole_update, 0, &OutlierPolicy::None).unwrap();
+        assert!((precision - 4.303 / 3f64.sqrt() / 10.0).abs() < 1e-9);
+        assert_eq!(
+            relative_precision(&whole_update, 0, &OutlierPolicy::DropFirstRun)
+                .map(|p| p > precision),
+            Some(true)
+        );
+        assert_eq!(
+            relative_precision(
+                &samples(&[&[9.0, 10.0]]),
+                0,
+                &OutlierPolicy::None
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_run_means() {
+        let whole_update = samples(&[
+            &[50.0, 9.0, 11.0],
+            &[50.0, 12.0, 12.0],
+            &[50.0, 40.0, 40.0],
+            &[50.0, 11.0, 9.0],
+        ]);
+        assert_eq!(
+            run_means(&whole_update, &whole_update, 1, &OutlierPolicy::None),
+            vec![10.0, 12.0, 40.0, 10.0]
+        );
+        assert_eq!(
+            run_means(
+                &whole_update,
+                &whole_update,
+                1,
+                &OutlierPolicy::Mad(3.0)
+            ),
+            vec![10.0, 12.0, 10.0]
+        );
+    }
+
+    #[test]
+    fn test_outlier_policy_parse() {
+        for policy in &[
+            OutlierPolicy::None,
+            OutlierPolicy::DropFirstRun,
+            OutlierPolicy::Mad(3.5),
+            OutlierPolicy::Trimmed(0.1),
+        ] {
+            assert_eq!(
+                policy.to_string().parse::<OutlierPolicy>(),
+                Ok(*policy)
+            );
+        }
+        assert!("mad:0".parse::<OutlierPolicy>().is_err());
+        assert!("trimmed:0.5".parse::<OutlierPolicy>().is_err());
+        assert!("mad".parse::<OutlierPolicy>().is_err());
+    }
+}
@@ -1,25 +1,30 @@
 extern crate serde;
 extern crate serde_json;
 
+use crate::error::FbhError;
 use crate::performance_results::collection_data::Mod;
+use crate::performance_results::statistics::OutlierPolicy;
 use crate::util::fbh_cache_path;
+use crate::util::mirror::{fetch_from_mirror, is_offline, missing_from_mirror};
 use crate::util::prompt_until_allowed_val;
 use crate::util::{
-    fbh_procedure_json_local_file, fbh_procedure_json_master_file, Map,
+    fbh_procedure_json_local_file, fbh_procedure_json_master_file,
+    is_whitelisted_download_link, Map,
 };
 use core::str::FromStr;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::fmt;
 use std::fmt::Debug;
 use std::fs::read;
 use std::fs::OpenOptions;
+use std::io;
 use std::io::Read;
 use std::io::Write;
 use std::ops::Not;
 use std::path::PathBuf;
-use std::process::exit;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct TopLevel {
@@ -53,6 +58,37 @@ pub struct BenchmarkSet {
     pub maps: BTreeSet<Map>,
     pub ticks: u32,
     pub runs: u32,
+    /// The ticks at the start of every run which are discarded when
+    /// summarizing results. These are part of `ticks`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub warmup_ticks: u32,
+    #[serde(default, skip_serializing_if = "OutlierPolicy::is_none")]
+    pub outlier_policy: OutlierPolicy,
+    /// Overrides the timeout multiplier of config.ini for the maps of this
+    /// set, for maps whose run times vary more than most.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_multiplier: Option<f64>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+impl BenchmarkSet {
+    /// The ticks of every run of `map`, which the map may override.
+    pub fn map_ticks(&self, map: &Map) -> u32 {
+        map.ticks.unwrap_or(self.ticks)
+    }
+
+    /// The runs of `map`, which the map may override.
+    pub fn map_runs(&self, map: &Map) -> u32 {
+        map.runs.unwrap_or(self.runs)
+    }
+
+    /// The warmup ticks of every run of `map`, which the map may override.
+    pub fn map_warmup_ticks(&self, map: &Map) -> u32 {
+        map.warmup_ticks.unwrap_or(self.warmup_ticks)
+    }
 }
 
 impl Default for BenchmarkSet {
@@ -63,6 +99,9 @@ impl Default for BenchmarkSet {
             maps: BTreeSet::new(),
             ticks: 0,
             runs: 0,
+            warmup_ticks: 0,
+            outlier_policy: OutlierPolicy::None,
+            timeout_multiplier: None,
         }
     }
 }
@@ -74,25 +113,20 @@ pub enum ProcedureKind {
     Both,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ProcedureFileKind {
     Local,
     Master,
     Custom(PathBuf),
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
 pub enum ProcedureOverwrite {
     True,
+    #[default]
     False,
 }
 
-impl Default for ProcedureOverwrite {
-    fn default() -> Self {
-        ProcedureOverwrite::False
-    }
-}
-
 impl From<bool> for ProcedureOverwrite {
     fn from(b: bool) -> ProcedureOverwrite {
         if b {
@@ -139,12 +173,16 @@ impl Not for ProcedureInteractive {
     }
 }
 
-pub fn update_master_json() {
+pub fn update_master_json() -> Result<(), FbhError> {
     if let Some(orig_top_level) =
         load_top_level_from_file(&ProcedureFileKind::Master)
     {
         let new = fbh_cache_path().join(".new.json");
-        perform_master_json_dl(&new);
+        if let Err(e) = perform_master_json_dl(&new) {
+            eprintln!("Failed to update master.json: {}", e);
+            eprintln!("Continuing with the existing master.json.");
+            return Ok(());
+        }
         if let Some(new_top_level) =
             load_top_level_from_file(&ProcedureFileKind::Custom(new))
         {
@@ -158,7 +196,7 @@ pub fn update_master_json() {
                     ProcedureOverwrite::True,
                     ProcedureFileKind::Master,
                     ProcedureInteractive::True,
-                );
+                )?;
             }
             for (k, v) in new_top_level.meta_sets {
                 if orig_top_level.meta_sets.contains_key(&k) {
@@ -169,29 +207,55 @@ pub fn update_master_json() {
                     v,
                     true.into(),
                     ProcedureFileKind::Master,
-                );
+                )?;
             }
         }
     } else {
-        perform_master_json_dl(&fbh_procedure_json_master_file());
+        // Only offline mode has to stop here, other commands may not need
+        // master.json
+        match perform_master_json_dl(&fbh_procedure_json_master_file()) {
+            Err(e @ FbhError::MissingFromMirror { .. }) => return Err(e),
+            Err(e) => eprintln!("{}", e),
+            Ok(()) => (),
+        }
     }
+    Ok(())
 }
 
-fn perform_master_json_dl(file_to_write: &PathBuf) {
+/// Downloads master.json from the mirror, or from GitHub unless offline.
+fn perform_master_json_dl(file_to_write: &PathBuf) -> Result<(), FbhError> {
+    match fetch_from_mirror("master.json") {
+        Ok(Some(buf)) => {
+            std::fs::write(file_to_write, buf)?;
+            return Ok(());
+        }
+        Ok(None) => (),
+        Err(e) => {
+            eprintln!("Failed to read master.json from the mirror: {}", e)
+        }
+    }
+    if is_offline() {
+        return Err(missing_from_mirror("master.json"));
+    }
     let resp = ureq::get(
         "https://raw.githubusercontent.com/mulark/factorio-benchmark-helper/master/master.json",
     )
     .call();
-    if resp.status() == 200 {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(file_to_write)
-            .unwrap();
-        let mut buf = Vec::new();
-        resp.into_reader().read_to_end(&mut buf).unwrap();
-        file.write_all(&buf).unwrap();
+    if resp.status() != 200 {
+        return Err(FbhError::Io(io::Error::other(format!(
+            "Failed to download master.json: {}",
+            resp.status_line()
+        ))));
     }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(file_to_write)?;
+    let mut buf = Vec::new();
+    resp.into_reader().read_to_end(&mut buf)?;
+    file.write_all(&buf)?;
+    Ok(())
 }
 
 fn load_top_level_from_file(file_type: &ProcedureFileKind) -> Option<TopLevel> {
@@ -289,7 +353,7 @@ pub fn write_benchmark_set_to_file(
     force: ProcedureOverwrite,
     file_kind: ProcedureFileKind,
     interactive: ProcedureInteractive,
-) {
+) -> Result<(), FbhError> {
     let mut top_level;
     match load_top_level_from_file(&file_kind) {
         Some(m) => {
@@ -313,22 +377,63 @@ pub fn write_benchmark_set_to_file(
                 "y" => {
                     top_level.benchmark_sets.insert(name.to_string(), set);
                     let j = serde_json::to_string_pretty(&top_level).unwrap();
-                    std::fs::write(procedure_file_path, j).unwrap();
+                    std::fs::write(procedure_file_path, j)?;
                 }
                 "n" => (),
                 _ => unreachable!("interactive answer not y or n, but how?"),
             }
         } else {
-            eprintln!(
-                "Cannot write procedure to file, {:?} already exists! Maybe use --overwrite?",
-                name
-            );
-            exit(1);
+            return Err(benchmark_set_exists_error(name));
         }
     } else {
         top_level.benchmark_sets.insert(name.to_string(), set);
         let j = serde_json::to_string_pretty(&top_level).unwrap();
-        std::fs::write(procedure_file_path, j).unwrap();
+        std::fs::write(procedure_file_path, j)?;
+    }
+    Ok(())
+}
+
+fn benchmark_set_exists_error(name: &str) -> FbhError {
+    FbhError::InvalidInput(format!(
+        "Cannot write procedure to file, {:?} already exists! Maybe use --overwrite?",
+        name
+    ))
+}
+
+fn meta_set_exists_error(name: &str) -> FbhError {
+    FbhError::InvalidInput(format!("Cannot write procedure to master file, meta set {:?} already exists! Maybe use --overwrite?", name))
+}
+
+/// Checks that the benchmark sets and meta sets named can be written to
+/// `file_kind` the same as `write_benchmark_set_to_file` and
+/// `write_meta_to_file` would, without writing anything. Existing benchmark
+/// sets pass when `interactive`, as the user is asked before they are
+/// overwritten.
+pub(crate) fn check_procedures_writable<'a>(
+    benchmark_sets: impl IntoIterator<Item = &'a String>,
+    meta_sets: impl IntoIterator<Item = &'a String>,
+    force: ProcedureOverwrite,
+    file_kind: &ProcedureFileKind,
+    interactive: ProcedureInteractive,
+) -> Result<(), FbhError> {
+    if force == ProcedureOverwrite::True {
+        return Ok(());
+    }
+    let top_level = load_top_level_from_file(file_kind).unwrap_or_default();
+    if interactive == ProcedureInteractive::False {
+        if let Some(name) = benchmark_sets
+            .into_iter()
+            .find(|name| top_level.benchmark_sets.contains_key(*name))
+        {
+            return Err(benchmark_set_exists_error(name));
+        }
+    }
+    match meta_sets
+        .into_iter()
+        .find(|name| top_level.meta_sets.contains_key(*name))
+    {
+        Some(name) => Err(meta_set_exists_error(name)),
+        None => Ok(()),
     }
 }
 
@@ -347,12 +452,55 @@ pub fn read_meta_from_file(
     None
 }
 
+/// Reads the benchmark set `name` to run it. If the set is in both
+/// master.json and local.json, the one in master.json is used.
+pub fn find_benchmark_set(name: &str) -> Result<BenchmarkSet, FbhError> {
+    let local = read_benchmark_set_from_file(name, ProcedureFileKind::Local);
+    let master = read_benchmark_set_from_file(name, ProcedureFileKind::Master);
+    if master.is_some() && local.is_some() && master != local {
+        println!("WARN: benchmark with name {:?} is present in both local and master, and they differ.", name);
+        println!("WARN: benchmark is being ran from master.json");
+    }
+    master.or(local).ok_or_else(|| {
+        FbhError::InvalidInput(format!(
+            "Could not find benchmark with the name: {:?}",
+            name
+        ))
+    })
+}
+
+/// Reads every benchmark set within the meta set `name` to run them. If the
+/// meta set is in both master.json and local.json, the one in master.json is
+/// used.
+pub fn find_meta_set_benchmarks(
+    name: &str,
+) -> Result<HashMap<String, BenchmarkSet>, FbhError> {
+    let local = read_meta_from_file(name, ProcedureFileKind::Local);
+    let master = read_meta_from_file(name, ProcedureFileKind::Master);
+    if local.is_none() && master.is_none() {
+        return Err(FbhError::InvalidInput(format!(
+            "Could not find meta benchmark set with the name: {:?}",
+            name
+        )));
+    }
+    if local.is_some() && master.is_some() && local != master {
+        println!("WARN: meta set with name {:?} is present in both local and master, and they differ.", name);
+        println!("WARN: meta set is being ran from master.json");
+    }
+    let meta_src_file = if master.is_some() {
+        ProcedureFileKind::Master
+    } else {
+        ProcedureFileKind::Local
+    };
+    Ok(get_sets_from_meta(name.to_string(), meta_src_file))
+}
+
 pub fn write_meta_to_file(
     name: &str,
     members: BTreeSet<String>,
     force: ProcedureOverwrite,
     file_kind: ProcedureFileKind,
-) {
+) -> Result<(), FbhError> {
     let mut top_level;
     match load_top_level_from_file(&file_kind) {
         Some(m) => top_level = m,
@@ -365,13 +513,12 @@ pub fn write_meta_to_file(
     };
 
     if top_level.meta_sets.contains_key(name) && force == false.into() {
-        eprintln!("Cannot write procedure to master file, meta set {:?} already exists! Maybe use --overwrite?", name);
-        exit(1);
-    } else {
-        top_level.meta_sets.insert(name.to_string(), members);
-        let j = serde_json::to_string_pretty(&top_level).unwrap();
-        std::fs::write(procedure_file_path, j).unwrap();
+        return Err(meta_set_exists_error(name));
     }
+    top_level.meta_sets.insert(name.to_string(), members);
+    let j = serde_json::to_string_pretty(&top_level).unwrap();
+    std::fs::write(procedure_file_path, j)?;
+    Ok(())
 }
 
 // Returns a hashmap of all benchmark sets contained within this meta set, as well as the meta sets
@@ -404,7 +551,7 @@ fn walk_meta_recursive_for_benchmarks(
             for k in &top_level.meta_sets[&key] {
                 walk_meta_recursive_for_benchmarks(
                     k.to_string(),
-                    &top_level,
+                    top_level,
                     seen_keys,
                     current_benchmark_sets,
                 );
@@ -444,7 +591,7 @@ fn walk_meta_recursive_for_metas(
         for k in &top_level.meta_sets[&key] {
             walk_meta_recursive_for_metas(
                 k.to_string(),
-                &top_level,
+                top_level,
                 seen_keys,
                 current_meta_sets,
             );
@@ -452,3 +599,459 @@ fn walk_meta_recursive_for_metas(
         current_meta_sets.push(key);
     }
 }
+
+/// A problem found when validating a procedure file.
+#[derive(Debug, PartialEq)]
+pub struct ValidationProblem {
+    /// The benchmark or meta set with the problem, empty if the problem is
+    /// with the file itself.
+    pub set_name: String,
+    pub description: String,
+}
+
+impl ValidationProblem {
+    fn new(set_name: &str, description: String) -> ValidationProblem {
+        ValidationProblem {
+            set_name: set_name.to_owned(),
+            description,
+        }
+    }
+}
+
+impl fmt::Display for ValidationProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.set_name.is_empty() {
+            write!(f, "{}", self.description)
+        } else {
+            write!(f, "{:?}: {}", self.set_name, self.description)
+        }
+    }
+}
+
+impl TopLevel {
+    /// Checks the benchmark and meta sets for problems that would make them
+    /// fail to run, or make their results misleading.
+    pub fn validate(&self) -> Vec<ValidationProblem> {
+        let mut problems = Vec::new();
+        // The set and name every map hash was first seen with, and the set
+        // and hash every map name was first seen with. Sets may share maps,
+        // but a map must keep its name and hash across them.
+        let mut names_by_hash: HashMap<&str, (&str, &str)> = HashMap::new();
+        let mut hashes_by_name: HashMap<&str, (&str, &str)> = HashMap::new();
+        for (name, set) in &self.benchmark_sets {
+            // The name every map hash was seen with in this set
+            let mut set_hashes: HashMap<&str, &str> = HashMap::new();
+            if set.ticks == 0 {
+                problems
+                    .push(ValidationProblem::new(name, "ticks is 0".into()));
+            } else if set.warmup_ticks >= set.ticks {
+                problems.push(ValidationProblem::new(
+                    name,
+                    format!(
+                        "warmup_ticks {} leaves none of the {} ticks",
+                        set.warmup_ticks, set.ticks
+                    ),
+                ));
+            }
+            if set.runs == 0 {
+                problems.push(ValidationProblem::new(name, "runs is 0".into()));
+            }
+            if let Some(multiplier) = set.timeout_multiplier {
+                if multiplier != 0.0 && multiplier < 1.0 {
+                    problems.push(ValidationProblem::new(
+                        name,
+                        format!(
+                            "timeout_multiplier {} is neither 0 nor at least 1",
+                            multiplier
+                        ),
+                    ));
+                }
+            }
+            if set.maps.is_empty() {
+                problems
+                    .push(ValidationProblem::new(name, "has no maps".into()));
+            }
+            for map in &set.maps {
+                if map.ticks == Some(0) || map.runs == Some(0) {
+                    problems.push(ValidationProblem::new(
+                        name,
+                        format!(
+                            "map {:?} overrides ticks or runs with 0",
+                            map.name
+                        ),
+                    ));
+                } else if (map.ticks.is_some() || map.warmup_ticks.is_some())
+                    && set.map_warmup_ticks(map) >= set.map_ticks(map)
+                {
+                    problems.push(ValidationProblem::new(
+                        name,
+                        format!(
+                            "map {:?} warmup_ticks {} leaves none of its {} \
+                            ticks",
+                            map.name,
+                            set.map_warmup_ticks(map),
+                            set.map_ticks(map)
+                        ),
+                    ));
+                }
+                if map.download_link.is_empty() {
+                    problems.push(ValidationProblem::new(
+                        name,
+                        format!("map {:?} has no download_link", map.name),
+                    ));
+                } else if !is_whitelisted_download_link(&map.download_link) {
+                    problems.push(ValidationProblem::new(
+                        name,
+                        format!(
+                            "map {:?} is downloaded from a domain that is not \
+                            whitelisted: {}",
+                            map.name, map.download_link
+                        ),
+                    ));
+                }
+                if !is_sha256(&map.sha256) {
+                    problems.push(ValidationProblem::new(
+                        name,
+                        format!(
+                            "map {:?} has a malformed sha256 {:?}",
+                            map.name, map.sha256
+                        ),
+                    ));
+                    continue;
+                }
+                if let Some(other_name) =
+                    set_hashes.insert(map.sha256.as_str(), map.name.as_str())
+                {
+                    let problem = if other_name == map.name {
+                        format!("map {:?} is listed more than once", map.name)
+                    } else {
+                        format!(
+                            "sha256 {} is duplicated by maps {:?} and {:?}",
+                            map.sha256, other_name, map.name
+                        )
+                    };
+                    problems.push(ValidationProblem::new(name, problem));
+                    continue;
+                }
+                let (other_set, other_map) = *names_by_hash
+                    .entry(&map.sha256)
+                    .or_insert((name.as_str(), map.name.as_str()));
+                if other_map != map.name {
+                    problems.push(ValidationProblem::new(
+                        name,
+                        format!(
+                            "map {:?} has the same sha256 as map {:?} of set \
+                            {:?}",
+                            map.name, other_map, other_set
+                        ),
+                    ));
+                }
+                let (other_set, other_hash) = *hashes_by_name
+                    .entry(&map.name)
+                    .or_insert((name.as_str(), map.sha256.as_str()));
+                if other_hash != map.sha256 {
+                    problems.push(ValidationProblem::new(
+                        name,
+                        format!(
+                            "map {:?} has a different sha256 than in set {:?}",
+                            map.name, other_set
+                        ),
+                    ));
+                }
+            }
+        }
+
+        for (name, members) in &self.meta_sets {
+            for member in members {
+                if !self.benchmark_sets.contains_key(member)
+                    && !self.meta_sets.contains_key(member)
+                {
+                    problems.push(ValidationProblem::new(
+                        name,
+                        format!("member {:?} does not exist", member),
+                    ));
+                }
+            }
+        }
+        for cycle in self.find_meta_cycles() {
+            problems.push(ValidationProblem::new(
+                &cycle[0],
+                format!("meta sets contain each other: {}", cycle.join(" -> ")),
+            ));
+        }
+        problems
+    }
+
+    /// Finds every cycle of meta sets containing each other, each listed
+    /// once starting and ending with the same meta set.
+    fn find_meta_cycles(&self) -> Vec<Vec<String>> {
+        let mut cycles = Vec::new();
+        let mut finished = BTreeSet::new();
+        for name in self.meta_sets.keys() {
+            let mut path = Vec::new();
+            self.walk_meta_for_cycles(
+                name,
+                &mut path,
+                &mut finished,
+                &mut cycles,
+            );
+        }
+        cycles
+    }
+
+    fn walk_meta_for_cycles<'a>(
+        &'a self,
+        key: &'a str,
+        path: &mut Vec<&'a str>,
+        finished: &mut BTreeSet<&'a str>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        if let Some(start) = path.iter().position(|k| *k == key) {
+            let mut cycle: Vec<String> =
+                path[start..].iter().map(|k| k.to_string()).collect();
+            cycle.push(key.to_owned());
+            cycles.push(cycle);
+            return;
+        }
+        if finished.contains(key) {
+            return;
+        }
+        if let Some(members) = self.meta_sets.get(key) {
+            path.push(key);
+            for member in members {
+                self.walk_meta_for_cycles(member, path, finished, cycles);
+            }
+            path.pop();
+        }
+        finished.insert(key);
+    }
+}
+
+fn is_sha256(hash: &str) -> bool {
+    hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Validates each procedure file, printing every problem found. Fails if
+/// any file could not be read or has problems.
+pub fn validate_procedure_files(
+    file_kinds: &[ProcedureFileKind],
+) -> Result<(), FbhError> {
+    let mut problem_count = 0;
+    for file_kind in file_kinds {
+        let path = procedure_file_path(file_kind);
+        let problems = match read(&path) {
+            Ok(bytes) => match serde_json::from_slice::<TopLevel>(&bytes) {
+                Ok(top_level) => top_level.validate(),
+                Err(e) => vec![ValidationProblem::new(
+                    "",
+                    format!("could not be parsed: {}", e),
+                )],
+            },
+            Err(e) => {
+                return Err(FbhError::InvalidInput(format!(
+                    "Could not read procedure file {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        if problems.is_empty() {
+            println!("{}: OK", path.display());
+        } else {
+            println!("{}:", path.display());
+            for problem in &problems {
+                println!("    {}", problem);
+            }
+        }
+        problem_count += problems.len();
+    }
+    if problem_count == 0 {
+        Ok(())
+    } else {
+        Err(FbhError::InvalidInput(format!(
+            "Found {} problem(s) in the procedure files",
+            problem_count
+        )))
+    }
+}
+
+fn procedure_file_path(file_kind: &ProcedureFileKind) -> PathBuf {
+    match file_kind {
+        ProcedureFileKind::Local => fbh_procedure_json_local_file(),
+        ProcedureFileKind::Master => fbh_procedure_json_master_file(),
+        ProcedureFileKind::Custom(p) => p.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(name: &str, sha256: &str, download_link: &str) -> Map {
+        Map {
+            name: name.to_owned(),
+            path: PathBuf::from(name),
+            min_compatible_version: Default::default(),
+            sha256: sha256.to_owned(),
+            download_link: download_link.to_owned(),
+            ticks: None,
+            runs: None,
+            warmup_ticks: None,
+        }
+    }
+
+    #[test]
+    fn test_map_overrides() {
+        let hash = "b".repeat(64);
+        let link = "https://forums.factorio.com/download/file.php?id=2";
+        // Maps from before overrides existed have none
+        let plain: Map = serde_json::from_str(&format!(
+            r#"{{"name":"a.zip","sha256":"{}","download_link":"{}"}}"#,
+            hash, link
+        ))
+        .unwrap();
+        assert_eq!(
+            (plain.ticks, plain.runs, plain.warmup_ticks),
+            (None, None, None)
+        );
+        assert!(!serde_json::to_string(&plain).unwrap().contains("ticks"));
+
+        let mut small = map("b.zip", &"c".repeat(64), link);
+        small.ticks = Some(50);
+        small.runs = Some(20);
+        let mut set = BenchmarkSet {
+            maps: vec![plain.clone(), small.clone()].into_iter().collect(),
+            ticks: 1000,
+            runs: 3,
+            warmup_ticks: 60,
+            ..BenchmarkSet::default()
+        };
+        assert_eq!((set.map_ticks(&plain), set.map_runs(&plain)), (1000, 3));
+        assert_eq!((set.map_ticks(&small), set.map_runs(&small)), (50, 20));
+
+        let mut top_level = TopLevel::default();
+        top_level
+            .benchmark_sets
+            .insert("mixed".to_owned(), set.clone());
+        let problems: Vec<String> =
+            top_level.validate().iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            problems,
+            vec![
+                "\"mixed\": map \"b.zip\" warmup_ticks 60 leaves none of its 50 \
+                ticks"
+            ]
+        );
+
+        small.warmup_ticks = Some(10);
+        set.maps = vec![plain, small].into_iter().collect();
+        top_level.benchmark_sets.insert("mixed".to_owned(), set);
+        assert_eq!(top_level.validate(), Vec::new());
+    }
+
+    #[test]
+    fn test_validate_top_level() {
+        let hash = "a".repeat(64);
+        let mut top_level = TopLevel::default();
+        top_level.benchmark_sets.insert(
+            "good".to_owned(),
+            BenchmarkSet {
+                maps: vec![map(
+                    "a.zip",
+                    &hash,
+                    "https://forums.factorio.com/download/file.php?id=1",
+                )]
+                .into_iter()
+                .collect(),
+                ticks: 100,
+                runs: 1,
+                ..BenchmarkSet::default()
+            },
+        );
+        // Sets may share a map under the same name and hash.
+        top_level.benchmark_sets.insert(
+            "shared".to_owned(),
+            BenchmarkSet {
+                maps: vec![map(
+                    "a.zip",
+                    &hash,
+                    "https://forums.factorio.com/download/file.php?id=1",
+                )]
+                .into_iter()
+                .collect(),
+                ticks: 100,
+                runs: 1,
+                ..BenchmarkSet::default()
+            },
+        );
+        top_level.meta_sets.insert(
+            "meta".to_owned(),
+            vec!["good".to_owned()].into_iter().collect(),
+        );
+        assert_eq!(top_level.validate(), Vec::new());
+
+        top_level.benchmark_sets.insert(
+            "bad".to_owned(),
+            BenchmarkSet {
+                maps: vec![
+                    map("b.zip", &hash, ""),
+                    map("c.zip", "abc", "https://example.com/c.zip"),
+                    map("d.zip", &"d".repeat(64), ""),
+                    map("a.zip", &"f".repeat(64), ""),
+                ]
+                .into_iter()
+                .collect(),
+                timeout_multiplier: Some(0.5),
+                ..BenchmarkSet::default()
+            },
+        );
+        // Collecting would drop maps equal by hash, deserializing does not.
+        top_level
+            .benchmark_sets
+            .get_mut("bad")
+            .unwrap()
+            .maps
+            .insert(map("e.zip", &"d".repeat(64), ""));
+        top_level.meta_sets.insert(
+            "loop".to_owned(),
+            vec!["missing".to_owned(), "meta".to_owned()]
+                .into_iter()
+                .collect(),
+        );
+        top_level
+            .meta_sets
+            .get_mut("meta")
+            .unwrap()
+            .insert("loop".to_owned());
+        let problems: Vec<String> =
+            top_level.validate().iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            problems,
+            vec![
+                "\"bad\": ticks is 0",
+                "\"bad\": runs is 0",
+                "\"bad\": timeout_multiplier 0.5 is neither 0 nor at least 1",
+                "\"bad\": map \"a.zip\" has no download_link",
+                "\"bad\": map \"b.zip\" has no download_link",
+                "\"bad\": map \"c.zip\" is downloaded from a domain that is \
+                    not whitelisted: https://example.com/c.zip",
+                "\"bad\": map \"c.zip\" has a malformed sha256 \"abc\"",
+                "\"bad\": map \"d.zip\" has no download_link",
+                "\"bad\": map \"e.zip\" has no download_link",
+                "\"bad\": sha256 dddddddddddddddddddddddddddddddddddddddddddddddd\
+                    dddddddddddddddd is duplicated by maps \"d.zip\" and \
+                    \"e.zip\"",
+                "\"good\": map \"a.zip\" has the same sha256 as map \"b.zip\" \
+                    of set \"bad\"",
+                "\"good\": map \"a.zip\" has a different sha256 than in set \
+                    \"bad\"",
+                "\"shared\": map \"a.zip\" has the same sha256 as map \
+                    \"b.zip\" of set \"bad\"",
+                "\"shared\": map \"a.zip\" has a different sha256 than in \
+                    set \"bad\"",
+                "\"loop\": member \"missing\" does not exist",
+                "\"loop\": meta sets contain each other: loop -> meta -> loop",
+            ]
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! Bisecting the headless Factorio releases between a good and a bad version
+//! to find the first release in which a map slowed down.
+
+use crate::benchmark_runner::benchmark_timeout;
+use crate::benchmark_runner::parse_stdout_for_errors;
+use crate::benchmark_runner::parse_stdout_for_factorio_version;
+use crate::benchmark_runner::parse_stdout_for_verbose_data;
+use crate::benchmark_runner::run_factorio_benchmark;
+use crate::benchmark_runner::unmeasured_run_time;
+use crate::benchmark_runner::SimpleBenchmarkParams;
+use crate::error::FbhError;
+use crate::performance_results::collection_data::BenchmarkData;
+use crate::performance_results::statistics::summarize;
+use crate::regression_tester::headless_downloader::download_versions_between;
+use crate::regression_tester::headless_downloader::get_local_headless_versions;
+use crate::regression_tester::headless_downloader::get_unpacked_executables;
+use crate::regression_tester::headless_downloader::unpack_headless_version;
+use crate::regression_tester::resolve_regression_params;
+use crate::regression_tester::REGRESSION_TEST_SUBFOLDER;
+use crate::util::config_file::RegressionParams;
+use crate::util::config_file::CONFIG_FILE_SETTINGS;
+use crate::util::fbh_mod_use_dir;
+use crate::util::mirror::is_offline;
+use crate::util::ProcessScheduling;
+use megabase_index_incrementer::FactorioVersion;
+use std::path::PathBuf;
+use ureq::Agent;
+
+/// The versions benchmarked during a bisection, and the first one found to
+/// be slower than the good version by more than the threshold.
+#[derive(Debug, PartialEq)]
+struct Bisection {
+    /// The mean wholeUpdate in milliseconds of each version benchmarked, in
+    /// the order they were benchmarked.
+    measured: Vec<(FactorioVersion, f64)>,
+    /// None if the bad version was not slower than the threshold.
+    first_slow: Option<FactorioVersion>,
+    /// The last version before `first_slow`.
+    last_good: Option<FactorioVersion>,
+}
+
+/// Binary searches `versions`, ordered from the good to the bad version, for
+/// the first one whose mean wholeUpdate is more than `threshold` percent above
+/// that of the good version. Versions are assumed to stay slow once slow.
+fn bisect<F>(
+    versions: &[FactorioVersion],
+    threshold: f64,
+    mut measure: F,
+) -> Result<Bisection, FbhError>
+where
+    F: FnMut(FactorioVersion) -> Result<f64, FbhError>,
+{
+    let mut bisection = Bisection {
+        measured: Vec::new(),
+        first_slow: None,
+        last_good: None,
+    };
+    let good = versions[0];
+    let baseline = measure(good)?;
+    bisection.measured.push((good, baseline));
+    if slowdown(baseline, baseline).is_none() {
+        return Err(FbhError::Factorio(format!(
+            "Factorio {} reported a mean wholeUpdate of {} ms, which cannot \
+             be compared against",
+            good, baseline
+        )));
+    }
+    let is_slow = |mean: f64| {
+        slowdown(baseline, mean).is_some_and(|change| change > threshold)
+    };
+
+    let mut low = 0;
+    let mut high = versions.len() - 1;
+    let bad_mean = measure(versions[high])?;
+    bisection.measured.push((versions[high], bad_mean));
+    if !is_slow(bad_mean) {
+        return Ok(bisection);
+    }
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        let mean = measure(versions[middle])?;
+        bisection.measured.push((versions[middle], mean));
+        if is_slow(mean) {
+            high = middle;
+        } else {
+            low = middle;
+        }
+    }
+    bisection.first_slow = Some(versions[high]);
+    bisection.last_good = Some(versions[low]);
+    Ok(bisection)
+}
+
+/// The change in percent from `baseline` to `mean`, None if `baseline` is
+/// not a positive time.
+fn slowdown(baseline: f64, mean: f64) -> Option<f64> {
+    if baseline > 0.0 && baseline.is_finite() {
+        Some((mean - baseline) / baseline * 100.0)
+    } else {
+        None
+    }
+}
+
+/// The mean wholeUpdate in milliseconds of a benchmark, None if it has no
+/// verbose data.
+fn mean_whole_update(data: &BenchmarkData) -> Option<f64> {
+    let whole_update: Vec<f64> = data
+        .whole_update_samples()
+        .iter()
+        .map(|sample| sample.value)
+        .collect();
+    summarize(&whole_update).map(|summary| summary.mean / 1e6)
+}
+
+/// Finds a map given by path, or by name in the regression test folder.
+fn find_map(map: &str) -> Result<PathBuf, FbhError> {
+    let path = PathBuf::from(map);
+    if path.is_file() {
+        return Ok(path);
+    }
+    let in_regression_folder = REGRESSION_TEST_SUBFOLDER.join(map);
+    if in_regression_folder.is_file() {
+        return Ok(in_regression_folder);
+    }
+    Err(FbhError::InvalidInput(format!(
+        "Could not find map {:?}, nor {:?}",
+        map, in_regression_folder
+    )))
+}
+
+/// The downloaded or unpacked headless versions from `good` to `bad`, in
+/// order. Unless offline, the missing ones are downloaded first.
+fn versions_between(
+    good: FactorioVersion,
+    bad: FactorioVersion,
+) -> Result<Vec<FactorioVersion>, FbhError> {
+    if !is_offline() {
+        if let Err(e) = download_versions_between(&Agent::new(), good, bad) {
+            eprintln!("Error fetching headless versions: {}", e);
+        }
+    }
+    let mut versions: Vec<FactorioVersion> = get_local_headless_versions()
+        .unwrap_or_default()
+        .into_iter()
+        .chain(get_unpacked_executables().unwrap_or_default())
+        .map(|(version, _path)| version)
+        .filter(|version| *version >= good && *version <= bad)
+        .collect();
+    versions.sort();
+    versions.dedup();
+    for version in &[good, bad] {
+        if !versions.contains(version) {
+            return Err(FbhError::InvalidInput(format!(
+                "Headless Factorio {} is not downloaded, try --headless \
+                 fetch {}",
+                version, version
+            )));
+        }
+    }
+    Ok(versions)
+}
+
+/// Unpacks and benchmarks `version`, returning its mean wholeUpdate in
+/// milliseconds.
+fn measure_version(
+    version: FactorioVersion,
+    params: &SimpleBenchmarkParams,
+) -> Result<f64, FbhError> {
+    unpack_headless_version(version)?;
+    let executable = get_unpacked_executables()?
+        .into_iter()
+        .find(|(unpacked, _path)| *unpacked == version)
+        .map(|(_version, path)| path)
+        .ok_or_else(|| {
+            FbhError::Factorio(format!(
+                "Could not unpack headless Factorio {}",
+                version
+            ))
+        })?;
+    let stdout = run_factorio_benchmark(&executable, params)?;
+    parse_stdout_for_errors(&stdout)?;
+    let ran_version = parse_stdout_for_factorio_version(&stdout);
+    if ran_version != Some(version) {
+        return Err(FbhError::Factorio(format!(
+            "Expected to run Factorio {}, but ran {:?}",
+            version, ran_version
+        )));
+    }
+    let data = BenchmarkData {
+        verbose_data: parse_stdout_for_verbose_data(&stdout),
+        ..Default::default()
+    };
+    mean_whole_update(&data).ok_or_else(|| {
+        FbhError::Factorio(format!(
+            "Factorio {} reported no verbose timings",
+            version
+        ))
+    })
+}
+
+/// Benchmarks `map` in the headless Factorio versions between `good` and
+/// `bad` to find the first one more than `threshold` percent slower than
+/// `good`. Any ticks or runs in `params` override those in config.ini.
+pub fn run_regression_bisect(
+    map: &str,
+    good: FactorioVersion,
+    bad: FactorioVersion,
+    threshold: f64,
+    params: RegressionParams,
+) -> Result<(), FbhError> {
+    let map_path = find_map(map)?;
+    let versions = versions_between(good, bad)?;
+    let map_name = map_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (ticks, runs) =
+        resolve_regression_params(&map_name, params, &CONFIG_FILE_SETTINGS);
+    println!(
+        "Bisecting {} versions from {} to {} with {} for {} ticks and {} runs",
+        versions.len(),
+        good,
+        bad,
+        map_name,
+        ticks,
+        runs
+    );
+    let benchmark_params = SimpleBenchmarkParams {
+        map_path,
+        mod_directory: fbh_mod_use_dir(),
+        mods: vec![],
+        runs,
+        ticks,
+        scheduling: ProcessScheduling::default(),
+        timeout: benchmark_timeout(
+            unmeasured_run_time(ticks, runs),
+            CONFIG_FILE_SETTINGS.timeout_multiplier,
+        ),
+    };
+    let mut baseline = None;
+    let bisection = bisect(&versions, threshold, |version| {
+        println!("Benchmarking Factorio {}", version);
+        let mean = measure_version(version, &benchmark_params)?;
+        let baseline = *baseline.get_or_insert(mean);
+        match slowdown(baseline, mean) {
+            Some(change) => println!(
+                "Factorio {}: {:.3} ms wholeUpdate ({:+.1}% from {})",
+                version, mean, change, good
+            ),
+            None => {
+                println!("Factorio {}: {:.3} ms wholeUpdate", version, mean)
+            }
+        }
+        Ok(mean)
+    })?;
+    match (bisection.first_slow, bisection.last_good) {
+        (Some(first_slow), Some(last_good)) => println!(
+            "First version more than {}% slower than {}: {} (last good \
+             version {})",
+            threshold, good, first_slow, last_good
+        ),
+        _ => println!(
+            "Factorio {} is not more than {}% slower than {}, nothing to \
+             bisect",
+            bad, threshold, good
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bisect() {
+        let versions: Vec<FactorioVersion> = (0..8)
+            .map(|patch| FactorioVersion::new(1, 1, patch))
+            .collect();
+        let measure = |version: FactorioVersion| {
+            Ok(if version.patch >= 5 { 2.2 } else { 2.0 })
+        };
+        let bisection = bisect(&versions, 5.0, measure).unwrap();
+        assert_eq!(bisection.first_slow, Some(FactorioVersion::new(1, 1, 5)));
+        assert_eq!(bisection.last_good, Some(FactorioVersion::new(1, 1, 4)));
+        let measured: Vec<u16> = bisection
+            .measured
+            .iter()
+            .map(|(version, _mean)| version.patch)
+            .collect();
+        assert_eq!(measured, vec![0, 7, 3, 5, 4]);
+
+        let bisection = bisect(&versions, 15.0, measure).unwrap();
+        assert_eq!(bisection.first_slow, None);
+        assert_eq!(bisection.measured.len(), 2);
+    }
+
+    #[test]
+    fn test_bisect_zero_baseline() {
+        let versions: Vec<FactorioVersion> = (0..4)
+            .map(|patch| FactorioVersion::new(1, 1, patch))
+            .collect();
+        assert!(bisect(&versions, 5.0, |_version| Ok(0.0)).is_err());
+        assert_eq!(slowdown(0.0, 2.0), None);
+        assert_eq!(slowdown(2.0, 2.2).map(|change| change.round()), Some(10.0));
+    }
+
+    #[test]
+    fn test_mean_whole_update() {
+        let mut data = BenchmarkData {
+            verbose_data: vec![
+                "0,1000000,5,1".to_owned(),
+                "1,3000000,5,1".to_owned(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(mean_whole_update(&data), Some(2.0));
+        data.verbose_data.clear();
+        assert_eq!(mean_whole_update(&data), None);
+    }
+}
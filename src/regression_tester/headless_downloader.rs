@@ -42,7 +42,7 @@ fn get_downloadable_headless_versions(
 pub fn get_local_headless_versions() -> Result<Vec<(FactorioVersion, PathBuf)>, std::io::Error> {
     let mut versions = vec![];
 
-    let rd_dir = std::fs::read_dir(&fbh_regression_headless_storage())?;
+    let rd_dir = std::fs::read_dir(fbh_regression_headless_storage())?;
     for entry in rd_dir {
         let entry = entry?;
         let fname = entry.file_name();
@@ -65,41 +65,74 @@ pub fn get_local_headless_versions() -> Result<Vec<(FactorioVersion, PathBuf)>,
 
 /// Download a single Factorio version from the Factorio website.
 /// Does not require any authentication for headless version of Factorio.
-fn download_single_version(client: &Agent, url_segment: &str) {
+/// Returns the last failure if the download did not succeed.
+fn download_single_version(
+    client: &Agent,
+    url_segment: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let combined_url = format!("{}{}", FACTORIO_BASE_URL, url_segment);
+    let mut failure = String::new();
     for i in 1..=3 {
-        let combined_url = format!("{}{}", FACTORIO_BASE_URL, url_segment);
         eprintln!("Attempting download of {}, attempt {}", combined_url, i);
         let resp = client.get(&combined_url).timeout(Duration::from_secs(60)).call();
-        let url = resp.get_url();
-        if resp.ok() {
+        if let Some(e) = resp.synthetic_error() {
+            failure = format!("{}: {}", combined_url, e);
+        } else if resp.ok() {
+            let url = resp.get_url().to_owned();
             let parsed_filename = url.split('?').next().unwrap();
             let parsed_filename =
-                parsed_filename.split('/').last().unwrap().to_owned();
-            assert!(
-                parsed_filename.starts_with("factorio_headless_x64_"),
-                "Parsing filename had unexpected format!? {}\nParsed {}",
-                url,
-                parsed_filename
-            );
+                parsed_filename.split('/').next_back().unwrap().to_owned();
+            if !parsed_filename.starts_with("factorio_headless_x64_") {
+                return Err(format!(
+                    "Parsing filename had unexpected format!? {}\nParsed {}",
+                    url,
+                    parsed_filename
+                ).into());
+            }
             let mut reader = resp.into_reader();
             let mut bytes = vec![];
-            if reader.read_to_end(&mut bytes).is_ok()
-                && std::fs::write(
-                    &fbh_regression_headless_storage().join(parsed_filename),
-                    bytes,
-                )
-                .is_ok()
-            {
-                break;
+            match reader.read_to_end(&mut bytes) {
+                Ok(_) => {
+                    std::fs::write(
+                        fbh_regression_headless_storage().join(parsed_filename),
+                        bytes,
+                    )?;
+                    return Ok(());
+                }
+                Err(e) => failure = format!("{}: {}", combined_url, e),
             }
         } else if resp.status() == 503 {
             eprintln!("Recieved 503 error, sleeping 1 second");
+            failure = format!("{} returned {}", combined_url, resp.status_line());
             std::thread::sleep(Duration::from_secs(1));
         } else {
-            eprintln!("Some other unknown error error happened, giving up on this version");
-            break;
+            return Err(format!("{} returned {}", combined_url, resp.status_line()).into());
         }
     }
+    Err(failure.into())
+}
+
+/// Downloads a single Factorio version, unless it is already present locally.
+/// Returns Ok(true) if the version is present locally afterwards.
+/// Returns Ok(false) if the version is not available for download.
+/// Returns Err if the download failed.
+pub fn download_headless_version(
+    client: &Agent,
+    version: FactorioVersion,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let is_local = || -> Result<bool, io::Error> {
+        Ok(get_local_headless_versions()?.iter().any(|(local, _path)| *local == version))
+    };
+    if is_local()? {
+        return Ok(true);
+    }
+    let remote_versions = get_downloadable_headless_versions(client)?;
+    if let Some((_version, url_segment)) =
+        remote_versions.iter().find(|(remote, _url)| *remote == version)
+    {
+        download_single_version(client, url_segment)?;
+    }
+    Ok(is_local()?)
 }
 
 /// Unpacks a given FactorioVersion if it's present.
@@ -145,26 +178,100 @@ pub fn get_unpacked_executables() -> Result<Vec<(FactorioVersion, PathBuf)>, io:
     Ok(found_version_path_tuple)
 }
 
-/// Download the Factorio versions that are available remotely but not present
-/// locally.
-pub fn download_nonlocal_versions(client: &Agent) {
-    let remote_versions_available = get_downloadable_headless_versions(&client);
-    let local_versions = get_local_headless_versions();
-    if let Ok(remote_versions) = remote_versions_available {
-        if let Ok(local_versions) = local_versions {
-            let needed_remote_versions = remote_versions
-                .iter()
-                .filter(|(vers, _urls)| {
-                    !local_versions
-                        .iter()
-                        .map(|x| x.0)
-                        .any(|x| x == *vers)
-                    });
-            for (_ver, url_segment) in needed_remote_versions {
-                download_single_version(client, url_segment);
+/// The newest `keep` of `versions` at or above `oldest`, or all of them if
+/// `keep` is None, newest first.
+fn newest_versions(
+    versions: &[FactorioVersion],
+    oldest: FactorioVersion,
+    keep: Option<usize>,
+) -> Vec<FactorioVersion> {
+    let mut newest = versions
+        .iter()
+        .copied()
+        .filter(|version| *version >= oldest)
+        .collect::<Vec<_>>();
+    newest.sort_by(|a, b| b.cmp(a));
+    newest.dedup();
+    if let Some(keep) = keep {
+        newest.truncate(keep);
+    }
+    newest
+}
+
+/// Downloads the headless versions at or above `oldest` which are not present
+/// locally. With a `keep`, only the newest `keep` of them are needed, so that
+/// a regression test does not download more than `--headless prune --keep`
+/// would keep. Without a `client`, only the local versions are considered.
+/// Returns the needed versions, whether downloaded or already local.
+pub fn download_needed_versions(
+    client: Option<&Agent>,
+    oldest: FactorioVersion,
+    keep: Option<usize>,
+) -> Result<Vec<FactorioVersion>, Box<dyn std::error::Error>> {
+    let local_versions = get_local_headless_versions()?
+        .into_iter()
+        .map(|(vers, _path)| vers)
+        .collect::<Vec<_>>();
+    let remote_versions = match client {
+        Some(client) => get_downloadable_headless_versions(client)?,
+        None => vec![],
+    };
+    let needed = newest_versions(
+        &local_versions
+            .iter()
+            .copied()
+            .chain(remote_versions.iter().map(|(vers, _url)| *vers))
+            .collect::<Vec<_>>(),
+        oldest,
+        keep,
+    );
+    if let Some(client) = client {
+        let to_download = remote_versions
+            .iter()
+            .filter(|(vers, _url)| needed.contains(vers) && !local_versions.contains(vers))
+            .collect::<Vec<_>>();
+        for (i, (vers, url_segment)) in to_download.iter().enumerate() {
+            println!(
+                "Downloading headless Factorio {} ({} of {})",
+                vers,
+                i + 1,
+                to_download.len()
+            );
+            if let Err(e) = download_single_version(client, url_segment) {
+                eprintln!("Failed to download Factorio {}: {}", vers, e);
             }
         }
     }
+    Ok(needed)
+}
+
+/// Downloads every available headless version from `good` to `bad` which is
+/// not already present locally.
+pub fn download_versions_between(
+    client: &Agent,
+    good: FactorioVersion,
+    bad: FactorioVersion,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let local_versions = get_local_headless_versions()?
+        .into_iter()
+        .map(|(vers, _path)| vers)
+        .collect::<Vec<_>>();
+    let to_download = get_downloadable_headless_versions(client)?
+        .into_iter()
+        .filter(|(vers, _url)| *vers >= good && *vers <= bad && !local_versions.contains(vers))
+        .collect::<Vec<_>>();
+    for (i, (vers, url_segment)) in to_download.iter().enumerate() {
+        println!(
+            "Downloading headless Factorio {} ({} of {})",
+            vers,
+            i + 1,
+            to_download.len()
+        );
+        if let Err(e) = download_single_version(client, url_segment) {
+            eprintln!("Failed to download Factorio {}: {}", vers, e);
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -191,17 +298,34 @@ mod tests {
         }        
     }
 
-    #[ignore]
     #[test]
-    fn test_download_nonlocal_versions() {
-        let client = Agent::new();
-        download_nonlocal_versions(&client);
+    fn test_newest_versions() {
+        let versions = [
+            FactorioVersion::new(0, 17, 79),
+            FactorioVersion::new(1, 1, 107),
+            FactorioVersion::new(1, 0, 0),
+            FactorioVersion::new(1, 1, 107),
+            FactorioVersion::new(0, 18, 47),
+        ];
+        assert_eq!(
+            newest_versions(&versions, FactorioVersion::new(0, 18, 0), Some(2)),
+            vec![FactorioVersion::new(1, 1, 107), FactorioVersion::new(1, 0, 0)]
+        );
+        assert_eq!(
+            newest_versions(&versions, FactorioVersion::new(1, 0, 0), Some(5)).len(),
+            2
+        );
+        assert_eq!(
+            newest_versions(&versions, FactorioVersion::new(0, 18, 0), None).len(),
+            3
+        );
     }
 
     #[test]
+    #[ignore]
     fn test_unpack_headless_fv() {
-        test_download_nonlocal_versions();
         let fv = FactorioVersion::new(0, 17, 79);
+        download_headless_version(&Agent::new(), fv).unwrap();
         unpack_headless_version(fv).unwrap();
     }
 }
@@ -0,0 +1,323 @@
+//! Managing the downloaded and unpacked headless Factorio versions used by
+//! regression tests, which can take up tens of GB.
+
+use crate::error::FbhError;
+use crate::regression_tester::headless_downloader::download_headless_version;
+use crate::regression_tester::headless_downloader::get_local_headless_versions;
+use crate::regression_tester::headless_downloader::get_unpacked_executables;
+use crate::regression_tester::headless_downloader::unpack_headless_version;
+use crate::util::fbh_unpacked_headless_storage;
+use crate::util::mirror::is_offline;
+use megabase_index_incrementer::FactorioVersion;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use ureq::Agent;
+
+/// The number of newest versions `prune` keeps when no --keep is given.
+pub const DEFAULT_HEADLESS_KEEP: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeadlessCommand {
+    /// Lists every downloaded and unpacked version with its disk usage.
+    List,
+    /// Downloads a version from factorio.com.
+    Fetch(FactorioVersion),
+    /// Unpacks a downloaded version.
+    Unpack(FactorioVersion),
+    /// Removes every version but the newest `keep`.
+    Prune {
+        keep: usize,
+        keep_unpacked: KeepUnpacked,
+        dry_run: bool,
+    },
+}
+
+/// Which of the versions kept by `prune` stay unpacked. Versions without a
+/// downloaded archive always stay unpacked, as they could not be unpacked
+/// again.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum KeepUnpacked {
+    #[default]
+    All,
+    /// The newest N, where `none` is 0.
+    Newest(usize),
+}
+
+impl std::str::FromStr for KeepUnpacked {
+    type Err = String;
+    fn from_str(s: &str) -> Result<KeepUnpacked, Self::Err> {
+        match s.trim() {
+            "all" => Ok(KeepUnpacked::All),
+            "none" => Ok(KeepUnpacked::Newest(0)),
+            n => n.parse().map(KeepUnpacked::Newest).map_err(|_| {
+                format!(
+                    "Invalid --keep-unpacked {:?}, expected all, none or a \
+                     number",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+/// A downloaded archive or unpacked directory of a version, and the bytes
+/// it takes up.
+#[derive(Debug, Clone, PartialEq)]
+struct HeadlessFile {
+    path: PathBuf,
+    size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct HeadlessInstall {
+    version: FactorioVersion,
+    archive: Option<HeadlessFile>,
+    unpacked: Option<HeadlessFile>,
+}
+
+pub fn run_headless_command(command: &HeadlessCommand) -> Result<(), FbhError> {
+    match command {
+        HeadlessCommand::List => {
+            print_headless_installs(&headless_installs()?);
+            Ok(())
+        }
+        HeadlessCommand::Fetch(version) => fetch_version(*version),
+        HeadlessCommand::Unpack(version) => unpack_version(*version),
+        HeadlessCommand::Prune {
+            keep,
+            keep_unpacked,
+            dry_run,
+        } => prune_versions(*keep, *keep_unpacked, *dry_run),
+    }
+}
+
+/// The total size of the files under `path`, not following symlinks.
+fn disk_usage(path: &Path) -> u64 {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| disk_usage(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn format_size(bytes: u64) -> String {
+    let mib = bytes as f64 / (1024.0 * 1024.0);
+    if mib >= 1024.0 {
+        format!("{:.1} GiB", mib / 1024.0)
+    } else {
+        format!("{:.1} MiB", mib)
+    }
+}
+
+/// Every downloaded or unpacked version, oldest first.
+fn headless_installs() -> Result<Vec<HeadlessInstall>, FbhError> {
+    let mut installs: BTreeMap<FactorioVersion, HeadlessInstall> =
+        BTreeMap::new();
+    let install = |version| HeadlessInstall {
+        version,
+        archive: None,
+        unpacked: None,
+    };
+    for (version, path) in get_local_headless_versions()? {
+        let size = disk_usage(&path);
+        installs
+            .entry(version)
+            .or_insert_with(|| install(version))
+            .archive = Some(HeadlessFile { path, size });
+    }
+    for (version, _executable) in get_unpacked_executables()? {
+        let path = fbh_unpacked_headless_storage().join(version.to_string());
+        let size = disk_usage(&path);
+        installs
+            .entry(version)
+            .or_insert_with(|| install(version))
+            .unpacked = Some(HeadlessFile { path, size });
+    }
+    Ok(installs.into_values().collect())
+}
+
+fn print_headless_installs(installs: &[HeadlessInstall]) {
+    if installs.is_empty() {
+        println!("No headless Factorio versions downloaded");
+        return;
+    }
+    let size = |file: &Option<HeadlessFile>| {
+        file.as_ref()
+            .map(|file| format_size(file.size))
+            .unwrap_or_else(|| "-".to_owned())
+    };
+    println!("{:<10} {:>12} {:>12}", "version", "archive", "unpacked");
+    let mut total = 0;
+    for install in installs {
+        println!(
+            "{:<10} {:>12} {:>12}",
+            install.version.to_string(),
+            size(&install.archive),
+            size(&install.unpacked)
+        );
+        total += install_files(install).map(|file| file.size).sum::<u64>();
+    }
+    println!(
+        "{} versions using {} in total",
+        installs.len(),
+        format_size(total)
+    );
+}
+
+fn install_files(
+    install: &HeadlessInstall,
+) -> impl Iterator<Item = &HeadlessFile> {
+    install.archive.iter().chain(install.unpacked.iter())
+}
+
+fn fetch_version(version: FactorioVersion) -> Result<(), FbhError> {
+    if is_offline() {
+        return Err(FbhError::HeadlessDownload(format!(
+            "Offline, not downloading Factorio {}",
+            version
+        )));
+    }
+    match download_headless_version(&Agent::new(), version) {
+        Ok(true) => {
+            println!("Headless Factorio {} is downloaded", version);
+            Ok(())
+        }
+        Ok(false) => Err(FbhError::HeadlessDownload(format!(
+            "Factorio {} is not available for download",
+            version
+        ))),
+        Err(e) => Err(FbhError::HeadlessDownload(format!(
+            "Could not download Factorio {}: {}",
+            version, e
+        ))),
+    }
+}
+
+fn unpack_version(version: FactorioVersion) -> Result<(), FbhError> {
+    if unpack_headless_version(version)? {
+        println!("Headless Factorio {} is unpacked", version);
+        Ok(())
+    } else {
+        Err(FbhError::InvalidInput(format!(
+            "Headless Factorio {} is not downloaded, use --headless fetch {0}",
+            version
+        )))
+    }
+}
+
+/// The archives and unpacked directories `prune` removes from `installs`,
+/// which are ordered oldest first.
+fn prune_plan(
+    installs: &[HeadlessInstall],
+    keep: usize,
+    keep_unpacked: KeepUnpacked,
+) -> Vec<&HeadlessFile> {
+    let mut removed = Vec::new();
+    for (age, install) in installs.iter().rev().enumerate() {
+        if age >= keep {
+            removed.extend(install_files(install));
+        } else if let (KeepUnpacked::Newest(n), Some(_), Some(unpacked)) =
+            (keep_unpacked, &install.archive, &install.unpacked)
+        {
+            if age >= n {
+                removed.push(unpacked);
+            }
+        }
+    }
+    removed
+}
+
+fn prune_versions(
+    keep: usize,
+    keep_unpacked: KeepUnpacked,
+    dry_run: bool,
+) -> Result<(), FbhError> {
+    let installs = headless_installs()?;
+    let removed = prune_plan(&installs, keep, keep_unpacked);
+    if removed.is_empty() {
+        println!("Nothing to prune");
+        return Ok(());
+    }
+    for file in &removed {
+        println!(
+            "{} {:?} ({})",
+            if dry_run { "Would remove" } else { "Removing" },
+            file.path,
+            format_size(file.size)
+        );
+        if !dry_run {
+            if file.path.is_dir() {
+                std::fs::remove_dir_all(&file.path)?;
+            } else {
+                std::fs::remove_file(&file.path)?;
+            }
+        }
+    }
+    println!(
+        "{} {}",
+        if dry_run { "Would free" } else { "Freed" },
+        format_size(removed.iter().map(|file| file.size).sum())
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install(patch: u16, archive: bool, unpacked: bool) -> HeadlessInstall {
+        let file = |kind: &str| HeadlessFile {
+            path: PathBuf::from(format!("{}-{}", kind, patch)),
+            size: 10,
+        };
+        HeadlessInstall {
+            version: FactorioVersion::new(1, 1, patch),
+            archive: if archive { Some(file("archive")) } else { None },
+            unpacked: if unpacked {
+                Some(file("unpacked"))
+            } else {
+                None
+            },
+        }
+    }
+
+    #[test]
+    fn test_prune_plan() {
+        let installs = vec![
+            install(0, true, true),
+            install(1, true, false),
+            install(2, false, true),
+            install(3, true, true),
+            install(4, true, true),
+        ];
+        let removed = |keep, keep_unpacked| -> Vec<String> {
+            prune_plan(&installs, keep, keep_unpacked)
+                .iter()
+                .map(|file| file.path.to_string_lossy().into_owned())
+                .collect()
+        };
+        assert_eq!(
+            removed(3, KeepUnpacked::All),
+            vec!["archive-1", "archive-0", "unpacked-0"]
+        );
+        assert_eq!(
+            removed(3, KeepUnpacked::Newest(1)),
+            vec!["unpacked-3", "archive-1", "archive-0", "unpacked-0"]
+        );
+        assert_eq!(removed(5, KeepUnpacked::Newest(0)).len(), 3);
+        assert_eq!("none".parse(), Ok(KeepUnpacked::Newest(0)));
+        assert!("some".parse::<KeepUnpacked>().is_err());
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024 / 2), "1.5 GiB");
+    }
+}
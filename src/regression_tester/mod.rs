@@ -1,7 +1,21 @@
 //! Module for running regression tests against Factorio versions.
 mod headless_downloader;
+pub use headless_downloader::get_unpacked_executables;
+
+mod bisect;
+mod headless_manager;
+pub use headless_manager::run_headless_command;
+pub use headless_manager::HeadlessCommand;
+pub use headless_manager::KeepUnpacked;
+pub use headless_manager::DEFAULT_HEADLESS_KEEP;
+pub use bisect::run_regression_bisect;
 
 mod regression_db;
+mod regression_report;
+pub use regression_report::write_regression_report;
+pub use regression_report::RegressionReportFormat;
+pub use regression_report::RegressionReportOptions;
+pub use regression_report::DEFAULT_REGRESSION_THRESHOLD;
 
 use crate::regression_tester::regression_db::put_testcase_to_db;
 use crate::regression_tester::regression_db::get_scenarios;
@@ -13,29 +27,36 @@ use crate::benchmark_runner::parse_stdout_for_factorio_version;
 use crate::benchmark_runner::run_factorio_benchmark;
 use crate::util::fbh_mod_use_dir;
 use crate::benchmark_runner::SimpleBenchmarkParams;
-use crate::regression_tester::headless_downloader::get_unpacked_executables;
+use crate::util::ProcessScheduling;
+use crate::util::config_file::CONFIG_FILE_SETTINGS;
+use crate::util::config_file::ForwardCompatibilityConfigSettings;
+use crate::util::config_file::RegressionParams;
+use crate::util::mirror::{fetch_from_mirror, is_offline, missing_from_mirror, mirror_root, mirror_save_path};
 use crate::regression_tester::headless_downloader::unpack_headless_version;
-use crate::regression_tester::headless_downloader::get_local_headless_versions;
 use megabase_index_incrementer::MegabaseMetadata;
 use megabase_index_incrementer::FactorioVersion;
 use std::collections::HashSet;
+use std::path::Path;
 use std::path::PathBuf;
 use crate::util::sha256sum;
 use crate::util::factorio_save_directory;
 use ureq::Agent;
-use std::io;
 use std::io::Read;
 use megabase_index_incrementer::Megabases;
 use crate::regression_tester::regression_db::put_scenario_to_db;
-use crate::regression_tester::headless_downloader::download_nonlocal_versions;
+use crate::regression_tester::headless_downloader::download_needed_versions;
+use crate::error::FbhError;
 
 lazy_static! {
     /// The subfolder where any applicable megabases are to be stored.
     static ref REGRESSION_TEST_SUBFOLDER: PathBuf
         = factorio_save_directory().join("regression-test");
-    static ref MEGABASES: Megabases = fetch_megabase_list().unwrap();
 }
 
+/// The ticks and runs of each map when not configured otherwise.
+const DEFAULT_REGRESSION_TICKS: u32 = 100;
+const DEFAULT_REGRESSION_RUNS: u32 = 10;
+
 const RECIPE_VERSIONS: [FactorioVersion; 3] = [
     FactorioVersion::new(0,16,51),
     FactorioVersion::new(0,17,0),
@@ -55,11 +76,21 @@ pub struct RegressionScenario {
     pub cpuid: String,
     pub sha256: String,
     pub author: String,
-    /// The versions for which this scenario has testcases. None unless queried.
-    pub versions: Option<Vec<FactorioVersion>>,
+    /// The versions, ticks and runs for which this scenario has testcases.
+    /// None unless queried.
+    pub versions: Option<Vec<TestedVersion>>,
     pub test_instances: Vec<RegressionTestInstance>,
 }
 
+/// The Factorio version, ticks and runs of an earlier testcase. Testcases only
+/// match when all three do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TestedVersion {
+    pub factorio_version: FactorioVersion,
+    pub ticks: u32,
+    pub runs: u32,
+}
+
 /// A single map ran in a single version of Factorio.
 #[derive(Default, Debug, Clone)]
 pub struct RegressionTestInstance {
@@ -70,10 +101,36 @@ pub struct RegressionTestInstance {
     pub verbose_data: Vec<String>,
 }
 
+/// The ticks and runs to test `map_name` with, taken from `params`, the map's
+/// section of config.ini, config.ini, then the defaults.
+fn resolve_regression_params(
+    map_name: &str,
+    params: RegressionParams,
+    settings: &ForwardCompatibilityConfigSettings,
+) -> (u32, u32) {
+    let resolved = params
+        .or(settings.regression_maps.get(map_name).copied().unwrap_or_default())
+        .or(settings.regression);
+    (
+        resolved.ticks.unwrap_or(DEFAULT_REGRESSION_TICKS),
+        resolved.runs.unwrap_or(DEFAULT_REGRESSION_RUNS),
+    )
+}
+
 /// Runs regression tests against Factorio
 /// A value of `true` in clean will run all available versions against all maps
-/// A value of `false` will only run new maps and/or new versions.
-pub fn run_regression_tests(clean: bool, single_map_path: Option<&PathBuf>) {
+/// A value of `false` will only run new maps and/or new versions, or maps
+/// whose ticks or runs changed.
+/// Any ticks or runs in `params` override those in config.ini for every map.
+/// Every headless version the maps can be loaded in is downloaded and
+/// unpacked, or only the newest `keep` of them, as `--headless prune --keep`
+/// would keep.
+pub fn run_regression_tests(
+    clean: bool,
+    single_map_path: Option<&PathBuf>,
+    params: RegressionParams,
+    keep: Option<usize>,
+) -> Result<(), FbhError> {
     println!("Attempting to run regression tests");
 
     let already_ran_scenarios = if !clean {
@@ -83,26 +140,29 @@ pub fn run_regression_tests(clean: bool, single_map_path: Option<&PathBuf>) {
     };
 
 
-    if let Ok(validated) = fetch_files() {
-        println!("Fetched all files");
-        let mut megabases_to_run = MEGABASES.saves.clone();
-        megabases_to_run.retain(|save| validated.contains(&save.sha256));
-        if let Some(single_map) = single_map_path {
-            megabases_to_run.clear();
-            megabases_to_run.push(megabase_index_incrementer::populate_metadata(single_map).unwrap());
-        }
-        println!("Using set of files {:#?}", megabases_to_run);
-        let mut least_seen_version = FactorioVersion::new(100,100,100);
-        for save in &megabases_to_run {
-            if save.factorio_version < least_seen_version {
-                least_seen_version = save.factorio_version;
-            }
+    let megabases = fetch_megabase_list()?;
+    let validated = fetch_files(&megabases)?;
+    println!("Fetched all files");
+    let mut megabases_to_run = megabases.saves.clone();
+    megabases_to_run.retain(|save| validated.contains(&save.sha256));
+    if let Some(single_map) = single_map_path {
+        megabases_to_run.clear();
+        megabases_to_run.push(megabase_index_incrementer::populate_metadata(single_map).unwrap());
+    }
+    println!("Using set of files {:#?}", megabases_to_run);
+    let mut least_seen_version = FactorioVersion::new(100,100,100);
+    for save in &megabases_to_run {
+        if save.factorio_version < least_seen_version {
+            least_seen_version = save.factorio_version;
         }
-        if let Ok(mut headless_versions) = get_local_headless_versions() {
-            headless_versions.retain(|(vers, _paths)| vers >= &least_seen_version);
+    }
+    let client = Agent::new();
+    let client = if is_offline() { None } else { Some(&client) };
+    match download_needed_versions(client, least_seen_version, keep) {
+        Ok(headless_versions) => {
+            let unpacked_count = headless_versions.len();
             let mut version_unpacking_jhs = vec![];
-            for headless_tuple in headless_versions {
-                let version = headless_tuple.0;
+            for version in headless_versions {
                 version_unpacking_jhs.push(std::thread::spawn(move || {
                     unpack_headless_version(version)
                 }));
@@ -110,133 +170,147 @@ pub fn run_regression_tests(clean: bool, single_map_path: Option<&PathBuf>) {
             for jh in version_unpacking_jhs {
                 let _ = jh.join().unwrap();
             }
-            println!("Unpacked all headless versions");
+            println!("Unpacked {} headless versions", unpacked_count);
         }
+        Err(e) => eprintln!("Error fetching headless versions: {}", e),
+    }
 
-        // The quantity of versions each recipe can be tested in, based on
-        // available unpacked factorio executables.
-        let mut recipe_versions: HashMap<FactorioVersion, u32> = HashMap::new();
+    // The quantity of versions each recipe can be tested in, based on
+    // available unpacked factorio executables.
+    let mut recipe_versions: HashMap<FactorioVersion, u32> = HashMap::new();
 
-        if let Ok(unpacked) = get_unpacked_executables() {
-            for (fv, _p) in &unpacked {
-                let mut fv_recipe = FactorioVersion::default();
-                for recipe in RECIPE_VERSIONS.iter() {
-                    if recipe > fv {
-                        break;
-                    }
-                    fv_recipe = *recipe;
+    if let Ok(unpacked) = get_unpacked_executables() {
+        for (fv, _p) in &unpacked {
+            let mut fv_recipe = FactorioVersion::default();
+            for recipe in RECIPE_VERSIONS.iter() {
+                if recipe > fv {
+                    break;
                 }
-                let recipe_ct = recipe_versions.entry(fv_recipe).or_insert(0u32);
-                *recipe_ct += 1;
+                fv_recipe = *recipe;
             }
-            for save in megabases_to_run {
-                let mut fv_recipe = FactorioVersion::default();
-                for recipe in RECIPE_VERSIONS.iter() {
-                    if recipe > &save.factorio_version {
-                        break;
-                    }
-                    fv_recipe = *recipe;
+            let recipe_ct = recipe_versions.entry(fv_recipe).or_insert(0u32);
+            *recipe_ct += 1;
+        }
+        for save in megabases_to_run {
+            let mut fv_recipe = FactorioVersion::default();
+            for recipe in RECIPE_VERSIONS.iter() {
+                if recipe > &save.factorio_version {
+                    break;
                 }
-                // Skip testing saves if theres 0-1 recipe compatible versions
-                // available.
-                if let Some(num_exes) = recipe_versions.get(&fv_recipe) {
-                    if num_exes <= &1 {
-                        continue;
-                    }
-                } else {
+                fv_recipe = *recipe;
+            }
+            // Skip testing saves if theres 0-1 recipe compatible versions
+            // available.
+            if let Some(num_exes) = recipe_versions.get(&fv_recipe) {
+                if num_exes <= &1 {
                     continue;
                 }
-                println!("Running save {:?}", save);
-                let mut scenario = RegressionScenario {
-                    db_id: None,
-                    author: save.author.unwrap_or_default(),
-                    cpuid: query_system_cpuid(),
-                    factorio_version: save.factorio_version,
-                    platform: "linux64 headless".to_owned(),
-                    map_name: save.name.clone(),
-                    sha256: save.sha256,
-                    versions: None,
-                    test_instances: vec![],
-                };
-                for factorio_install in &unpacked {
-                    if factorio_install.0 < save.factorio_version {
-                        continue;
-                    }
-                    if !clean {
-                        if let Some(entry) = already_ran_scenarios.get(&scenario.sha256) {
-                            if entry.author == scenario.author
-                                    && entry.factorio_version == scenario.factorio_version
-                                    && entry.cpuid == scenario.cpuid
-                                    && entry.platform == scenario.platform
-                                    && entry.map_name == scenario.map_name {
-                                if let Some(vers_tested_before) = &entry.versions {
-                                    if vers_tested_before.contains(&factorio_install.0) {
-                                        println!("Skipping testing {} with version {} \
-                                        as we already have a testcase for it"
-                                        , scenario.map_name, factorio_install.0.to_string());
-                                        continue;
-                                    }
+            } else {
+                continue;
+            }
+            println!("Running save {:?}", save);
+            let mut scenario = RegressionScenario {
+                db_id: None,
+                author: save.author.unwrap_or_default(),
+                cpuid: query_system_cpuid(),
+                factorio_version: save.factorio_version,
+                platform: "linux64 headless".to_owned(),
+                map_name: save.name.clone(),
+                sha256: save.sha256,
+                versions: None,
+                test_instances: vec![],
+            };
+            let (ticks, runs) = resolve_regression_params(
+                &scenario.map_name,
+                params,
+                &CONFIG_FILE_SETTINGS,
+            );
+            println!("Testing {} ticks for {} runs", ticks, runs);
+            for factorio_install in &unpacked {
+                if factorio_install.0 < save.factorio_version {
+                    continue;
+                }
+                if !clean {
+                    if let Some(entry) = already_ran_scenarios.get(&scenario.sha256) {
+                        if entry.author == scenario.author
+                                && entry.factorio_version == scenario.factorio_version
+                                && entry.cpuid == scenario.cpuid
+                                && entry.platform == scenario.platform
+                                && entry.map_name == scenario.map_name {
+                            let tested = TestedVersion {
+                                factorio_version: factorio_install.0,
+                                ticks,
+                                runs,
+                            };
+                            if let Some(vers_tested_before) = &entry.versions {
+                                if vers_tested_before.contains(&tested) {
+                                    println!("Skipping testing {} with version {} \
+                                    as we already have a testcase for it with {} \
+                                    ticks and {} runs", scenario.map_name,
+                                    factorio_install.0, ticks, runs);
+                                    continue;
                                 }
                             }
                         }
                     }
-                    println!("In version {}", factorio_install.0.to_string());
-                    let param = SimpleBenchmarkParams {
-                        map_path: if let Some(map_path) = single_map_path {
-                            map_path.clone()
-                        } else {
-                            REGRESSION_TEST_SUBFOLDER.join(&save.name)
-                        },
-                        mod_directory: fbh_mod_use_dir(),
-                        mods: vec![],
-                        runs: 10,
-                        ticks: 100,
+                }
+                println!("In version {}", factorio_install.0);
+                let param = SimpleBenchmarkParams {
+                    map_path: if let Some(map_path) = single_map_path {
+                        map_path.clone()
+                    } else {
+                        REGRESSION_TEST_SUBFOLDER.join(&save.name)
+                    },
+                    mod_directory: fbh_mod_use_dir(),
+                    mods: vec![],
+                    runs,
+                    ticks,
+                    scheduling: ProcessScheduling::default(),
+                    timeout: None,
+                };
+                let stdout = run_factorio_benchmark(&factorio_install.1, &param);
+                if let Err(e) = &stdout {
+                    eprintln!("{}", e);
+                }
+                if let Ok(stdout) = stdout {
+                    let parsed_fv = parse_stdout_for_factorio_version(&stdout);
+                    if Some(factorio_install.0) != parsed_fv {
+                        return Err(FbhError::Factorio(format!(
+                            "version {:?} didn't match what was supposed \
+                            to be ran, {:?}",
+                            Some(factorio_install.0), parsed_fv)));
+                    }
+                    let instance = RegressionTestInstance {
+                        factorio_version: factorio_install.0,
+                        runs: param.runs,
+                        ticks: param.ticks,
+                        execution_time: parse_stdout_for_execution_time(&stdout).unwrap_or_default(),
+                        verbose_data: parse_stdout_for_verbose_data(&stdout),
                     };
-                    if let Some(stdout) = run_factorio_benchmark(&factorio_install.1, &param) {
-                        let parsed_fv = parse_stdout_for_factorio_version(&stdout);
-                        if Some(factorio_install.0) != parsed_fv {
-                            eprintln!("Error, Factorio version {:?} didn't match what was supposed to be ran, {:?}",
-                                Some(factorio_install.0), parsed_fv);
-                            std::process::exit(1);
-                        }
-                        let instance = RegressionTestInstance {
-                            factorio_version: factorio_install.0,
-                            runs: param.runs,
-                            ticks: param.ticks,
-                            execution_time: parse_stdout_for_execution_time(&stdout).unwrap_or_default(),
-                            verbose_data: parse_stdout_for_verbose_data(&stdout),
-                        };
 
-                        scenario.test_instances.push(instance);
-                    }
+                    scenario.test_instances.push(instance);
                 }
-                if !already_ran_scenarios.contains_key(&scenario.sha256) || clean {
-                    put_scenario_to_db(scenario);
-                } else if let Some(preexist ) = already_ran_scenarios.get(&scenario.sha256) {
-                    let scenario_id = preexist.db_id.unwrap();
-                    for testcase in scenario.test_instances {
-                        put_testcase_to_db(testcase, scenario_id);
-                    }
+            }
+            if !already_ran_scenarios.contains_key(&scenario.sha256) || clean {
+                put_scenario_to_db(scenario)?;
+            } else if let Some(preexist ) = already_ran_scenarios.get(&scenario.sha256) {
+                let scenario_id = preexist.db_id.unwrap();
+                for testcase in scenario.test_instances {
+                    put_testcase_to_db(testcase, scenario_id)?;
                 }
             }
         }
-    } else {
-        eprintln!("Error fetching files");
     }
+    Ok(())
 }
 
-/// Fetches all headless versions of Factorio from factorio.com, and all saves
-/// defined in the technicalfactorio megabase index.
-/// Returns a vector of the sha256sums of the saves downloaded.
-fn fetch_files() -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+/// Fetches all saves defined in the technicalfactorio megabase index.
+/// Returns a vector of the sha256sums of the saves downloaded, or the saves
+/// missing from the mirror as one error if that left no save to test.
+fn fetch_files(
+    megabases: &Megabases,
+) -> Result<HashSet<String>, FbhError> {
     println!("Fetching files");
-    let client = Agent::new();
-    let jh = {
-        std::thread::spawn(move || {
-            // Gather all available Factorio headless versions.
-            download_nonlocal_versions(&client);
-        })
-    };
     let mut valid_shas = HashSet::new();
     let mut jhs = Vec::new();
 
@@ -258,7 +332,7 @@ fn fetch_files() -> Result<HashSet<String>, Box<dyn std::error::Error>> {
         println!("Read files from disk");
     }
 
-    for save in &*MEGABASES.saves {
+    for save in &megabases.saves {
         if files_on_disk.contains(&save.name) {
             // check sha
             {
@@ -272,7 +346,9 @@ fn fetch_files() -> Result<HashSet<String>, Box<dyn std::error::Error>> {
                     }
                 }));
             }
-        } else if save.download_link_mirror.is_some() {
+        } else if save.download_link_mirror.is_some()
+                || mirror_root().is_some()
+                || is_offline() {
             // download it, then check sha
             {
                 let save = save.clone();
@@ -290,20 +366,53 @@ fn fetch_files() -> Result<HashSet<String>, Box<dyn std::error::Error>> {
         }
     }
 
+    let mut missing = None;
     for jh in jhs {
-        if let Ok(sha) = jh.join().unwrap() {
-            valid_shas.insert(sha);
+        match jh.join().unwrap() {
+            Ok(sha) => {
+                valid_shas.insert(sha);
+            }
+            Err(FbhError::MissingFromMirror { mirror, artifacts }) => {
+                let (_, all_artifacts) =
+                    missing.get_or_insert_with(|| (mirror, Vec::new()));
+                all_artifacts.extend(artifacts);
+            }
+            Err(e) => eprintln!("{}", e),
         }
     }
-    jh.join().unwrap();
+    if let Some((mirror, mut artifacts)) = missing {
+        artifacts.sort();
+        let missing = FbhError::MissingFromMirror { mirror, artifacts };
+        if valid_shas.is_empty() {
+            return Err(missing);
+        }
+        eprintln!("{}", missing);
+    }
 
     Ok(valid_shas)
 }
 
-fn download_single_save(save: &MegabaseMetadata) -> Result<String, io::Error> {
+fn download_single_save(save: &MegabaseMetadata) -> Result<String, FbhError> {
     if !REGRESSION_TEST_SUBFOLDER.exists() {
         std::fs::create_dir_all(&*REGRESSION_TEST_SUBFOLDER)?;
     }
+    let mirror_path = mirror_save_path(Some(Path::new("regression-test")), &save.name);
+    if let Some(buf) = fetch_from_mirror(&mirror_path)? {
+        let p = &*REGRESSION_TEST_SUBFOLDER.join(&save.name);
+        std::fs::write(p, buf)?;
+        let sha = sha256sum(p);
+        if sha != save.sha256 {
+            return Err(FbhError::HashMismatch {
+                name: save.name.clone(),
+                expected: save.sha256.clone(),
+                found: sha,
+            });
+        }
+        return Ok(sha);
+    }
+    if is_offline() {
+        return Err(missing_from_mirror(&mirror_path));
+    }
     if let Some(mirror) = &save.download_link_mirror {
         let resp = ureq::get(mirror).call();
         let sha = if resp.status() == 200 {
@@ -317,28 +426,42 @@ fn download_single_save(save: &MegabaseMetadata) -> Result<String, io::Error> {
             }
             sha
         } else {
-            eprintln!("Could not download file {}, {:?}", save.name, resp);
-            return Err(io::Error::new(io::ErrorKind::Other, "Could not download file"));
+            return Err(FbhError::MapDownload(format!(
+                "Could not download file {}, {:?}", save.name, resp)));
         };
         if sha != save.sha256 {
-            return Err(io::Error::new(io::ErrorKind::Other, "Downloaded file didn't match sha256 previously recorded"));
+            return Err(FbhError::HashMismatch {
+                name: save.name.clone(),
+                expected: save.sha256.clone(),
+                found: sha,
+            });
         }
         return Ok(sha);
     }
-    Err(io::Error::new(io::ErrorKind::Other, "No download link was defined"))
+    Err(FbhError::MapDownload(format!(
+        "No download link was defined for {}", save.name)))
 }
 
 /// Downloads and parses the technicalfactorio megabase index.
-fn fetch_megabase_list() -> Result<Megabases, Box<dyn std::error::Error>> {
+fn fetch_megabase_list() -> Result<Megabases, FbhError> {
+    let invalid = |e: serde_json::Error| {
+        FbhError::MapDownload(format!("Invalid listing of megabases: {}", e))
+    };
+    if let Some(buf) = fetch_from_mirror("megabases.json")? {
+        return serde_json::from_slice(&buf).map_err(invalid);
+    }
+    if is_offline() {
+        return Err(missing_from_mirror("megabases.json"));
+    }
     let resp = ureq::get("https://raw.githubusercontent.com/technicalfactorio/\
         technicalfactorio/master/megabase_index_incrementer/megabases.json")
         .call();
     if resp.status() == 200 {
         let s = resp.into_string()?;
-        Ok(serde_json::from_str(&s)?)
+        serde_json::from_str(&s).map_err(invalid)
     } else {
-        eprintln!("Could not download listing of megabases");
-        std::process::exit(1);
+        Err(FbhError::MapDownload(format!(
+            "Could not download listing of megabases, {:?}", resp)))
     }
 }
 
@@ -351,4 +474,20 @@ mod tests {
         \n   0.010 Operating system: Linux (Arch rolling)";
         assert_eq!(parse_stdout_for_factorio_version(snippet), Some(FactorioVersion::new(0,18,32)));
     }
+
+    #[test]
+    fn test_resolve_regression_params() {
+        let mut settings = ForwardCompatibilityConfigSettings::default();
+        assert_eq!(resolve_regression_params("a.zip", RegressionParams::default(), &settings),
+            (DEFAULT_REGRESSION_TICKS, DEFAULT_REGRESSION_RUNS));
+        settings.regression = RegressionParams { ticks: Some(1000), runs: Some(5) };
+        settings.regression_maps.insert("a.zip".to_owned(),
+            RegressionParams { ticks: Some(3600), runs: None });
+        assert_eq!(resolve_regression_params("a.zip", RegressionParams::default(), &settings),
+            (3600, 5));
+        assert_eq!(resolve_regression_params("b.zip", RegressionParams::default(), &settings),
+            (1000, 5));
+        let cli = RegressionParams { ticks: None, runs: Some(2) };
+        assert_eq!(resolve_regression_params("a.zip", cli, &settings), (3600, 2));
+    }
 }
@@ -4,9 +4,12 @@ use megabase_index_incrementer::FactorioVersion;
 use rusqlite::NO_PARAMS;
 use std::error::Error;
 use crate::regression_tester::RegressionScenario;
+use crate::regression_tester::TestedVersion;
 use crate::util::fbh_regression_testing_dir;
 use std::sync::Mutex;
-use std::process::exit;
+use std::sync::MutexGuard;
+use std::sync::OnceLock;
+use crate::error::FbhError;
 use rusqlite::Connection;
 use std::convert::TryFrom;
 
@@ -60,12 +63,21 @@ CREATE TABLE IF NOT EXISTS `verbose` (
 );
 ";
 
-lazy_static! {
-    static ref DB_CONNECTION: Mutex<Connection> =
-        Mutex::new(setup_regression_db());
+static DB_CONNECTION: OnceLock<Mutex<Connection>> = OnceLock::new();
+
+/// Locks the regression database, opening it on first use.
+fn lock_regression_db() -> Result<MutexGuard<'static, Connection>, FbhError> {
+    let connection = match DB_CONNECTION.get() {
+        Some(connection) => connection,
+        None => {
+            let database = setup_regression_db()?;
+            DB_CONNECTION.get_or_init(|| Mutex::new(database))
+        }
+    };
+    Ok(connection.lock().unwrap())
 }
 
-fn setup_regression_db() -> Connection {
+fn setup_regression_db() -> Result<Connection, FbhError> {
     let p = fbh_regression_testing_dir().join("regression.db");
     if !p.exists() {
         std::fs::OpenOptions::new()
@@ -74,23 +86,16 @@ fn setup_regression_db() -> Connection {
             .open(&p)
             .ok();
     }
-    let database = rusqlite::Connection::open(&p).unwrap();
-    match database.execute_batch(SQL) {
-        Ok(_) => (),
-        Err(e) => {
-            eprintln!("Couldn't create tables in db?");
-            eprintln!("{}", e);
-            exit(1);
-        }
-    }
-    database
+    let database = rusqlite::Connection::open(&p)?;
+    database.execute_batch(SQL)?;
+    Ok(database)
 }
 
 /// Puts a specific testcase into the database
-pub fn put_testcase_to_db(data: RegressionTestInstance, scenario_id: u32) {
+pub fn put_testcase_to_db(data: RegressionTestInstance, scenario_id: u32) -> Result<(), FbhError> {
     let test_instance_header = "factorio_version,runs,ticks,execution_time,scenario_id";
-    let mut db = DB_CONNECTION.lock().unwrap();
-    let tx = db.transaction().unwrap();
+    let mut db = lock_regression_db()?;
+    let tx = db.transaction()?;
 
     let csv_instance = format!("{:?},{:?},{:?},{:?},{:?}",
         data.factorio_version.to_string(),
@@ -103,14 +108,10 @@ pub fn put_testcase_to_db(data: RegressionTestInstance, scenario_id: u32) {
         "INSERT INTO regression_test_instance({}) VALUES ({});",
         test_instance_header, csv_instance
     );
-    match tx.execute_batch(&combined_sql) {
-        Ok(_) => (),
-        Err(e) => {
-            eprintln!("Failed to insert benchmark data to database!");
-            eprintln!("{}", e);
-            eprintln!("{:?}", combined_sql);
-            exit(1);
-        }
+    if let Err(e) = tx.execute_batch(&combined_sql) {
+        eprintln!("Failed to insert benchmark data to database!");
+        eprintln!("{:?}", combined_sql);
+        return Err(e.into());
     }
 
     let instance_id = tx.last_insert_rowid() as u32;
@@ -126,19 +127,16 @@ pub fn put_testcase_to_db(data: RegressionTestInstance, scenario_id: u32) {
             verbose_header, line, instance_id
         ));
     }
-    match tx.execute_batch(&combined_sql) {
-        Ok(_) => {}
-        Err(e) => {
-            eprintln!("Failed to insert verbose data to database!");
-            eprintln!("{}", e);
-            exit(1);
-        }
+    if let Err(e) = tx.execute_batch(&combined_sql) {
+        eprintln!("Failed to insert verbose data to database!");
+        return Err(e.into());
     }
-    tx.commit().unwrap();
+    tx.commit()?;
+    Ok(())
 }
 
 /// Puts data to the database.
-pub fn put_scenario_to_db(data: RegressionScenario) {
+pub fn put_scenario_to_db(data: RegressionScenario) -> Result<(), FbhError> {
     let scenario_id = {
         let collection_header =
             "map_name,start_factorio_version,platform,cpuid,sha256,author";
@@ -157,28 +155,25 @@ pub fn put_scenario_to_db(data: RegressionScenario) {
             collection_header, csv_collection
         );
 
-        let mut db = DB_CONNECTION.lock().unwrap();
-        let tx = db.transaction().unwrap();
-        match tx.execute_batch(&combined_sql) {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("Failed to insert regression data to database!");
-                eprintln!("{}", e);
-                eprintln!("{:?}", combined_sql);
-                exit(1);
-            }
+        let mut db = lock_regression_db()?;
+        let tx = db.transaction()?;
+        if let Err(e) = tx.execute_batch(&combined_sql) {
+            eprintln!("Failed to insert regression data to database!");
+            eprintln!("{:?}", combined_sql);
+            return Err(e.into());
         }
         let scenario_id = tx.last_insert_rowid() as u32;
-        tx.commit().unwrap();
+        tx.commit()?;
         scenario_id
     };
     for instance in data.test_instances {
-        put_testcase_to_db(instance, scenario_id);
+        put_testcase_to_db(instance, scenario_id)?;
     }
+    Ok(())
 }
 
 pub fn get_scenarios() -> Result<HashMap<String, RegressionScenario>, Box<dyn Error>> {
-    let db = &*DB_CONNECTION.lock().unwrap();
+    let db = &*lock_regression_db()?;
     let mut stmt = db.prepare(
 r"
 SELECT ID, platform, cpuid, map_name, sha256, author, start_factorio_version
@@ -200,14 +195,18 @@ FROM regression_scenario;
         )
     })?;
     let mut stmt = db.prepare(
-        "SELECT factorio_version FROM regression_test_instance where scenario_ID = ?")?;
+        "SELECT factorio_version, ticks, runs FROM regression_test_instance where scenario_ID = ?")?;
 
     let mut mash = HashMap::new();
     for row in rows {
         let mut row = row?;
-        let versions_rows = stmt.query_map(&[row.db_id], |row| {
+        let versions_rows = stmt.query_map([row.db_id], |row| {
             Ok(
-                FactorioVersion::try_from(row.get::<_, String>(0)?.as_ref()).unwrap(),
+                TestedVersion {
+                    factorio_version: FactorioVersion::try_from(row.get::<_, String>(0)?.as_ref()).unwrap(),
+                    ticks: row.get(1)?,
+                    runs: row.get(2)?,
+                }
             )
         })?;
         for ver in versions_rows {
@@ -219,3 +218,102 @@ FROM regression_scenario;
 
     Ok(mash)
 }
+
+/// The results of a map in a single Factorio version, averaged over every
+/// test instance of that version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionResult {
+    pub map_name: String,
+    pub sha256: String,
+    pub cpuid: String,
+    pub factorio_version: String,
+    pub ticks: u32,
+    pub runs: u32,
+    /// The number of test instances averaged.
+    pub instances: u32,
+    /// In seconds.
+    pub execution_time: f64,
+    /// The mean wholeUpdate in nanoseconds, None if no verbose data was stored.
+    pub mean_whole_update: Option<f64>,
+}
+
+/// Reads back the results of every map and Factorio version tested, ordered by
+/// map. Testcases of different ticks or runs are kept apart.
+pub fn get_version_results() -> Result<Vec<VersionResult>, FbhError> {
+    let db = &*lock_regression_db()?;
+    Ok(version_results(db)?)
+}
+
+fn version_results(db: &Connection) -> Result<Vec<VersionResult>, rusqlite::Error> {
+    let mut stmt = db.prepare(
+r"
+SELECT map_name, sha256, cpuid, factorio_version, ticks, runs, COUNT(*),
+    AVG(execution_time), AVG(mean_whole_update)
+FROM (
+    SELECT s.map_name, s.sha256, s.cpuid, i.factorio_version, i.ticks, i.runs,
+        i.execution_time,
+        (SELECT AVG(wholeUpdate) FROM verbose WHERE instance_ID = i.ID)
+            AS mean_whole_update
+    FROM regression_test_instance i
+    JOIN regression_scenario s ON i.scenario_ID = s.ID
+)
+GROUP BY sha256, cpuid, ticks, runs, factorio_version
+ORDER BY map_name, sha256, cpuid, ticks, runs;
+")?;
+    let rows = stmt.query_map(NO_PARAMS, |row| {
+        Ok(
+            VersionResult {
+                map_name: row.get(0)?,
+                sha256: row.get(1)?,
+                cpuid: row.get(2)?,
+                factorio_version: row.get(3)?,
+                ticks: row.get(4)?,
+                runs: row.get(5)?,
+                instances: row.get(6)?,
+                execution_time: row.get(7)?,
+                mean_whole_update: row.get(8)?,
+            }
+        )
+    })?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_results() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(SQL).unwrap();
+        db.execute_batch(
+r#"
+INSERT INTO regression_scenario(map_name,start_factorio_version,platform,cpuid,sha256,author)
+    VALUES ("b.zip","1.0.0","linux64","cpu","bb","x"), ("a.zip","1.0.0","linux64","cpu","aa","x");
+INSERT INTO regression_test_instance(factorio_version,runs,ticks,execution_time,scenario_ID)
+    VALUES ("1.0.0",1,2,10.0,1), ("1.0.0",1,2,12.0,1), ("1.1.0",1,2,5.0,2),
+        ("1.0.0",1,100,50.0,1);
+"#).unwrap();
+        let verbose_header = "run_index,tick_number,wholeUpdate,gameUpdate,\
+            circuitNetworkUpdate,transportLinesUpdate,fluidsUpdate,entityUpdate,\
+            mapGenerator,electricNetworkUpdate,logisticManagerUpdate,\
+            constructionManagerUpdate,pathFinder,trains,trainPathFinder,commander,\
+            chartRefresh,luaGarbageIncremental,chartUpdate,scriptUpdate,instance_ID";
+        for (instance, whole_update) in &[(1, 1000), (1, 3000), (2, 4000)] {
+            db.execute_batch(&format!(
+                "INSERT INTO verbose({}) VALUES (1,0,{},0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,{});",
+                verbose_header, whole_update, instance
+            )).unwrap();
+        }
+
+        let results = version_results(&db).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].map_name, "a.zip");
+        assert_eq!(results[0].mean_whole_update, None);
+        assert_eq!(results[1].factorio_version, "1.0.0");
+        assert_eq!(results[1].instances, 2);
+        assert_eq!(results[1].execution_time, 11.0);
+        assert_eq!(results[1].mean_whole_update, Some(3000.0));
+        assert_eq!((results[2].ticks, results[2].execution_time), (100, 50.0));
+    }
+}
@@ -0,0 +1,574 @@
+//! Reports of the regression test results stored in regression.db, showing how
+//! the performance of each map changed across Factorio versions.
+
+use crate::error::FbhError;
+use crate::regression_tester::regression_db::get_version_results;
+use crate::regression_tester::regression_db::VersionResult;
+use core::str::FromStr;
+use megabase_index_incrementer::FactorioVersion;
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// The change in percent between versions above which a version is flagged,
+/// when no --regression-threshold is given.
+pub const DEFAULT_REGRESSION_THRESHOLD: f64 = 5.0;
+const BAR_WIDTH: usize = 20;
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 160.0;
+const CHART_MARGIN: f64 = 30.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RegressionReportFormat {
+    /// A table and text bar chart per map.
+    #[default]
+    Markdown,
+    /// A page with a table and line charts per map.
+    Html,
+    /// A row per map and Factorio version.
+    Csv,
+}
+
+impl FromStr for RegressionReportFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<RegressionReportFormat, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(RegressionReportFormat::Markdown),
+            "html" => Ok(RegressionReportFormat::Html),
+            "csv" => Ok(RegressionReportFormat::Csv),
+            _ => Err(format!(
+                "Unknown regression report format {:?}, expected markdown, \
+                 html or csv",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RegressionReportOptions {
+    pub format: RegressionReportFormat,
+    /// Flag versions whose mean changed by more than this many percent from
+    /// the previous version.
+    pub threshold: f64,
+    /// Where to write the report, stdout if not set.
+    pub output: Option<PathBuf>,
+}
+
+/// The results of a map on a CPU with the same ticks and runs, across every
+/// Factorio version tested.
+#[derive(Debug)]
+struct MapHistory {
+    map_name: String,
+    sha256: String,
+    cpuid: String,
+    ticks: u32,
+    runs: u32,
+    versions: Vec<VersionPoint>,
+}
+
+#[derive(Debug)]
+struct VersionPoint {
+    version: FactorioVersion,
+    instances: u32,
+    /// In milliseconds.
+    mean_whole_update: Option<f64>,
+    /// In seconds.
+    execution_time: f64,
+    /// The change in percent from the previous version.
+    whole_update_change: Option<f64>,
+    execution_time_change: Option<f64>,
+    flagged: bool,
+}
+
+impl VersionPoint {
+    /// The change judged against the threshold, that of wholeUpdate if both
+    /// versions have verbose data.
+    fn change(&self) -> Option<f64> {
+        self.whole_update_change.or(self.execution_time_change)
+    }
+}
+
+/// Writes a report of every map in regression.db.
+pub fn write_regression_report(
+    options: &RegressionReportOptions,
+) -> Result<(), FbhError> {
+    let histories = build_histories(get_version_results()?, options.threshold)?;
+    if histories.is_empty() {
+        return Err(FbhError::InvalidInput(
+            "No regression test results, run --regression-test first"
+                .to_owned(),
+        ));
+    }
+    let rendered = render_report(&histories, options);
+    match &options.output {
+        Some(path) => {
+            std::fs::write(path, rendered)?;
+            println!("Wrote regression report to {:?}", path);
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+fn render_report(
+    histories: &[MapHistory],
+    options: &RegressionReportOptions,
+) -> String {
+    match options.format {
+        RegressionReportFormat::Markdown => {
+            render_markdown(histories, options.threshold)
+        }
+        RegressionReportFormat::Html => {
+            render_html(histories, options.threshold)
+        }
+        RegressionReportFormat::Csv => render_csv(histories),
+    }
+}
+
+/// Groups the results by map, CPU, ticks and runs, and flags the versions which changed by
+/// more than `threshold` percent.
+fn build_histories(
+    results: Vec<VersionResult>,
+    threshold: f64,
+) -> Result<Vec<MapHistory>, FbhError> {
+    let mut histories: Vec<MapHistory> = Vec::new();
+    for result in results {
+        let version =
+            FactorioVersion::try_from(result.factorio_version.as_str())
+                .map_err(|_| {
+                    FbhError::InvalidInput(format!(
+                        "Invalid Factorio version {:?} in regression.db",
+                        result.factorio_version
+                    ))
+                })?;
+        let point = VersionPoint {
+            version,
+            instances: result.instances,
+            mean_whole_update: result.mean_whole_update.map(|ns| ns / 1e6),
+            execution_time: result.execution_time,
+            whole_update_change: None,
+            execution_time_change: None,
+            flagged: false,
+        };
+        match histories.last_mut() {
+            Some(history)
+                if history.sha256 == result.sha256
+                    && history.cpuid == result.cpuid
+                    && history.ticks == result.ticks
+                    && history.runs == result.runs =>
+            {
+                history.versions.push(point)
+            }
+            _ => histories.push(MapHistory {
+                map_name: result.map_name,
+                sha256: result.sha256,
+                cpuid: result.cpuid,
+                ticks: result.ticks,
+                runs: result.runs,
+                versions: vec![point],
+            }),
+        }
+    }
+    flag_histories(&mut histories, threshold);
+    Ok(histories)
+}
+
+/// Orders the versions of each history by Factorio version and works out
+/// their change from the previous version.
+fn flag_histories(histories: &mut [MapHistory], threshold: f64) {
+    for history in histories {
+        history.versions.sort_by_key(|point| point.version);
+        for i in 1..history.versions.len() {
+            let (previous, current) = history.versions.split_at_mut(i);
+            let previous = &previous[i - 1];
+            let current = &mut current[0];
+            current.whole_update_change =
+                match (previous.mean_whole_update, current.mean_whole_update) {
+                    (Some(before), Some(after)) => {
+                        percent_change(before, after)
+                    }
+                    _ => None,
+                };
+            current.execution_time_change =
+                percent_change(previous.execution_time, current.execution_time);
+            current.flagged = current
+                .change()
+                .is_some_and(|change| change.abs() > threshold);
+        }
+    }
+}
+
+fn percent_change(before: f64, after: f64) -> Option<f64> {
+    if before > 0.0 {
+        Some((after - before) / before * 100.0)
+    } else {
+        None
+    }
+}
+
+fn format_change(change: Option<f64>) -> String {
+    match change {
+        Some(change) => format!("{:+.1}%", change),
+        None => String::new(),
+    }
+}
+
+/// Describes a flagged change, slower for a regression.
+fn flag_label(point: &VersionPoint) -> &'static str {
+    match point.change() {
+        Some(change) if point.flagged && change > 0.0 => "slower",
+        Some(_) if point.flagged => "faster",
+        _ => "",
+    }
+}
+
+fn text_bar(value: f64, max: f64) -> String {
+    let filled = if max > 0.0 {
+        (value / max * BAR_WIDTH as f64).round() as usize
+    } else {
+        0
+    };
+    "#".repeat(filled.min(BAR_WIDTH))
+}
+
+fn flagged_versions(history: &MapHistory) -> Vec<String> {
+    history
+        .versions
+        .iter()
+        .filter(|point| point.flagged)
+        .map(|point| format!("{} ({})", point.version, flag_label(point)))
+        .collect()
+}
+
+fn render_markdown(histories: &[MapHistory], threshold: f64) -> String {
+    let mut out = String::new();
+    writeln!(out, "# Regression report\n").unwrap();
+    writeln!(
+        out,
+        "Versions whose mean changed by more than {}% from the previous \
+         version are flagged.\n",
+        threshold
+    )
+    .unwrap();
+    for history in histories {
+        writeln!(
+            out,
+            "## {}\n\nsha256 `{}`, cpu {}, {} ticks, {} runs\n",
+            history.map_name,
+            history.sha256,
+            history.cpuid,
+            history.ticks,
+            history.runs
+        )
+        .unwrap();
+        let max_whole_update = history
+            .versions
+            .iter()
+            .filter_map(|point| point.mean_whole_update)
+            .fold(0.0, f64::max);
+        let max_execution_time = history
+            .versions
+            .iter()
+            .map(|point| point.execution_time)
+            .fold(0.0, f64::max);
+        writeln!(
+            out,
+            "| Version | wholeUpdate (ms) | Change | Execution time (s) | \
+             Change | Flag | wholeUpdate | Execution time |"
+        )
+        .unwrap();
+        writeln!(out, "|---|---:|---:|---:|---:|---|---|---|").unwrap();
+        for point in &history.versions {
+            writeln!(
+                out,
+                "| {} | {} | {} | {:.3} | {} | {} | `{}` | `{}` |",
+                point.version,
+                point
+                    .mean_whole_update
+                    .map(|mean| format!("{:.3}", mean))
+                    .unwrap_or_default(),
+                format_change(point.whole_update_change),
+                point.execution_time,
+                format_change(point.execution_time_change),
+                flag_label(point),
+                point
+                    .mean_whole_update
+                    .map(|mean| text_bar(mean, max_whole_update))
+                    .unwrap_or_default(),
+                text_bar(point.execution_time, max_execution_time),
+            )
+            .unwrap();
+        }
+        let flagged = flagged_versions(history);
+        if !flagged.is_empty() {
+            writeln!(out, "\nFlagged: {}", flagged.join(", ")).unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+    out
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A line chart of `values` across the versions of `history`, with flagged
+/// versions marked in red.
+fn svg_chart(
+    history: &MapHistory,
+    title: &str,
+    values: &[Option<f64>],
+) -> String {
+    let max = values.iter().flatten().cloned().fold(0.0, f64::max);
+    if max <= 0.0 {
+        return String::new();
+    }
+    let plot_width = CHART_WIDTH - 2.0 * CHART_MARGIN;
+    let plot_height = CHART_HEIGHT - 2.0 * CHART_MARGIN;
+    let step = if values.len() > 1 {
+        plot_width / (values.len() - 1) as f64
+    } else {
+        0.0
+    };
+    let points: Vec<(f64, f64, &VersionPoint, f64)> = values
+        .iter()
+        .zip(&history.versions)
+        .enumerate()
+        .filter_map(|(i, (value, point))| {
+            let value = (*value)?;
+            let x = CHART_MARGIN + step * i as f64;
+            let y = CHART_MARGIN + plot_height * (1.0 - value / max);
+            Some((x, y, point, value))
+        })
+        .collect();
+    let mut svg = String::new();
+    write!(
+        svg,
+        "<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\
+         <text x=\"{m}\" y=\"16\">{t}</text>\
+         <line x1=\"{m}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#999\"/>",
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT,
+        m = CHART_MARGIN,
+        b = CHART_HEIGHT - CHART_MARGIN,
+        r = CHART_WIDTH - CHART_MARGIN,
+        t = escape_html(title),
+    )
+    .unwrap();
+    let line: Vec<String> = points
+        .iter()
+        .map(|(x, y, _, _)| format!("{:.1},{:.1}", x, y))
+        .collect();
+    write!(
+        svg,
+        "<polyline points=\"{}\" fill=\"none\" stroke=\"#36c\"/>",
+        line.join(" ")
+    )
+    .unwrap();
+    for (x, y, point, value) in &points {
+        write!(
+            svg,
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"{}\">\
+             <title>{}: {:.3}</title></circle>\
+             <text x=\"{:.1}\" y=\"{}\" font-size=\"10\" \
+             text-anchor=\"middle\">{}</text>",
+            x,
+            y,
+            if point.flagged { "#c33" } else { "#36c" },
+            point.version,
+            value,
+            x,
+            CHART_HEIGHT - CHART_MARGIN + 14.0,
+            point.version,
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn render_html(histories: &[MapHistory], threshold: f64) -> String {
+    let mut out = String::new();
+    out.push_str(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Regression report</title>\n<style>\
+         body{font-family:sans-serif}\
+         table{border-collapse:collapse}\
+         td,th{border:1px solid #ccc;padding:2px 8px;text-align:right}\
+         tr.flagged{background:#fdd}\
+         </style>\n</head>\n<body>\n<h1>Regression report</h1>\n",
+    );
+    writeln!(
+        out,
+        "<p>Versions whose mean changed by more than {}% from the previous \
+         version are flagged.</p>",
+        threshold
+    )
+    .unwrap();
+    for history in histories {
+        writeln!(
+            out,
+            "<h2>{}</h2>\n<p>sha256 <code>{}</code>, cpu {}, {} ticks, {} \
+             runs</p>",
+            escape_html(&history.map_name),
+            escape_html(&history.sha256),
+            escape_html(&history.cpuid),
+            history.ticks,
+            history.runs
+        )
+        .unwrap();
+        let whole_updates: Vec<Option<f64>> = history
+            .versions
+            .iter()
+            .map(|point| point.mean_whole_update)
+            .collect();
+        let execution_times: Vec<Option<f64>> = history
+            .versions
+            .iter()
+            .map(|point| Some(point.execution_time))
+            .collect();
+        writeln!(
+            out,
+            "{}\n{}",
+            svg_chart(history, "Mean wholeUpdate (ms)", &whole_updates),
+            svg_chart(history, "Execution time (s)", &execution_times)
+        )
+        .unwrap();
+        out.push_str(
+            "<table>\n<tr><th>Version</th><th>wholeUpdate (ms)</th>\
+             <th>Change</th><th>Execution time (s)</th><th>Change</th>\
+             <th>Flag</th></tr>\n",
+        );
+        for point in &history.versions {
+            writeln!(
+                out,
+                "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{:.3}</td>\
+                 <td>{}</td><td>{}</td></tr>",
+                if point.flagged {
+                    " class=\"flagged\""
+                } else {
+                    ""
+                },
+                point.version,
+                point
+                    .mean_whole_update
+                    .map(|mean| format!("{:.3}", mean))
+                    .unwrap_or_default(),
+                format_change(point.whole_update_change),
+                point.execution_time,
+                format_change(point.execution_time_change),
+                flag_label(point),
+            )
+            .unwrap();
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn render_csv(histories: &[MapHistory]) -> String {
+    let mut out = String::from(
+        "map_name,sha256,cpuid,ticks,runs,factorio_version,instances,\
+         mean_whole_update_ms,whole_update_change_percent,execution_time_s,\
+         execution_time_change_percent,flagged\n",
+    );
+    for history in histories {
+        for point in &history.versions {
+            writeln!(
+                out,
+                "{:?},{},{:?},{},{},{},{},{},{},{},{},{}",
+                history.map_name,
+                history.sha256,
+                history.cpuid,
+                history.ticks,
+                history.runs,
+                point.version,
+                point.instances,
+                point
+                    .mean_whole_update
+                    .map(|mean| mean.to_string())
+                    .unwrap_or_default(),
+                point
+                    .whole_update_change
+                    .map(|change| format!("{:.3}", change))
+                    .unwrap_or_default(),
+                point.execution_time,
+                point
+                    .execution_time_change
+                    .map(|change| format!("{:.3}", change))
+                    .unwrap_or_default(),
+                point.flagged,
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(
+        version: FactorioVersion,
+        whole_update: Option<f64>,
+    ) -> VersionPoint {
+        VersionPoint {
+            version,
+            instances: 1,
+            mean_whole_update: whole_update,
+            execution_time: 10.0,
+            whole_update_change: None,
+            execution_time_change: None,
+            flagged: false,
+        }
+    }
+
+    #[test]
+    fn test_flag_changes() {
+        let mut history = MapHistory {
+            map_name: "a.zip".to_owned(),
+            sha256: "aa".to_owned(),
+            cpuid: "cpu".to_owned(),
+            ticks: 100,
+            runs: 10,
+            versions: vec![
+                point(FactorioVersion::new(1, 1, 0), Some(2.2)),
+                point(FactorioVersion::new(1, 0, 0), Some(2.0)),
+                point(FactorioVersion::new(1, 1, 1), Some(2.25)),
+                point(FactorioVersion::new(1, 1, 2), None),
+            ],
+        };
+        history.versions[3].execution_time = 8.0;
+        let mut histories = vec![history];
+        flag_histories(&mut histories, 5.0);
+        let versions = &histories[0].versions;
+        assert_eq!(versions[0].version, FactorioVersion::new(1, 0, 0));
+        assert!(!versions[0].flagged);
+        assert!((versions[1].whole_update_change.unwrap() - 10.0).abs() < 1e-9);
+        assert!(versions[1].flagged);
+        assert!(!versions[2].flagged);
+        assert_eq!(versions[3].whole_update_change, None);
+        assert_eq!(versions[3].execution_time_change, Some(-20.0));
+        assert!(versions[3].flagged);
+        assert_eq!(
+            flagged_versions(&histories[0]),
+            vec!["1.1.0 (slower)", "1.1.2 (faster)"]
+        );
+
+        let csv = render_csv(&histories);
+        assert_eq!(csv.lines().count(), 5);
+        assert!(csv.lines().nth(2).unwrap().starts_with(
+            "\"a.zip\",aa,\"cpu\",100,10,1.1.0,1,2.2,10.000,10,0.000,true"
+        ));
+        let markdown = render_markdown(&histories, 5.0);
+        assert!(markdown
+            .contains("| 1.1.0 | 2.200 | +10.0% | 10.000 | +0.0% | slower |"));
+        assert!(render_html(&histories, 5.0).contains("<tr class=\"flagged\">"));
+    }
+}
@@ -0,0 +1,333 @@
+//! Creating benchmark and meta sets in local.json, and committing them from
+//! local.json to master.json.
+
+use crate::backblaze::upload_files_to_backblaze;
+use crate::benchmark_runner::determine_saved_factorio_version;
+use crate::error::FbhError;
+use crate::performance_results::collection_data::Mod;
+use crate::performance_results::statistics::OutlierPolicy;
+use crate::procedure_file::{
+    get_metas_from_meta, get_sets_from_meta, read_benchmark_set_from_file,
+    read_meta_from_file, write_benchmark_set_to_file, write_meta_to_file,
+    BenchmarkSet, ProcedureFileKind, ProcedureInteractive, ProcedureOverwrite,
+};
+use crate::util::{
+    factorio_save_directory, fbh_save_dl_dir, get_mod_info, hash_saves,
+};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Everything a new benchmark set is built from.
+#[derive(Debug, Clone)]
+pub struct BenchmarkSetOptions {
+    /// The folder holding the maps of the set, each .zip in it is a map.
+    pub folder: PathBuf,
+    pub ticks: u32,
+    pub runs: u32,
+    pub warmup_ticks: u32,
+    pub outlier_policy: OutlierPolicy,
+    /// A comma separated list of mods as `name_version`, or just `name` for
+    /// the latest version. Empty for vanilla.
+    pub mods: String,
+}
+
+/// Builds a benchmark set from the maps in `options.folder`.
+///
+/// The maps are copied into the map cache, hashed, and uploaded to
+/// Backblaze-b2 if credentials are configured, which fills in their download
+/// links. The set is not written anywhere.
+pub fn build_benchmark_set(
+    options: &BenchmarkSetOptions,
+) -> Result<BenchmarkSet, FbhError> {
+    if options.ticks == 0 || options.runs == 0 {
+        return Err(FbhError::InvalidInput(
+            "A benchmark set needs at least 1 tick and 1 run".to_string(),
+        ));
+    }
+    if options.warmup_ticks >= options.ticks {
+        return Err(FbhError::InvalidInput(
+            "--warmup-ticks must be less than --ticks!".to_string(),
+        ));
+    }
+    let (map_paths, save_subdirectory) = get_map_paths(&options.folder)?;
+    if map_paths.is_empty() {
+        return Err(FbhError::InvalidInput(format!(
+            "Supplied folder found no maps! {:?}",
+            options.folder
+        )));
+    }
+    println!("Found the following maps:");
+    for map in map_paths.iter() {
+        println!("{:?}", map);
+    }
+    let mut maps_hashmap = hash_saves(&map_paths);
+
+    let mut benchmark = BenchmarkSet {
+        save_subdirectory,
+        mods: process_mod_list(&options.mods)?,
+        ticks: options.ticks,
+        runs: options.runs,
+        warmup_ticks: options.warmup_ticks,
+        outlier_policy: options.outlier_policy,
+        ..BenchmarkSet::default()
+    };
+
+    let save_subdirectory =
+        benchmark.save_subdirectory.clone().unwrap_or_default();
+    let subdir = save_subdirectory.to_string_lossy().to_string();
+
+    println!("Finding save versions");
+    let mut vers = Vec::new();
+    for path in map_paths.iter() {
+        let single_vers = determine_saved_factorio_version(path);
+        vers.push((path, single_vers));
+    }
+    println!("Finished determining the saved versions of each map.");
+    println!("Attempting upload to Backblaze-b2...");
+
+    match upload_files_to_backblaze(&subdir, &map_paths) {
+        Ok(uploaded_files) => {
+            println!("Finished uploading files");
+            for (filepath, dl_link) in uploaded_files {
+                if let Some(map) = maps_hashmap.get_mut(&filepath) {
+                    map.download_link = dl_link;
+                }
+            }
+        }
+        Err(msg) => {
+            eprintln!("Failed to upload to backblaze");
+            eprintln!("Reason: {}", msg);
+            eprintln!("Continuing without populating the map_dl field...");
+        }
+    };
+
+    for (path, vers) in vers {
+        if let Some(map) = maps_hashmap.get_mut(path) {
+            map.min_compatible_version = vers.unwrap_or_default();
+        }
+    }
+    benchmark.maps = maps_hashmap.values().map(|x| x.to_owned()).collect();
+    Ok(benchmark)
+}
+
+/// Creates a meta set in local.json from a comma separated list of benchmark
+/// and meta set names.
+pub fn create_meta_set(
+    name: &str,
+    members: &str,
+    overwrite: ProcedureOverwrite,
+) -> Result<(), FbhError> {
+    let members = slice_members_from_csv(members);
+    if members.is_empty() {
+        return Err(FbhError::InvalidInput(format!(
+            "No members contained within meta set {:?}!",
+            name
+        )));
+    }
+    write_meta_to_file(name, members, overwrite, ProcedureFileKind::Local)
+}
+
+/// Copies a benchmark set from local.json to master.json.
+pub fn commit_benchmark_set(
+    name: &str,
+    overwrite: ProcedureOverwrite,
+) -> Result<(), FbhError> {
+    let benchmark_set =
+        read_benchmark_set_from_file(name, ProcedureFileKind::Local)
+            .ok_or_else(|| {
+                FbhError::InvalidInput(format!(
+                    "Failed to commit benchmark set {:?} to master, because \
+                    that benchmark set doesn't exist in local!",
+                    name
+                ))
+            })?;
+    write_benchmark_set_to_file(
+        name,
+        benchmark_set,
+        overwrite,
+        ProcedureFileKind::Master,
+        ProcedureInteractive::False,
+    )
+}
+
+/// Copies a meta set from local.json to master.json. With `recursive` every
+/// benchmark and meta set it contains is committed too.
+pub fn commit_meta_set(
+    name: &str,
+    recursive: bool,
+    overwrite: ProcedureOverwrite,
+    interactive: ProcedureInteractive,
+) -> Result<(), FbhError> {
+    let meta_set = read_meta_from_file(name, ProcedureFileKind::Local)
+        .ok_or_else(|| {
+            FbhError::InvalidInput(format!(
+                "Failed to commit meta set {:?} to master, because that meta \
+                set doesn't exist in local!",
+                name
+            ))
+        })?;
+    if recursive {
+        println!("Selected recursive, committing all members of this meta");
+        let meta_sets =
+            get_metas_from_meta(name.to_string(), ProcedureFileKind::Local);
+        let benchmark_sets =
+            get_sets_from_meta(name.to_string(), ProcedureFileKind::Local);
+        for (set_name, set) in benchmark_sets {
+            write_benchmark_set_to_file(
+                &set_name,
+                set,
+                overwrite,
+                ProcedureFileKind::Master,
+                interactive,
+            )?;
+        }
+        for meta in meta_sets {
+            if let Some(members) =
+                read_meta_from_file(&meta, ProcedureFileKind::Local)
+            {
+                write_meta_to_file(
+                    &meta,
+                    members,
+                    overwrite,
+                    ProcedureFileKind::Master,
+                )?;
+            }
+        }
+    }
+    write_meta_to_file(name, meta_set, overwrite, ProcedureFileKind::Master)
+}
+
+fn process_mod_list(raw_mod_list: &str) -> Result<BTreeSet<Mod>, FbhError> {
+    let mut found_mods = BTreeSet::new();
+    for (name, vers) in slice_mods_from_csv(raw_mod_list) {
+        if name == "__CURRENT__" {
+            println!("it's a __CURRENT__! not yet implemented!",);
+        } else {
+            match get_mod_info(&name, &vers) {
+                Some(m) => {
+                    found_mods.insert(m);
+                }
+                None => {
+                    return Err(FbhError::ModPortal(format!(
+                        "Could not download mod {}",
+                        name
+                    )));
+                }
+            }
+        };
+    }
+    Ok(found_mods)
+}
+
+fn slice_mods_from_csv(s: &str) -> Vec<(String, String)> {
+    let mut vals = Vec::new();
+    if s.is_empty() {
+        return vals;
+    }
+    for indiv_mod in s.split(',') {
+        let mut indiv_mod_owned = indiv_mod.to_owned();
+        if indiv_mod_owned.ends_with('_') {
+            indiv_mod_owned.push('_');
+        }
+        let sliced_indiv_mod: Vec<_> = indiv_mod_owned.split('_').collect();
+        if sliced_indiv_mod.len() < 2 {
+            vals.push((sliced_indiv_mod[0].to_string(), "".to_string()));
+        } else {
+            let mod_name =
+                sliced_indiv_mod[0..(sliced_indiv_mod.len() - 1)].join("_");
+            let mod_version =
+                sliced_indiv_mod[sliced_indiv_mod.len() - 1].to_string();
+            vals.push((mod_name, mod_version));
+        }
+    }
+    vals
+}
+
+fn slice_members_from_csv(s: &str) -> BTreeSet<String> {
+    s.split(',')
+        .map(|member| member.trim().to_string())
+        .filter(|member| !member.is_empty())
+        .collect()
+}
+
+/// Gets the paths of all maps within the specified directory. Returns a Vec of
+/// the paths of the found saves, and optionally a common subdirectory.
+fn get_map_paths(
+    dir: &Path,
+) -> Result<(Vec<PathBuf>, Option<PathBuf>), FbhError> {
+    if !dir.is_dir() {
+        return Err(FbhError::InvalidInput(format!(
+            "{:?} is not a folder",
+            dir
+        )));
+    }
+    let mut map_paths = Vec::new();
+    for item in std::fs::read_dir(dir)?.flatten() {
+        if let Some(extension) = item.path().extension() {
+            if let Some("zip") = extension.to_str() {
+                map_paths.push(item.path());
+            }
+        }
+    }
+    let subdir = find_map_subdirectory(dir);
+    move_maps_to_cache(&map_paths, &subdir)?;
+    Ok((map_paths, subdir))
+}
+
+fn find_map_subdirectory(dir: &Path) -> Option<PathBuf> {
+    if let Ok(stripped_path) = dir.strip_prefix(factorio_save_directory()) {
+        Some(stripped_path.to_path_buf())
+    } else {
+        dir.file_name().map(PathBuf::from)
+    }
+}
+
+/// Copy the given maps into the cache directory, nested within a new
+/// subdirectory if provided.
+fn move_maps_to_cache(
+    map_paths: &[PathBuf],
+    subdir: &Option<PathBuf>,
+) -> Result<(), FbhError> {
+    let save_to_dir = if let Some(subdir) = subdir {
+        fbh_save_dl_dir().join(subdir)
+    } else {
+        fbh_save_dl_dir()
+    };
+    std::fs::create_dir_all(&save_to_dir)?;
+    for path in map_paths {
+        let dest_path = save_to_dir.join(path.file_name().unwrap());
+        std::fs::copy(path, &dest_path)?;
+        println!("Copied {:?} to {:?}", &path, &dest_path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice_mods_from_csv() {
+        assert!(slice_mods_from_csv("").is_empty());
+        assert_eq!(
+            slice_mods_from_csv(
+                "rso-mod_6.2.3,Bottleneck,even_distribution_1.0.7"
+            ),
+            vec![
+                ("rso-mod".to_string(), "6.2.3".to_string()),
+                ("Bottleneck".to_string(), "".to_string()),
+                ("even_distribution".to_string(), "1.0.7".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slice_members_from_csv() {
+        let members = slice_members_from_csv(" a, b ,,a");
+        assert_eq!(
+            members.into_iter().collect::<Vec<_>>(),
+            vec!["a".to_string(), "b".to_string()]
+        );
+    }
+}
@@ -5,7 +5,7 @@ extern crate regex;
 extern crate sha1;
 extern crate sha2;
 
-use crate::util::args::MINIFY_SAVES;
+use crate::error::FbhError;
 use crate::util::config_file::CONFIG_FILE_SETTINGS;
 use core::fmt::Debug;
 use core::str::FromStr;
@@ -13,10 +13,12 @@ use std::collections::HashMap;
 use std::io::stdin;
 use std::ops::Range;
 use std::path::Path;
-use std::process::exit;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
 mod fbh_paths;
 pub use fbh_paths::{
-    fbh_cache_path, fbh_mod_dl_dir,
+    fbh_cache_path, fbh_checkpoint_file, fbh_mod_dl_dir,
     fbh_mod_use_dir, fbh_procedure_json_local_file,
     fbh_procedure_json_master_file, fbh_regression_headless_storage,
     fbh_regression_testing_dir, fbh_results_database, fbh_save_dl_dir,
@@ -36,36 +38,58 @@ use directories::BaseDirs;
 use ini::Ini;
 use regex::Regex;
 use std::path::PathBuf;
-mod args;
-pub use args::{add_options_and_parse, UserArgs};
 mod mod_dl;
 pub use mod_dl::{fetch_mod_deps_parallel, get_mod_info};
 mod map_dl;
-pub use map_dl::{fetch_map_deps_parallel, Map};
+pub use map_dl::{
+    fetch_map_deps_parallel, is_whitelisted_download_link,
+    query_map_download_size, Map,
+};
+mod scheduling;
+mod environment;
+mod progress;
+pub use progress::BenchmarkProgress;
+pub mod mirror;
+pub use mirror::MirrorRoot;
+pub use environment::{query_system_environment, SystemEnvironment};
+pub use scheduling::{parse_nice, CpuList, ProcessScheduling};
 
 pub mod common;
 
 
 lazy_static! {
-    #[derive(Debug)]
-    pub static ref FACTORIO_INFO: FactorioInfo = get_factorio_info();
     static ref FACTORIO_EXECUTABLE_VERSION_LINE: Regex
             = Regex::new(r"Version: \d{1,2}\.\d{1,3}\.\d{1,3}.*\n").unwrap();
     // If Factorio ever goes to 3/4/4 digits for their versioning, this will
     // break.
 }
 
-pub fn download_benchmark_deps_parallel(sets: &HashMap<String, BenchmarkSet>) {
+static MINIFY_SAVES: AtomicBool = AtomicBool::new(false);
+static FACTORIO_INFO: OnceLock<FactorioInfo> = OnceLock::new();
+
+/// Downloads the mods and maps of every set, returning the first error any of
+/// the downloads ran into once all of them have finished.
+pub fn download_benchmark_deps_parallel(
+    sets: &HashMap<String, BenchmarkSet>,
+) -> Result<(), FbhError> {
     let mut handles = Vec::new();
     let mut mods = Vec::new();
     let mut maps = Vec::new();
+    let mut first_error = None;
     for set in sets.values() {
         for indiv_mod in set.mods.clone() {
             mods.push(indiv_mod);
         }
         mods.sort();
         mods.dedup();
-        handles.extend(fetch_mod_deps_parallel(&mods));
+        match fetch_mod_deps_parallel(&mods) {
+            Ok(mod_handles) => handles.extend(mod_handles),
+            Err(e) => {
+                // Wait for the downloads already started before giving up
+                first_error = Some(e);
+                break;
+            }
+        }
 
         for indiv_map in set.maps.clone() {
             maps.push(indiv_map)
@@ -78,8 +102,42 @@ pub fn download_benchmark_deps_parallel(sets: &HashMap<String, BenchmarkSet>) {
             set.save_subdirectory.clone(),
         );
     }
+    let mut missing = None;
     for handle in handles {
-        handle.join().expect("");
+        let result = handle.join().unwrap_or_else(|_| {
+            Err(FbhError::Io(std::io::Error::other(
+                "a download thread panicked",
+            )))
+        });
+        match result {
+            Ok(()) => (),
+            // Gather everything missing from the mirror into a single error
+            Err(FbhError::MissingFromMirror { mirror, artifacts }) => {
+                let (_, all_artifacts) =
+                    missing.get_or_insert_with(|| (mirror, Vec::new()));
+                all_artifacts.extend(artifacts);
+            }
+            Err(e) => {
+                if first_error.is_none() {
+                    first_error = Some(e);
+                } else {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+    }
+    let missing = missing.map(|(mirror, mut artifacts)| {
+        artifacts.sort();
+        artifacts.dedup();
+        FbhError::MissingFromMirror { mirror, artifacts }
+    });
+    match (first_error, missing) {
+        (Some(e), Some(missing)) => {
+            eprintln!("{}", missing);
+            Err(e)
+        }
+        (Some(e), None) | (None, Some(e)) => Err(e),
+        (None, None) => Ok(()),
     }
 }
 
@@ -93,11 +151,11 @@ fn generic_read_configuration_setting(
     }
 }
 
-fn factorio_path() -> PathBuf {
+fn factorio_path() -> Result<PathBuf, FbhError> {
     if CONFIG_FILE_SETTINGS.use_steam_version {
         let base_dir = BaseDirs::new().unwrap();
         if cfg!(target_os = "linux") {
-            base_dir
+            Ok(base_dir
                 .home_dir()
                 .join(".local")
                 .join("share")
@@ -105,15 +163,15 @@ fn factorio_path() -> PathBuf {
                 .join("steamapps")
                 .join("common")
                 .join("Factorio")
-                .join("")
+                .join(""))
         } else {
-            PathBuf::from("C:\\")
+            Ok(PathBuf::from("C:\\")
                 .join("Program Files (x86)")
                 .join("Steam")
                 .join("steamapps")
                 .join("common")
                 .join("Factorio")
-                .join("")
+                .join(""))
         }
     } else if CONFIG_FILE_SETTINGS
         .factorio_path
@@ -121,54 +179,58 @@ fn factorio_path() -> PathBuf {
         .map(|x| x.is_dir())
         == Some(true)
     {
-        CONFIG_FILE_SETTINGS
+        Ok(CONFIG_FILE_SETTINGS
             .factorio_path
             .as_ref()
             .unwrap()
-            .to_path_buf()
+            .to_path_buf())
     } else {
-        eprintln!(
+        Err(FbhError::Factorio(
             "Could not resolve path from config file to a valid directory of a Factorio install"
-        );
-        exit(1);
+                .to_string(),
+        ))
     }
 }
 
-pub fn factorio_executable_path() -> PathBuf {
+pub fn factorio_executable_path() -> Result<PathBuf, FbhError> {
     if cfg!(target_os = "linux") {
-        factorio_path().join("bin").join("x64").join("factorio")
+        Ok(factorio_path()?.join("bin").join("x64").join("factorio"))
     } else {
-        factorio_path().join("bin").join("x64").join("factorio.exe")
+        Ok(factorio_path()?.join("bin").join("x64").join("factorio.exe"))
     }
 }
 
 fn factorio_rw_directory() -> PathBuf {
-    let ini_path = factorio_path().join("config-path.cfg");
-    let use_system_rw_directories: bool = generic_read_configuration_setting(
-        ini_path,
-        "use-system-read-write-directories",
-    )
-    .unwrap_or_default()
-    .parse::<bool>()
-    .unwrap_or(true);
-    if use_system_rw_directories {
-        if cfg!(target_os = "linux") {
-            // ~/.factorio/
-            BaseDirs::new()
-                .unwrap()
-                .home_dir()
-                .join(".factorio")
-                .join("")
-        } else {
-            // %appdata%\Roaming\
-            BaseDirs::new()
-                .unwrap()
-                .data_dir()
-                .join("Factorio")
-                .join("")
+    // Without a Factorio install there is no config-path.cfg to read, so the
+    // system read-write directories are used
+    if let Ok(factorio_dir) = factorio_path() {
+        let ini_path = factorio_dir.join("config-path.cfg");
+        let use_system_rw_directories: bool =
+            generic_read_configuration_setting(
+                ini_path,
+                "use-system-read-write-directories",
+            )
+            .unwrap_or_default()
+            .parse::<bool>()
+            .unwrap_or(true);
+        if !use_system_rw_directories {
+            return factorio_dir;
         }
+    }
+    if cfg!(target_os = "linux") {
+        // ~/.factorio/
+        BaseDirs::new()
+            .unwrap()
+            .home_dir()
+            .join(".factorio")
+            .join("")
     } else {
-        factorio_path()
+        // %appdata%\Roaming\
+        BaseDirs::new()
+            .unwrap()
+            .data_dir()
+            .join("Factorio")
+            .join("")
     }
 }
 
@@ -176,25 +238,49 @@ pub fn factorio_save_directory() -> PathBuf {
     factorio_rw_directory().join("saves").join("")
 }
 
-#[derive(Default, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct FactorioInfo {
     pub version: String,
     pub operating_system: String,
     pub platform: String,
 }
 
-fn get_factorio_info() -> FactorioInfo {
-    //Don't call this, use FACTORIO_VERSION instead
-    let line = FACTORIO_EXECUTABLE_VERSION_LINE
-        .captures(&String::from_utf8_lossy(
-            &std::process::Command::new(factorio_executable_path())
-                .arg("--version")
-                .output()
-                .expect("")
-                .stdout,
-        ))
-        .unwrap()[0]
-        .to_string();
+/// Gets the version, operating system and platform of the Factorio from
+/// config.ini, querying it only once.
+pub fn factorio_info() -> Result<&'static FactorioInfo, FbhError> {
+    if let Some(info) = FACTORIO_INFO.get() {
+        return Ok(info);
+    }
+    let info = query_factorio_info(&factorio_executable_path()?)?;
+    Ok(FACTORIO_INFO.get_or_init(|| info))
+}
+
+/// Gets the version, operating system and platform of a Factorio executable
+/// from its `--version` output.
+pub fn query_factorio_info(executable: &Path) -> Result<FactorioInfo, FbhError> {
+    let output = match std::process::Command::new(executable)
+        .arg("--version")
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) => {
+            return Err(FbhError::Factorio(format!(
+                "could not launch {:?}: {}",
+                executable, e
+            )))
+        }
+    };
+    let line = match FACTORIO_EXECUTABLE_VERSION_LINE
+        .captures(&String::from_utf8_lossy(&output.stdout))
+    {
+        Some(captures) => captures[0].to_string(),
+        None => {
+            return Err(FbhError::Factorio(format!(
+                "{:?} did not report its version",
+                executable
+            )))
+        }
+    };
     let split = line.split_whitespace();
 
     let mut info_holder = FactorioInfo::default();
@@ -212,7 +298,7 @@ fn get_factorio_info() -> FactorioInfo {
             _ => (),
         }
     }
-    info_holder
+    Ok(info_holder)
 }
 
 pub fn sha1sum<P: AsRef<Path>>(file_path: &P) -> String {
@@ -373,7 +459,7 @@ fn path_of_7z_install() -> Option<PathBuf> {
 
     if let Some(paths) = std::env::var_os("PATH") {
         for path in std::env::split_paths(&paths) {
-            let full_path = path.join(&exe_name);
+            let full_path = path.join(exe_name);
             if full_path.is_file() {
                 found_path = Some(full_path);
                 break;
@@ -392,8 +478,14 @@ fn path_of_7z_install() -> Option<PathBuf> {
     found_path
 }
 
+/// Deletes the preview image of maps as they are added to a benchmark set,
+/// making the maps smaller to download.
+pub fn set_minify_saves(minify: bool) {
+    MINIFY_SAVES.store(minify, Ordering::SeqCst);
+}
+
 pub fn delete_preview_image_from_save(save: &PathBuf) {
-    if !MINIFY_SAVES.load(std::sync::atomic::Ordering::SeqCst) {
+    if !MINIFY_SAVES.load(Ordering::SeqCst) {
         println!("Skipping preview image deletion, it --minify not found.");
         return;
     }
@@ -404,7 +496,7 @@ pub fn delete_preview_image_from_save(save: &PathBuf) {
                     // Delete the preview image, saving 100-800KB from the few samples I've seen
                     if let Ok(mut process) = std::process::Command::new(&exe_7z)
                         .arg("d")
-                        .arg(&save)
+                        .arg(save)
                         .arg("preview.jpg")
                         .arg("preview.png")
                         .arg("-r")
@@ -455,10 +547,10 @@ pub fn hash_saves(saves: &[PathBuf]) -> HashMap<PathBuf, Map> {
     let erase_preview_image = CONFIG_FILE_SETTINGS.erase_preview_image;
     if path_of_7z_install().is_some() && erase_preview_image {
         for save in saves {
-            delete_preview_image_from_save(&save);
+            delete_preview_image_from_save(save);
         }
     }
-    for (path, sha256) in bulk_sha256(&saves) {
+    for (path, sha256) in bulk_sha256(saves) {
         map_holder.insert(path.clone(), Map::new(&path, &sha256, ""));
     }
     map_holder
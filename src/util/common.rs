@@ -1,3 +1,3 @@
 pub const FACTORIO_BENCHMARK_HELPER_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const FACTORIO_BENCHMARK_HELPER_NAME: &str = env!("CARGO_PKG_NAME");
-pub const CONFIG_FILE_VERSION: u32 = 1;
+pub const CONFIG_FILE_VERSION: u32 = 5;
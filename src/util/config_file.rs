@@ -1,13 +1,24 @@
+use crate::error::FbhError;
 use crate::util::common::CONFIG_FILE_VERSION;
 use crate::util::fbh_paths::fbh_config_file;
+use crate::util::parse_nice;
+use crate::util::CpuList;
+use crate::util::MirrorRoot;
 use ini::Ini;
+use ini::Properties;
+use std::collections::BTreeMap;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::PathBuf;
 
 lazy_static! {
+    /// The settings of config.ini. An invalid config.ini already fails
+    /// `initialize`, so the defaults are only used when it was skipped.
     pub static ref CONFIG_FILE_SETTINGS: ForwardCompatibilityConfigSettings =
-        load_forward_compatiblity_config_settings();
+        load_forward_compatiblity_config_settings().unwrap_or_else(|e| {
+            eprintln!("{}, using the default settings", e);
+            default_config_settings()
+        });
 }
 
 #[derive(Debug, Default)]
@@ -16,21 +27,132 @@ pub struct ForwardCompatibilityConfigSettings {
     pub use_steam_version: bool,
     pub factorio_path: Option<PathBuf>,
     pub erase_preview_image: bool,
+    pub cpu_affinity: Option<CpuList>,
+    pub nice: Option<i32>,
+    pub mirror_root: Option<MirrorRoot>,
+    pub timeout_multiplier: f64,
+    pub regression: RegressionParams,
+    /// The regression test settings of single maps, by map name.
+    pub regression_maps: BTreeMap<String, RegressionParams>,
     pub b2_backblaze_key_id: String,
     pub b2_backblaze_application_key: String,
     pub travis_ci_b2_key_id: String,
     pub travis_ci_b2_applicationkey: String,
 }
 
+/// How many times longer than expected a benchmark may take before Factorio
+/// is killed, unless configured otherwise.
+pub const DEFAULT_TIMEOUT_MULTIPLIER: f64 = 3.0;
+
+/// The prefix of the section holding the regression test settings of a map,
+/// as in `[regression.map_name.zip]`.
+const REGRESSION_MAP_SECTION_PREFIX: &str = "regression.";
+
+/// The ticks and runs of regression tests, either of which may be left to a
+/// less specific setting.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RegressionParams {
+    pub ticks: Option<u32>,
+    pub runs: Option<u32>,
+}
+
+impl RegressionParams {
+    /// Fills in the settings missing from self with those of `fallback`.
+    pub fn or(self, fallback: RegressionParams) -> RegressionParams {
+        RegressionParams {
+            ticks: self.ticks.or(fallback.ticks),
+            runs: self.runs.or(fallback.runs),
+        }
+    }
+}
+
+fn parse_nonzero_property(
+    properties: &Properties,
+    key: &str,
+) -> Result<Option<u32>, String> {
+    match properties.get(key).map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => match value.parse::<u32>() {
+            Ok(n) if n > 0 => Ok(Some(n)),
+            _ => Err(format!(
+                "Invalid {} {:?}, expected a number above 0",
+                key, value
+            )),
+        },
+    }
+}
+
+/// Parses regression-ticks and regression-runs, and the ticks and runs of
+/// every `[regression.map_name.zip]` section.
+fn parse_regression_params(
+    i: &Ini,
+) -> Result<(RegressionParams, BTreeMap<String, RegressionParams>), String> {
+    let mut params = RegressionParams::default();
+    let mut map_params = BTreeMap::new();
+    for (section, properties) in i.iter() {
+        match section {
+            None => {
+                params = RegressionParams {
+                    ticks: parse_nonzero_property(
+                        properties,
+                        "regression-ticks",
+                    )?,
+                    runs: parse_nonzero_property(
+                        properties,
+                        "regression-runs",
+                    )?,
+                }
+            }
+            Some(section) => {
+                if let Some(map_name) =
+                    section.strip_prefix(REGRESSION_MAP_SECTION_PREFIX)
+                {
+                    map_params.insert(
+                        map_name.to_owned(),
+                        RegressionParams {
+                            ticks: parse_nonzero_property(properties, "ticks")?,
+                            runs: parse_nonzero_property(properties, "runs")?,
+                        },
+                    );
+                }
+            }
+        }
+    }
+    Ok((params, map_params))
+}
+
+/// Parses a timeout multiplier, where 0 disables timeouts.
+pub fn parse_timeout_multiplier(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(multiplier) if multiplier == 0.0 || multiplier >= 1.0 => {
+            Ok(multiplier)
+        }
+        _ => Err(format!(
+            "Invalid timeout multiplier {:?}, expected 0 or a number of at \
+            least 1",
+            s
+        )),
+    }
+}
+
+/// The settings used when there is no config.ini.
+fn default_config_settings() -> ForwardCompatibilityConfigSettings {
+    ForwardCompatibilityConfigSettings {
+        use_steam_version: true,
+        timeout_multiplier: DEFAULT_TIMEOUT_MULTIPLIER,
+        ..ForwardCompatibilityConfigSettings::default()
+    }
+}
+
 pub fn load_forward_compatiblity_config_settings(
-) -> ForwardCompatibilityConfigSettings {
-    let mut settings = ForwardCompatibilityConfigSettings::default();
-    settings.use_steam_version = true;
+) -> Result<ForwardCompatibilityConfigSettings, FbhError> {
+    let mut settings = default_config_settings();
     if !fbh_config_file().is_file() {
-        return settings;
+        return Ok(settings);
     }
-    // Safe to unwrap because of early return if file doesn't exist
-    let i = Ini::load_from_file(fbh_config_file()).unwrap();
+    let i = Ini::load_from_file(fbh_config_file()).map_err(|e| {
+        FbhError::InvalidInput(format!("Could not read config.ini: {}", e))
+    })?;
     settings.cfg_file_version = i
         .get_from_or::<&str>(None, "config-file-version", "0")
         .parse::<u32>()
@@ -49,6 +171,67 @@ pub fn load_forward_compatiblity_config_settings(
         .get_from_or::<&str>(None, "erase-preview-image", "true")
         .parse()
         .unwrap_or(true);
+    let cpu_affinity = i.get_from_or::<&str>(None, "cpu-affinity", "");
+    if !cpu_affinity.is_empty() {
+        match cpu_affinity.parse() {
+            Ok(cpus) => settings.cpu_affinity = Some(cpus),
+            Err(e) => {
+                return Err(FbhError::InvalidInput(format!(
+                    "Invalid cpu-affinity in config.ini: {}",
+                    e
+                )));
+            }
+        }
+    }
+    let nice = i.get_from_or::<&str>(None, "nice", "");
+    if !nice.is_empty() {
+        match parse_nice(nice) {
+            Ok(nice) => settings.nice = Some(nice),
+            Err(e) => {
+                return Err(FbhError::InvalidInput(format!(
+                    "Invalid nice in config.ini: {}",
+                    e
+                )));
+            }
+        }
+    }
+    let mirror_root = i.get_from_or::<&str>(None, "mirror-root", "");
+    if !mirror_root.is_empty() {
+        match mirror_root.parse() {
+            Ok(root) => settings.mirror_root = Some(root),
+            Err(e) => {
+                return Err(FbhError::InvalidInput(format!(
+                    "Invalid mirror-root in config.ini: {}",
+                    e
+                )));
+            }
+        }
+    }
+    let timeout_multiplier =
+        i.get_from_or::<&str>(None, "timeout-multiplier", "");
+    if !timeout_multiplier.is_empty() {
+        match parse_timeout_multiplier(timeout_multiplier) {
+            Ok(multiplier) => settings.timeout_multiplier = multiplier,
+            Err(e) => {
+                return Err(FbhError::InvalidInput(format!(
+                    "Invalid timeout-multiplier in config.ini: {}",
+                    e
+                )));
+            }
+        }
+    }
+    match parse_regression_params(&i) {
+        Ok((params, map_params)) => {
+            settings.regression = params;
+            settings.regression_maps = map_params;
+        }
+        Err(e) => {
+            return Err(FbhError::InvalidInput(format!(
+                "Invalid regression setting in config.ini: {}",
+                e
+            )));
+        }
+    }
     settings.b2_backblaze_key_id = i
         .get_from_or::<&str>(None, "b2-backblaze-keyID", "")
         .to_string();
@@ -61,11 +244,11 @@ pub fn load_forward_compatiblity_config_settings(
     settings.travis_ci_b2_applicationkey = i
         .get_from_or::<&str>(None, "TRAVIS_CI_B2_APPLICATIONKEY", "")
         .to_string();
-    settings
+    Ok(settings)
 }
 
-pub fn fbh_write_config_file() -> Result<(), std::io::Error> {
-    let prev_or_default_settings = load_forward_compatiblity_config_settings();
+pub fn fbh_write_config_file() -> Result<(), FbhError> {
+    let prev_or_default_settings = load_forward_compatiblity_config_settings()?;
     if prev_or_default_settings.cfg_file_version != CONFIG_FILE_VERSION {
         if let Ok(mut file) = OpenOptions::new()
             .write(true)
@@ -113,6 +296,100 @@ pub fn fbh_write_config_file() -> Result<(), std::io::Error> {
                 prev_or_default_settings.erase_preview_image
             )?;
             writeln!(file)?;
+            writeln!(
+                file,
+                "; The CPUs to pin Factorio to while benchmarking, e.g. 0-3,6"
+            )?;
+            writeln!(file, "; Empty to not pin Factorio, Linux only")?;
+            writeln!(
+                file,
+                "cpu-affinity={}",
+                prev_or_default_settings
+                    .cpu_affinity
+                    .map(|cpus| cpus.to_string())
+                    .unwrap_or_default()
+            )?;
+            writeln!(file)?;
+            writeln!(
+                file,
+                "; The nice value to run Factorio with, -20 (highest priority) to 19"
+            )?;
+            writeln!(
+                file,
+                "; Empty to keep the current priority, Linux only. Negative values \
+                need root or CAP_SYS_NICE"
+            )?;
+            writeln!(
+                file,
+                "nice={}",
+                prev_or_default_settings
+                    .nice
+                    .map(|nice| nice.to_string())
+                    .unwrap_or_default()
+            )?;
+            writeln!(file)?;
+            writeln!(
+                file,
+                "; A directory or http(s) URL mirroring master.json, megabases.json, saves/ and mods/"
+            )?;
+            writeln!(
+                file,
+                "; Consulted before downloading from the internet, required for --offline"
+            )?;
+            writeln!(
+                file,
+                "mirror-root={}",
+                prev_or_default_settings
+                    .mirror_root
+                    .map(|root| root.to_string())
+                    .unwrap_or_default()
+            )?;
+            writeln!(file)?;
+            writeln!(
+                file,
+                "; Factorio is killed if a benchmark takes this many times longer than expected"
+            )?;
+            writeln!(
+                file,
+                "; The map is recorded as failed and the set continues. 0 to never time out"
+            )?;
+            writeln!(
+                file,
+                "timeout-multiplier={}",
+                prev_or_default_settings.timeout_multiplier
+            )?;
+            writeln!(file)?;
+            writeln!(
+                file,
+                "; The ticks and runs of each map in a --regression-test, unless given with --ticks and --runs"
+            )?;
+            writeln!(
+                file,
+                "; Empty for 100 ticks and 10 runs. Set a single map in a section at the end of the file"
+            )?;
+            writeln!(
+                file,
+                "; such as [regression.map_name.zip] with ticks= and runs="
+            )?;
+            writeln!(
+                file,
+                "regression-ticks={}",
+                prev_or_default_settings
+                    .regression
+                    .ticks
+                    .map(|ticks| ticks.to_string())
+                    .unwrap_or_default()
+            )?;
+            writeln!(
+                file,
+                "regression-runs={}",
+                prev_or_default_settings
+                    .regression
+                    .runs
+                    .map(|runs| runs.to_string())
+                    .unwrap_or_default()
+            )?;
+            writeln!(file)?;
             writeln!(
                 file,
                 "; Backblaze keyID to allow automatic upload of saves to b2 Backblaze"
@@ -146,6 +423,21 @@ pub fn fbh_write_config_file() -> Result<(), std::io::Error> {
                     prev_or_default_settings.travis_ci_b2_applicationkey
                 )?;
             }
+            for (map_name, params) in &prev_or_default_settings.regression_maps
+            {
+                writeln!(file)?;
+                writeln!(
+                    file,
+                    "[{}{}]",
+                    REGRESSION_MAP_SECTION_PREFIX, map_name
+                )?;
+                if let Some(ticks) = params.ticks {
+                    writeln!(file, "ticks={}", ticks)?;
+                }
+                if let Some(runs) = params.runs {
+                    writeln!(file, "runs={}", runs)?;
+                }
+            }
         }
     }
     Ok(())
@@ -0,0 +1,167 @@
+//! The hardware and software environment benchmarks are ran in, recorded
+//! with every collection to help explain differences between results.
+
+use crate::util::common::FACTORIO_BENCHMARK_HELPER_VERSION;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::read_to_string;
+
+/// Anything that could not be determined on this system is left as `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SystemEnvironment {
+    /// The number of hardware threads available.
+    pub logical_cores: Option<u32>,
+    pub physical_cores: Option<u32>,
+    /// The cpufreq scaling governor of the first CPU, such as performance.
+    pub cpu_governor: Option<String>,
+    pub total_memory_kb: Option<u64>,
+    pub kernel_version: Option<String>,
+    pub turbo_enabled: Option<bool>,
+    pub hostname: Option<String>,
+    /// The version of the benchmark helper that ran the collection.
+    pub helper_version: String,
+}
+
+impl SystemEnvironment {
+    /// Lists the fields which differ from `other`, as the field name and both
+    /// values.
+    pub fn differences(
+        &self,
+        other: &SystemEnvironment,
+    ) -> Vec<(&'static str, String, String)> {
+        let fields = |e: &SystemEnvironment| {
+            vec![
+                ("logical cores", display_option(&e.logical_cores)),
+                ("physical cores", display_option(&e.physical_cores)),
+                ("cpu governor", display_option(&e.cpu_governor)),
+                ("total memory kB", display_option(&e.total_memory_kb)),
+                ("kernel version", display_option(&e.kernel_version)),
+                ("turbo enabled", display_option(&e.turbo_enabled)),
+                ("hostname", display_option(&e.hostname)),
+                ("helper version", e.helper_version.clone()),
+            ]
+        };
+        fields(self)
+            .into_iter()
+            .zip(fields(other))
+            .filter(|((_, a), (_, b))| a != b)
+            .map(|((name, a), (_, b))| (name, a, b))
+            .collect()
+    }
+}
+
+fn display_option<T: ToString>(value: &Option<T>) -> String {
+    match value {
+        Some(v) => v.to_string(),
+        None => "unknown".to_owned(),
+    }
+}
+
+/// Queries the environment of this system.
+pub fn query_system_environment() -> SystemEnvironment {
+    SystemEnvironment {
+        logical_cores: std::thread::available_parallelism()
+            .ok()
+            .map(|n| n.get() as u32),
+        physical_cores: read_trimmed("/proc/cpuinfo")
+            .and_then(|cpuinfo| count_physical_cores(&cpuinfo)),
+        cpu_governor: read_trimmed(
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+        ),
+        total_memory_kb: read_trimmed("/proc/meminfo")
+            .and_then(|meminfo| parse_total_memory_kb(&meminfo)),
+        kernel_version: read_trimmed("/proc/sys/kernel/osrelease"),
+        turbo_enabled: query_turbo_enabled(),
+        hostname: read_trimmed("/proc/sys/kernel/hostname")
+            .or_else(|| std::env::var("COMPUTERNAME").ok())
+            .or_else(|| std::env::var("HOSTNAME").ok()),
+        helper_version: FACTORIO_BENCHMARK_HELPER_VERSION.to_owned(),
+    }
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    let contents = read_to_string(path).ok()?;
+    let trimmed = contents.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_owned())
+    }
+}
+
+/// Intel CPUs report turbo through intel_pstate, others through cpufreq
+/// boost.
+fn query_turbo_enabled() -> Option<bool> {
+    if let Some(no_turbo) =
+        read_trimmed("/sys/devices/system/cpu/intel_pstate/no_turbo")
+    {
+        return Some(no_turbo == "0");
+    }
+    read_trimmed("/sys/devices/system/cpu/cpufreq/boost")
+        .map(|boost| boost == "1")
+}
+
+/// Counts the unique physical id and core id pairs of /proc/cpuinfo.
+fn count_physical_cores(cpuinfo: &str) -> Option<u32> {
+    let mut cores = BTreeSet::new();
+    let mut physical_id = None;
+    for line in cpuinfo.lines() {
+        let mut split = line.splitn(2, ':');
+        let key = split.next().unwrap_or("").trim();
+        let value = split.next().unwrap_or("").trim();
+        match key {
+            "physical id" => physical_id = Some(value.to_owned()),
+            "core id" => {
+                cores.insert((physical_id.clone(), value.to_owned()));
+            }
+            _ => (),
+        }
+    }
+    if cores.is_empty() {
+        None
+    } else {
+        Some(cores.len() as u32)
+    }
+}
+
+fn parse_total_memory_kb(meminfo: &str) -> Option<u64> {
+    let line = meminfo.lines().find(|l| l.starts_with("MemTotal:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_files() {
+        let cpuinfo = "processor\t: 0\nphysical id\t: 0\ncore id\t\t: 0\n\n\
+            processor\t: 1\nphysical id\t: 0\ncore id\t\t: 1\n\n\
+            processor\t: 2\nphysical id\t: 0\ncore id\t\t: 0\n\n\
+            processor\t: 3\nphysical id\t: 1\ncore id\t\t: 0\n";
+        assert_eq!(count_physical_cores(cpuinfo), Some(3));
+        assert_eq!(count_physical_cores("processor\t: 0\n"), None);
+
+        let meminfo = "MemTotal:       16314064 kB\nMemFree:  1 kB\n";
+        assert_eq!(parse_total_memory_kb(meminfo), Some(16_314_064));
+        assert_eq!(parse_total_memory_kb(""), None);
+    }
+
+    #[test]
+    fn test_differences() {
+        let a = SystemEnvironment {
+            logical_cores: Some(8),
+            kernel_version: Some("5.10".to_owned()),
+            ..SystemEnvironment::default()
+        };
+        let b = SystemEnvironment {
+            logical_cores: Some(8),
+            ..SystemEnvironment::default()
+        };
+        assert!(a.differences(&a).is_empty());
+        assert_eq!(
+            a.differences(&b),
+            vec![("kernel version", "5.10".to_owned(), "unknown".to_owned())]
+        );
+    }
+}
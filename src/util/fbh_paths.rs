@@ -1,3 +1,4 @@
+use crate::error::FbhError;
 use crate::performance_results::database::setup_database;
 use crate::procedure_file::update_master_json;
 use crate::util::config_file::fbh_write_config_file;
@@ -6,7 +7,7 @@ use simplelog::LevelFilter;
 use std::fs::File;
 use std::path::PathBuf;
 
-pub fn initialize() -> Result<(), std::io::Error> {
+pub fn initialize() -> Result<(), FbhError> {
     if !fbh_data_path().exists() {
         std::fs::create_dir_all(fbh_data_path())?;
     }
@@ -43,9 +44,9 @@ pub fn initialize() -> Result<(), std::io::Error> {
     // Will write config file with forward compatibility if needed
     fbh_write_config_file()?;
     if !fbh_results_database().exists() {
-        setup_database(true, &fbh_results_database());
+        setup_database(true, &fbh_results_database())?;
     }
-    update_master_json();
+    update_master_json()?;
     Ok(())
 }
 
@@ -85,6 +86,11 @@ pub fn fbh_cache_path() -> PathBuf {
     fbh_data_path().join("cache").join("")
 }
 
+/// The file tracking collections which were started but not yet finished.
+pub fn fbh_checkpoint_file() -> PathBuf {
+    fbh_cache_path().join("checkpoint.json")
+}
+
 pub fn fbh_mod_dl_dir() -> PathBuf {
     fbh_cache_path().join("mods").join("")
 }
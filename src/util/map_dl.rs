@@ -1,11 +1,16 @@
 use megabase_index_incrementer::FactorioVersion;
+use crate::error::FbhError;
+use crate::util::mirror::{
+    fetch_from_mirror, is_offline, mirror_file_size, mirror_save_path,
+    missing_from_mirror, query_content_length,
+};
 use crate::util::{factorio_save_directory, fbh_save_dl_dir, sha256sum};
 use serde::{Deserialize, Serialize};
 use std::fs::OpenOptions;
 use std::io::Read;
 use std::io::Write;
+use std::path::Path;
 use std::path::PathBuf;
-use std::process::exit;
 use std::thread::JoinHandle;
 use std::convert::TryInto;
 
@@ -17,6 +22,27 @@ lazy_static! {
     );
 }
 
+/// Whether downloads from the domain of this link are known to work.
+pub fn is_whitelisted_download_link(url: &str) -> bool {
+    WHITELISTED_DOMAINS.iter().any(|domain| url.contains(domain))
+}
+
+/// The size in bytes of a map's download, from the mirror or the server of
+/// its download link, without downloading it. `None` if neither reports it.
+pub fn query_map_download_size(
+    map: &Map,
+    save_subdirectory: Option<&Path>,
+) -> Option<u64> {
+    let mirror_path = mirror_save_path(save_subdirectory, &map.name);
+    if let Some(size) = mirror_file_size(&mirror_path) {
+        return Some(size);
+    }
+    if is_offline() || map.download_link.is_empty() {
+        return None;
+    }
+    query_content_length(&map.download_link)
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Ord, Eq, PartialOrd)]
 pub struct Map {
     pub name: String,
@@ -26,16 +52,28 @@ pub struct Map {
     pub min_compatible_version: FactorioVersion,
     pub sha256: String,
     pub download_link: String,
+    /// Overrides the ticks of the set this map is in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ticks: Option<u32>,
+    /// Overrides the runs of the set this map is in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runs: Option<u32>,
+    /// Overrides the warmup ticks of the set this map is in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warmup_ticks: Option<u32>,
 }
 
 impl Map {
-    pub fn new(path: &PathBuf, sha256: &str, download_link: &str) -> Map {
+    pub fn new(path: &Path, sha256: &str, download_link: &str) -> Map {
         Map {
             name: path.file_name().unwrap().to_string_lossy().to_string(),
             path: path.to_path_buf(),
             sha256: sha256.to_string(),
             min_compatible_version: "0.0.0".try_into().unwrap(),
             download_link: download_link.to_string(),
+            ticks: None,
+            runs: None,
+            warmup_ticks: None,
         }
     }
 }
@@ -51,7 +89,7 @@ impl PartialEq for Map {
 
 pub fn fetch_map_deps_parallel(
     maps: &[Map],
-    handles: &mut Vec<JoinHandle<()>>,
+    handles: &mut Vec<JoinHandle<Result<(), FbhError>>>,
     save_subdirectory: Option<PathBuf>,
 ) {
     let mut unique_maps: Vec<_> = maps.to_vec();
@@ -62,89 +100,91 @@ pub fn fetch_map_deps_parallel(
         handles.push(std::thread::spawn(move ||
             {
                 let mut sha256;
+                let mirror_path =
+                    mirror_save_path(save_subdirectory.as_deref(), &map.name);
                 let (filepath, alt_filepath) =
                 (
-                    fbh_save_dl_dir().join(&save_subdirectory.as_ref().unwrap_or(&PathBuf::new())).join(&map.name),
-                    factorio_save_directory().join(&save_subdirectory.unwrap_or_default()).join(&map.name)
+                    fbh_save_dl_dir().join(save_subdirectory.as_ref().unwrap_or(&PathBuf::new())).join(&map.name),
+                    factorio_save_directory().join(save_subdirectory.unwrap_or_default()).join(&map.name)
                 );
-                if let Some(extension) = filepath.extension() {
-                    if extension == "zip" {
-                        if !filepath.is_file() && alt_filepath.is_file() {
-                            match std::fs::create_dir_all(filepath.parent().unwrap()) {
-                                Ok(_) => (),
-                                Err(e) => {
-                                    eprintln!("Error: We found a map inside the \
-                                        Factorio save directory, but failed to \
-                                        create a folder to copy it."
-                                    );
-                                    eprintln!("Error details: {}", e);
-                                    eprintln!("Source: {:?}, Dest: {:?}", alt_filepath, filepath);
-                                    exit(1);
-                                },
-                            }
-                            match std::fs::copy(&alt_filepath, &filepath) {
-                                Ok(_) => (),
-                                Err(e) => {
-                                    eprintln!("Error: We found a map inside the \
-                                        Factorio save directory, but failed to \
-                                        copy it to the cache folder."
-                                    );
-                                    eprintln!("Error details: {}", e);
-                                    eprintln!("Source: {:?}, Dest: {:?}", alt_filepath, filepath);
-                                    exit(1);
-                                },
-                            }
-                        }
-                        if !filepath.is_file() {
-                            println!("Could not find map in cache or Factorio save directory, doing download.");
-                            download_save(&map.name, map.download_link, &filepath);
-                        } else {
-                            println!("Found an existing map, checking sha256sum... {:?}", &filepath);
-                            sha256 = sha256sum(&filepath);
-                            if sha256 == map.sha256 && map.sha256 != "" {
-                                println!("Found correct sha256sum, skipping download.");
-                            } else {
-                                println!("Found mismatched or empty sha256sum, performing download.");
-                                download_save(&map.name, map.download_link, &filepath);
-                            }
-                        }
-                    } else {
-                        eprintln!("Expected map \"{}\" to have a .zip extension!", &map.name);
-                        exit(1);
+                if filepath.extension() != Some(std::ffi::OsStr::new("zip")) {
+                    return Err(FbhError::InvalidInput(format!(
+                        "Expected map \"{}\" to have a .zip extension!",
+                        &map.name
+                    )));
+                }
+                if !filepath.is_file() && alt_filepath.is_file() {
+                    if let Err(e) = std::fs::create_dir_all(filepath.parent().unwrap()) {
+                        eprintln!("Error: We found a map inside the \
+                            Factorio save directory, but failed to \
+                            create a folder to copy it."
+                        );
+                        eprintln!("Source: {:?}, Dest: {:?}", alt_filepath, filepath);
+                        return Err(e.into());
                     }
+                    if let Err(e) = std::fs::copy(&alt_filepath, &filepath) {
+                        eprintln!("Error: We found a map inside the \
+                            Factorio save directory, but failed to \
+                            copy it to the cache folder."
+                        );
+                        eprintln!("Source: {:?}, Dest: {:?}", alt_filepath, filepath);
+                        return Err(e.into());
+                    }
+                }
+                if !filepath.is_file() {
+                    println!("Could not find map in cache or Factorio save directory, doing download.");
+                    download_save(&map.name, &mirror_path, map.download_link, &filepath)?;
                 } else {
-                    eprintln!("Expected map \"{}\" to have a .zip extension!", &map.name);
-                    exit(1);
+                    println!("Found an existing map, checking sha256sum... {:?}", &filepath);
+                    sha256 = sha256sum(&filepath);
+                    if sha256 == map.sha256 && !map.sha256.is_empty() {
+                        println!("Found correct sha256sum, skipping download.");
+                    } else if map.sha256.is_empty() && is_offline() {
+                        println!("No sha256sum on record, using the existing map while offline.");
+                    } else {
+                        println!("Found mismatched or empty sha256sum, performing download.");
+                        download_save(&map.name, &mirror_path, map.download_link, &filepath)?;
+                    }
                 }
                 if filepath.is_file() {
                     sha256 = sha256sum(&filepath);
-                    if sha256 != map.sha256 {
-                        eprintln!("We downloaded map {} but it doesn't match the sha256sum we have on file?", &map.name);
-                        eprintln!("sha256 in config: {}", map.sha256);
-                        eprintln!("sha256 of downloaded map: {}", sha256);
+                    if sha256 != map.sha256 && !map.sha256.is_empty() {
+                        return Err(FbhError::HashMismatch {
+                            name: map.name,
+                            expected: map.sha256,
+                            found: sha256,
+                        });
                     }
                     println!("Finished downloading map {}", &map.name);
                 }
+                Ok(())
             }
         ));
     }
 }
 
-fn download_save(save_name: &str, url: String, to_save_to_path: &PathBuf) {
+/// Downloads a save from the mirror at `mirror_path` if it has it, otherwise
+/// from `url`.
+fn download_save(
+    save_name: &str,
+    mirror_path: &str,
+    url: String,
+    to_save_to_path: &PathBuf,
+) -> Result<(), FbhError> {
+    if let Some(buf) = fetch_from_mirror(mirror_path)? {
+        println!("Found map {} in the mirror", save_name);
+        return write_save(&buf, to_save_to_path);
+    }
+    if is_offline() {
+        return Err(missing_from_mirror(mirror_path));
+    }
     if url.is_empty() {
-        eprintln!(
+        return Err(FbhError::MapDownload(format!(
             "Could not download map {} because a download link was not defined!",
             save_name
-        );
-        exit(1);
-    }
-    let mut whitelisted_url = false;
-    for domain in WHITELISTED_DOMAINS.iter() {
-        if url.contains(domain) {
-            whitelisted_url = true;
-        }
+        )));
     }
-    if !whitelisted_url {
+    if !is_whitelisted_download_link(&url) {
         println!(
             "Warning, downloads from this domain have not been verified to work.\n{}",
             url
@@ -152,36 +192,41 @@ fn download_save(save_name: &str, url: String, to_save_to_path: &PathBuf) {
     }
     let resp = ureq::get(&url).call();
     if resp.status() == 200 {
-        if to_save_to_path.exists() {
-            match std::fs::remove_file(&to_save_to_path) {
-                Ok(()) => (),
-                Err(e) => {
-                    eprintln!("A failure occured when trying to remove already existing map with mismatched hash");
-                    eprintln!("{}", e);
-                    exit(1);
-                }
-            }
-        }
-        if let Err(e) =
-            std::fs::create_dir_all(&to_save_to_path.parent().unwrap())
-        {
-            eprintln!("Could not create nested subdirectories in the Factorio Benchmark Helper cache directory");
-            eprintln!("{}", e);
-            exit(1);
-        }
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(to_save_to_path)
-            .unwrap();
         let mut buf = Vec::new();
-        resp.into_reader().read_to_end(&mut buf).unwrap();
-        file.write_all(&buf).unwrap();
+        if let Err(e) = resp.into_reader().read_to_end(&mut buf) {
+            return Err(FbhError::MapDownload(format!(
+                "download of map {} was interrupted: {}",
+                save_name, e
+            )));
+        }
+        write_save(&buf, to_save_to_path)
     } else {
-        eprintln!(
-            "Error: We recieved a bad response during a map download. Status code: {}",
+        Err(FbhError::MapDownload(format!(
+            "We recieved a bad response during the download of map {}. Status code: {}",
+            save_name,
             resp.status()
-        );
-        exit(1);
+        )))
+    }
+}
+
+fn write_save(buf: &[u8], to_save_to_path: &PathBuf) -> Result<(), FbhError> {
+    if to_save_to_path.exists() {
+        if let Err(e) = std::fs::remove_file(to_save_to_path) {
+            eprintln!("A failure occured when trying to remove already existing map with mismatched hash");
+            return Err(e.into());
+        }
+    }
+    if let Err(e) =
+        std::fs::create_dir_all(to_save_to_path.parent().unwrap())
+    {
+        eprintln!("Could not create nested subdirectories in the Factorio Benchmark Helper cache directory");
+        return Err(e.into());
     }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(to_save_to_path)?;
+    file.write_all(buf)?;
+    Ok(())
 }
@@ -0,0 +1,191 @@
+//! A local mirror of the files otherwise downloaded from the internet, for
+//! machines without internet access.
+//!
+//! The mirror root is either a directory or the URL of an HTTP server, laid
+//! out as
+//!
+//! ```text
+//! master.json
+//! megabases.json
+//! saves/<save subdirectory>/<map name>.zip
+//! mods/<mod name>_<version>.zip
+//! ```
+//!
+//! Downloads consult the mirror first and fall back to the internet, unless
+//! offline mode is enabled.
+
+use crate::error::FbhError;
+use crate::util::config_file::CONFIG_FILE_SETTINGS;
+use core::str::FromStr;
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref OFFLINE: AtomicBool = AtomicBool::new(false);
+    static ref MIRROR_ROOT_OVERRIDE: Mutex<Option<MirrorRoot>> =
+        Mutex::new(None);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MirrorRoot {
+    Directory(PathBuf),
+    /// A base URL without a trailing slash.
+    Http(String),
+}
+
+impl FromStr for MirrorRoot {
+    type Err = String;
+    fn from_str(s: &str) -> Result<MirrorRoot, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            Err("The mirror root can not be empty".to_owned())
+        } else if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(MirrorRoot::Http(s.trim_end_matches('/').to_owned()))
+        } else {
+            Ok(MirrorRoot::Directory(PathBuf::from(s)))
+        }
+    }
+}
+
+impl fmt::Display for MirrorRoot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MirrorRoot::Directory(path) => write!(f, "{}", path.display()),
+            MirrorRoot::Http(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+/// Stops all downloads from the internet, only the mirror is used.
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::SeqCst);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+}
+
+/// Uses `root` instead of the mirror-root of config.ini.
+pub fn set_mirror_root(root: MirrorRoot) {
+    *MIRROR_ROOT_OVERRIDE.lock().unwrap() = Some(root);
+}
+
+pub fn mirror_root() -> Option<MirrorRoot> {
+    MIRROR_ROOT_OVERRIDE
+        .lock()
+        .unwrap()
+        .clone()
+        .or_else(|| CONFIG_FILE_SETTINGS.mirror_root.clone())
+}
+
+/// The path of a save relative to the mirror root.
+pub fn mirror_save_path(
+    save_subdirectory: Option<&Path>,
+    name: &str,
+) -> String {
+    match save_subdirectory {
+        Some(subdir) => format!(
+            "saves/{}/{}",
+            subdir.to_string_lossy().replace('\\', "/"),
+            name
+        ),
+        None => format!("saves/{}", name),
+    }
+}
+
+/// The path of a mod relative to the mirror root.
+pub fn mirror_mod_path(file_name: &str) -> String {
+    format!("mods/{}", file_name)
+}
+
+/// Reads a file from the mirror by its path relative to the mirror root,
+/// such as `saves/map.zip`. Returns `None` if there is no mirror or the
+/// mirror does not have the file.
+pub fn fetch_from_mirror(
+    relative_path: &str,
+) -> Result<Option<Vec<u8>>, FbhError> {
+    match mirror_root() {
+        None => Ok(None),
+        Some(MirrorRoot::Directory(root)) => {
+            match std::fs::read(root.join(relative_path)) {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        }
+        Some(MirrorRoot::Http(url)) => {
+            let resp = ureq::get(&format!("{}/{}", url, relative_path)).call();
+            if resp.status() == 200 {
+                let mut buf = Vec::new();
+                resp.into_reader().read_to_end(&mut buf)?;
+                Ok(Some(buf))
+            } else {
+                if resp.status() != 404 {
+                    warn!(
+                        "Mirror request for {} failed with status {}",
+                        relative_path,
+                        resp.status()
+                    );
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// The size in bytes of a file in the mirror, without reading it. Returns
+/// `None` if there is no mirror, the mirror does not have the file, or the
+/// mirror does not report its size.
+pub fn mirror_file_size(relative_path: &str) -> Option<u64> {
+    match mirror_root()? {
+        MirrorRoot::Directory(root) => {
+            Some(std::fs::metadata(root.join(relative_path)).ok()?.len())
+        }
+        MirrorRoot::Http(url) => {
+            query_content_length(&format!("{}/{}", url, relative_path))
+        }
+    }
+}
+
+/// The Content-Length a server reports for `url`, if any.
+pub fn query_content_length(url: &str) -> Option<u64> {
+    let resp = ureq::head(url).call();
+    if resp.status() != 200 {
+        return None;
+    }
+    resp.header("Content-Length")?.parse().ok()
+}
+
+/// The error for a file needed in offline mode that the mirror does not
+/// have.
+pub fn missing_from_mirror(relative_path: &str) -> FbhError {
+    FbhError::MissingFromMirror {
+        mirror: mirror_root()
+            .map(|root| root.to_string())
+            .unwrap_or_else(|| "(no mirror-root configured)".to_owned()),
+        artifacts: vec![relative_path.to_owned()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mirror_root() {
+        assert_eq!(
+            "http://mirror.lan:8000/fbh/".parse(),
+            Ok(MirrorRoot::Http("http://mirror.lan:8000/fbh".to_owned()))
+        );
+        assert_eq!(
+            " /srv/fbh ".parse(),
+            Ok(MirrorRoot::Directory(PathBuf::from("/srv/fbh")))
+        );
+        assert!("".parse::<MirrorRoot>().is_err());
+    }
+}
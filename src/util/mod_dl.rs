@@ -1,10 +1,14 @@
+use crate::error::FbhError;
 use crate::performance_results::collection_data::Mod;
+use crate::util::mirror::{
+    fetch_from_mirror, is_offline, mirror_mod_path, mirror_root,
+    missing_from_mirror,
+};
 use crate::util::sha1sum;
 use serde::Deserialize;
 use std::fs::{File, OpenOptions};
 use std::io::Read;
 use std::io::Write;
-use std::process::exit;
 use std::sync::Arc;
 use std::thread::JoinHandle;
 
@@ -45,7 +49,9 @@ impl User {
 }
 
 //TODO make work with same mod but 2 diff versions
-pub fn fetch_mod_deps_parallel(mods: &[Mod]) -> Vec<JoinHandle<()>> {
+pub fn fetch_mod_deps_parallel(
+    mods: &[Mod],
+) -> Result<Vec<JoinHandle<Result<(), FbhError>>>, FbhError> {
     let mut user_data: User = User::default();
     let maybe_playerdata_json_file =
         factorio_rw_directory().join("player-data.json");
@@ -61,7 +67,9 @@ pub fn fetch_mod_deps_parallel(mods: &[Mod]) -> Vec<JoinHandle<()>> {
     unique_mods.sort();
     unique_mods.dedup();
 
-    let mut handles = Vec::new();
+    // Every mod is checked before any download starts, so an error never
+    // leaves downloads running in the background
+    let mut to_download = Vec::new();
 
     let mut filename;
     for m in unique_mods {
@@ -84,10 +92,18 @@ pub fn fetch_mod_deps_parallel(mods: &[Mod]) -> Vec<JoinHandle<()>> {
         } else {
             "".to_string()
         };
-        if computed_sha1 != m.sha1 || computed_sha1 == "" {
-            if !user_data.token.is_empty() && !user_data.username.is_empty() {
+        if computed_sha1 != m.sha1 || computed_sha1.is_empty() {
+            if is_offline() && m.version.is_empty() {
+                return Err(FbhError::InvalidInput(format!(
+                    "Mod {} needs a version to be found while offline",
+                    m.name
+                )));
+            }
+            let has_credentials =
+                !user_data.token.is_empty() && !user_data.username.is_empty();
+            if has_credentials || is_offline() || mirror_root().is_some() {
                 // if the mod isn't found or its hash doesn't match the one we have on file, download it.
-                handles.push(fetch_single_mod(user_data.clone(), filename, m));
+                to_download.push((filename, m));
             } else {
                 eprintln!(
                     "Couldn't read playerdata.json for service-username \
@@ -100,32 +116,60 @@ pub fn fetch_mod_deps_parallel(mods: &[Mod]) -> Vec<JoinHandle<()>> {
                 eprintln!("Presently running a benchmark on the Steam version \
                     causes the playerdata.json file to get overwritten until you \
                     run non-headlessly again.");
-                exit(1);
+                return Err(FbhError::ModPortal(
+                    "no mod portal credentials available".to_string(),
+                ));
             }
         } else {
             println!("Mod already up to date: {}", m.name);
         }
     }
 
-    handles
+    Ok(to_download
+        .into_iter()
+        .map(|(filename, m)| fetch_single_mod(user_data.clone(), filename, m))
+        .collect())
 }
 
 fn fetch_single_mod(
     user_data: Arc<User>,
     filename: String,
     mut m: Mod,
-) -> JoinHandle<()> {
+) -> JoinHandle<Result<(), FbhError>> {
     std::thread::spawn(move || {
         println!("Downloading Mod: {}", filename);
+        if !m.version.is_empty() {
+            let mirror_path = mirror_mod_path(&filename);
+            if let Some(buf) = fetch_from_mirror(&mirror_path)? {
+                std::fs::write(fbh_mod_dl_dir().join(&filename), buf)?;
+                return verify_downloaded_mod(m, &filename);
+            }
+            if is_offline() {
+                return Err(missing_from_mirror(&mirror_path));
+            }
+        }
+        if user_data.token.is_empty() || user_data.username.is_empty() {
+            return Err(FbhError::ModPortal(format!(
+                "{} is not in the mirror and there are no mod portal \
+                credentials to download it",
+                filename
+            )));
+        }
         let mod_url = format!("{}{}", MOD_PORTAL_API_URL, m.name);
         let resp = ureq::get(&mod_url).call();
 
-        let meta_info_response =
-            resp.into_json_deserialize::<ModMetaInfoHolder>().unwrap();
+        let meta_info_response = resp
+            .into_json_deserialize::<ModMetaInfoHolder>()
+            .map_err(|e| {
+                FbhError::ModPortal(format!(
+                    "could not read the release list of {}: {}",
+                    m.name, e
+                ))
+            })?;
 
         if m.version.is_empty() {
             for release in &meta_info_response.releases {
-                m.version = compare_version_str(&release.version, &m.version);
+                m.version = compare_version_str(&release.version, &m.version)?;
             }
         }
         for release in meta_info_response.releases {
@@ -144,98 +188,109 @@ fn fetch_single_mod(
                 if resp.status() == 200 {
                     let fpath = fbh_mod_dl_dir().join(&release.file_name);
                     if fpath.exists() {
-                        match std::fs::remove_file(&fpath) {
-                            Ok(_) => (),
-                            Err(e) => {
-                                eprintln!(
-                                    "Mod exists in local directory but we couldn't remove it!"
-                                );
-                                eprintln!("Reason: {}", e);
-                                exit(1);
-                            }
+                        if let Err(e) = std::fs::remove_file(&fpath) {
+                            eprintln!(
+                                "Mod exists in local directory but we couldn't remove it!"
+                            );
+                            return Err(e.into());
                         }
                     }
                     let mut file = OpenOptions::new()
                         .write(true)
                         .create(true)
-                        .open(fpath)
-                        .unwrap();
+                        .truncate(true)
+                        .open(fpath)?;
                     let mut buf = Vec::new();
                     match resp.into_reader().read_to_end(&mut buf) {
-                        Ok(_) => file.write_all(&buf).unwrap(),
+                        Ok(_) => file.write_all(&buf)?,
                         Err(e) => {
                             println!("Failed to write file to {:?}!", file);
-                            panic!("{}", e);
+                            return Err(FbhError::ModPortal(format!(
+                                "download of {} was interrupted: {}",
+                                release.file_name, e
+                            )));
                         }
                     }
                 } else {
-                    panic!(
-                        "Error: We recieved a bad response from the mod portal. Status code: {}",
+                    return Err(FbhError::ModPortal(format!(
+                        "bad response while downloading {}, status code {}",
+                        release.file_name,
                         resp.status()
-                    );
+                    )));
                 };
-                let newly_dl_mod_sha1 =
-                    sha1sum(&fbh_mod_dl_dir().join(&release.file_name));
-                if m.sha1 == "" {
-                    m.sha1 = newly_dl_mod_sha1.clone();
-                }
-                if newly_dl_mod_sha1 != m.sha1 {
-                    eprintln!(
-                        "Recently downloaded mod {} hash mismatch!",
-                        m.name
-                    );
-                    eprintln!("sha1 in config: {}", m.sha1);
-                    eprintln!("sha1 of downloaded mod: {}", newly_dl_mod_sha1);
-                }
-                println!("Finished Downloading Mod: {}", &release.file_name);
-                break;
+                return verify_downloaded_mod(m, &release.file_name);
             }
         }
+        Ok(())
     })
 }
 
-fn compare_version_str(vers1: &str, vers2: &str) -> String {
+/// Checks a mod just downloaded to the mod cache against the hash on record,
+/// if there is one.
+fn verify_downloaded_mod(m: Mod, file_name: &str) -> Result<(), FbhError> {
+    let newly_dl_mod_sha1 = sha1sum(&fbh_mod_dl_dir().join(file_name));
+    if !m.sha1.is_empty() && newly_dl_mod_sha1 != m.sha1 {
+        return Err(FbhError::HashMismatch {
+            name: m.name,
+            expected: m.sha1,
+            found: newly_dl_mod_sha1,
+        });
+    }
+    println!("Finished Downloading Mod: {}", file_name);
+    Ok(())
+}
+
+fn compare_version_str(vers1: &str, vers2: &str) -> Result<String, FbhError> {
     //Compare versions as vectors of u32 because 0.0.9 > 0.0.35 in String compare.
-    let vers_cmp1 = convert_version_str_to_vec(&vers1);
-    let vers_cmp2 = convert_version_str_to_vec(&vers2);
+    let vers_cmp1 = convert_version_str_to_vec(vers1)?;
+    let vers_cmp2 = convert_version_str_to_vec(vers2)?;
     if vers_cmp1 > vers_cmp2 {
-        format!("{}.{}.{}", vers_cmp1[0], vers_cmp1[1], vers_cmp1[2])
+        Ok(format!(
+            "{}.{}.{}",
+            vers_cmp1[0], vers_cmp1[1], vers_cmp1[2]
+        ))
     } else {
-        format!("{}.{}.{}", vers_cmp2[0], vers_cmp2[1], vers_cmp2[2])
+        Ok(format!(
+            "{}.{}.{}",
+            vers_cmp2[0], vers_cmp2[1], vers_cmp2[2]
+        ))
     }
 }
 
-fn convert_version_str_to_vec(version: &str) -> Vec<u32> {
+fn convert_version_str_to_vec(version: &str) -> Result<Vec<u32>, FbhError> {
     let mut vers = Vec::new();
     if !version.is_empty() {
         for u in version.split('.') {
             if let Ok(u) = u.parse::<u32>() {
                 vers.push(u);
             } else {
-                eprintln!(
-                    "Error: Could not parse version string {} as a valid version!",
+                return Err(FbhError::InvalidInput(format!(
+                    "Could not parse version string {} as a valid version!",
                     version
-                );
-                exit(1);
+                )));
             }
         }
     }
     if vers.len() > 3 {
-        eprintln!("Error: Mod versions can have at most 3 sections!",);
-        exit(1);
+        return Err(FbhError::InvalidInput(format!(
+            "Mod version {} has more than 3 sections!",
+            version
+        )));
     }
     if vers.is_empty() {
         vers = vec![0, 0, 0];
     }
-    vers
+    Ok(vers)
 }
 
-fn get_latest_mod_version(meta_info: ModMetaInfoHolder) -> String {
+fn get_latest_mod_version(
+    meta_info: ModMetaInfoHolder,
+) -> Result<String, FbhError> {
     let mut latest = "0.0.0".to_string();
     for release in &meta_info.releases {
-        latest = compare_version_str(&release.version, &latest);
+        latest = compare_version_str(&release.version, &latest)?;
     }
-    latest
+    Ok(latest)
 }
 
 pub fn get_mod_info(mod_name: &str, mod_version: &str) -> Option<Mod> {
@@ -249,7 +304,8 @@ pub fn get_mod_info(mod_name: &str, mod_version: &str) -> Option<Mod> {
         {
             if mod_version.is_empty() {
                 println!("Getting latest version...");
-                mod_v = get_latest_mod_version(meta_info_response.clone());
+                mod_v =
+                    get_latest_mod_version(meta_info_response.clone()).ok()?;
             }
             for release in meta_info_response.releases {
                 if release.version == mod_v {
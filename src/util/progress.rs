@@ -0,0 +1,266 @@
+//! Progress of the benchmark runs of a set, drawn to stderr from Factorio's
+//! stdout as it is printed.
+
+use crate::benchmark_runner::VERBOSE_DATA_ROW_MATCH_PATTERN;
+use crate::benchmark_runner::VERBOSE_RUN_MARKER_REGEX;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::time::Duration;
+use std::time::Instant;
+
+const BAR_WIDTH: usize = 20;
+/// How often the progress line is redrawn on a terminal.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The progress of every benchmark of a set, and of the map currently being
+/// benchmarked.
+#[derive(Debug)]
+pub struct BenchmarkProgress {
+    total_ticks: u64,
+    completed_ticks: u64,
+    started: Instant,
+    last_drawn: Option<Instant>,
+    /// Redraws a single line on a terminal, otherwise prints a line per run.
+    interactive: bool,
+    map: Option<MapProgress>,
+}
+
+#[derive(Debug)]
+struct MapProgress {
+    name: String,
+    ticks: u32,
+    runs: u32,
+    run: u32,
+    ticks_done: u64,
+    run_ticks: u32,
+    /// The sum of wholeUpdate of the current run, in nanoseconds.
+    run_whole_update: f64,
+}
+
+impl MapProgress {
+    fn total_ticks(&self) -> u64 {
+        u64::from(self.ticks) * u64::from(self.runs)
+    }
+
+    /// The mean wholeUpdate of the current run in milliseconds.
+    fn run_mean_ms(&self) -> Option<f64> {
+        if self.run_ticks == 0 {
+            None
+        } else {
+            Some(self.run_whole_update / f64::from(self.run_ticks) / 1e6)
+        }
+    }
+}
+
+impl BenchmarkProgress {
+    /// Tracks the progress of benchmarks adding up to `total_ticks`.
+    pub fn new(total_ticks: u64) -> BenchmarkProgress {
+        BenchmarkProgress {
+            total_ticks,
+            completed_ticks: 0,
+            started: Instant::now(),
+            last_drawn: None,
+            interactive: std::io::stderr().is_terminal(),
+            map: None,
+        }
+    }
+
+    /// Adds ticks to the total, for runs decided on after starting.
+    pub fn add_ticks(&mut self, ticks: u64) {
+        self.total_ticks += ticks;
+    }
+
+    pub fn start_map(&mut self, name: &str, ticks: u32, runs: u32) {
+        self.map = Some(MapProgress {
+            name: name.to_owned(),
+            ticks,
+            runs,
+            run: 0,
+            ticks_done: 0,
+            run_ticks: 0,
+            run_whole_update: 0.0,
+        });
+        self.draw(true);
+    }
+
+    /// Updates the progress from a line of Factorio's stdout, using the run
+    /// markers and verbose tick rows.
+    pub fn observe_line(&mut self, line: &str) {
+        let map = match &mut self.map {
+            Some(map) => map,
+            None => return,
+        };
+        if let Some(run) = parse_run_marker(line) {
+            if map.run > 0 && !self.interactive {
+                self.print_line();
+            }
+            let map = self.map.as_mut().unwrap();
+            map.run = run;
+            map.run_ticks = 0;
+            map.run_whole_update = 0.0;
+        } else if let Some(whole_update) = parse_tick_row(line) {
+            map.ticks_done += 1;
+            map.run_ticks += 1;
+            map.run_whole_update += whole_update;
+            self.draw(false);
+        }
+    }
+
+    pub fn finish_map(&mut self) {
+        if let Some(map) = &self.map {
+            if !self.interactive && map.run > 0 {
+                self.print_line();
+            }
+            self.completed_ticks += map.total_ticks();
+        }
+        self.map = None;
+    }
+
+    /// Ends the progress line, so that other output starts on a new line.
+    pub fn finish(&mut self) {
+        if self.interactive && self.last_drawn.is_some() {
+            eprintln!();
+        }
+        self.last_drawn = None;
+    }
+
+    fn draw(&mut self, force: bool) {
+        if !self.interactive {
+            return;
+        }
+        let now = Instant::now();
+        if let Some(last_drawn) = self.last_drawn {
+            if !force && now - last_drawn < REDRAW_INTERVAL {
+                return;
+            }
+        }
+        self.last_drawn = Some(now);
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r\x1b[K{}", self.status_line());
+        let _ = stderr.flush();
+    }
+
+    fn print_line(&self) {
+        eprintln!("{}", self.status_line());
+    }
+
+    fn done_ticks(&self) -> u64 {
+        self.completed_ticks
+            + self.map.as_ref().map(|map| map.ticks_done).unwrap_or(0)
+    }
+
+    /// The estimated time left, from the rate of ticks done so far, which
+    /// includes the time spent loading maps.
+    fn eta(&self) -> Option<Duration> {
+        let done = self.done_ticks();
+        if done == 0 {
+            return None;
+        }
+        let left = self.total_ticks.saturating_sub(done);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        Some(Duration::from_secs_f64(elapsed / done as f64 * left as f64))
+    }
+
+    fn status_line(&self) -> String {
+        let mut line = String::new();
+        if let Some(map) = &self.map {
+            line.push_str(&format!(
+                "{} run {}/{} {}",
+                map.name,
+                map.run.max(1),
+                map.runs,
+                progress_bar(map.ticks_done, map.total_ticks())
+            ));
+            if let Some(mean) = map.run_mean_ms() {
+                line.push_str(&format!(" {:.3} ms/tick", mean));
+            }
+            line.push_str(" | ");
+        }
+        line.push_str(&format!(
+            "overall {}",
+            progress_bar(self.done_ticks(), self.total_ticks)
+        ));
+        if let Some(eta) = self.eta() {
+            let secs = eta.as_secs();
+            line.push_str(&format!(
+                " ETA {}:{:02}:{:02}",
+                secs / 3600,
+                secs % 3600 / 60,
+                secs % 60
+            ));
+        }
+        line
+    }
+}
+
+fn progress_bar(done: u64, total: u64) -> String {
+    let fraction = if total == 0 {
+        0.0
+    } else {
+        (done as f64 / total as f64).min(1.0)
+    };
+    let filled = (fraction * BAR_WIDTH as f64).round() as usize;
+    format!(
+        "[{}{}] {:3.0}%",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        fraction * 100.0
+    )
+}
+
+/// Parses the run number from a `run N:` marker line.
+fn parse_run_marker(line: &str) -> Option<u32> {
+    VERBOSE_RUN_MARKER_REGEX.captures(line)?[1]
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Parses the wholeUpdate in nanoseconds from a verbose row such as
+/// `t12,1500000,...`.
+fn parse_tick_row(line: &str) -> Option<f64> {
+    if !VERBOSE_DATA_ROW_MATCH_PATTERN.is_match(line) {
+        return None;
+    }
+    line.split(',').nth(1)?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        let mut progress = BenchmarkProgress::new(8);
+        progress.interactive = false;
+        progress.start_map("map.zip", 2, 2);
+        for line in &["Loading map", "run 1:", "t0,1000000,5", "t1,3000000,5"] {
+            progress.observe_line(line);
+        }
+        let map = progress.map.as_ref().unwrap();
+        assert_eq!((map.run, map.ticks_done), (1, 2));
+        assert_eq!(map.run_mean_ms(), Some(2.0));
+        assert!(progress.status_line().starts_with(
+            "map.zip run 1/2 [##########----------]  50% 2.000 ms/tick | \
+            overall [#####---------------]  25% ETA"
+        ));
+
+        progress.observe_line("run 2:");
+        assert_eq!(progress.map.as_ref().unwrap().run_mean_ms(), None);
+        progress.observe_line("t0,1000000,5");
+        progress.observe_line("t1,1000000,5");
+        progress.finish_map();
+        assert_eq!(progress.done_ticks(), 4);
+        assert!(progress
+            .status_line()
+            .starts_with("overall [##########----------]  50% ETA"));
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        assert_eq!(parse_run_marker("run 12:"), Some(12));
+        assert_eq!(parse_run_marker("run x:"), None);
+        assert_eq!(parse_tick_row("t5,2500,0,0"), Some(2500.0));
+        assert_eq!(parse_tick_row("tick,wholeUpdate"), None);
+    }
+}
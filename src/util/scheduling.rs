@@ -0,0 +1,187 @@
+//! CPU affinity and priority of the Factorio processes ran for benchmarks.
+
+use crate::error::FbhError;
+use core::str::FromStr;
+use std::fmt;
+use std::process::Command;
+
+/// The highest CPU number that can be pinned to, exclusive.
+#[cfg(target_os = "linux")]
+const MAX_CPUS: usize = libc::CPU_SETSIZE as usize;
+#[cfg(not(target_os = "linux"))]
+const MAX_CPUS: usize = 1024;
+
+/// A set of CPUs, written as a comma separated list of CPU numbers and
+/// inclusive ranges such as `0-3,6`, the same as taskset and cpusets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuList(Vec<usize>);
+
+impl CpuList {
+    pub fn cpus(&self) -> &[usize] {
+        &self.0
+    }
+}
+
+impl FromStr for CpuList {
+    type Err = String;
+    fn from_str(s: &str) -> Result<CpuList, Self::Err> {
+        let invalid =
+            || format!("Invalid CPU list {:?}, expected e.g. 0-3,6", s);
+        let mut cpus = Vec::new();
+        for part in s.split(',').map(str::trim) {
+            let (first, last) = match part.find('-') {
+                Some(i) => (&part[..i], &part[i + 1..]),
+                None => (part, part),
+            };
+            let first = first.trim().parse::<usize>().map_err(|_| invalid())?;
+            let last = last.trim().parse::<usize>().map_err(|_| invalid())?;
+            if first > last {
+                return Err(invalid());
+            }
+            if last >= MAX_CPUS {
+                return Err(format!(
+                    "CPU {} is out of range, the highest allowed CPU is {}",
+                    last,
+                    MAX_CPUS - 1
+                ));
+            }
+            cpus.extend(first..=last);
+        }
+        cpus.sort_unstable();
+        cpus.dedup();
+        Ok(CpuList(cpus))
+    }
+}
+
+impl fmt::Display for CpuList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for &cpu in &self.0 {
+            match ranges.last_mut() {
+                Some((_, last)) if *last + 1 == cpu => *last = cpu,
+                _ => ranges.push((cpu, cpu)),
+            }
+        }
+        let ranges: Vec<String> = ranges
+            .iter()
+            .map(|(first, last)| {
+                if first == last {
+                    first.to_string()
+                } else {
+                    format!("{}-{}", first, last)
+                }
+            })
+            .collect();
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+/// Parses a nice value, -20 (highest priority) to 19 (lowest priority).
+pub fn parse_nice(s: &str) -> Result<i32, String> {
+    match s.trim().parse::<i32>() {
+        Ok(nice) if (-20..=19).contains(&nice) => Ok(nice),
+        _ => Err(format!(
+            "Invalid nice value {:?}, expected a number from -20 to 19",
+            s
+        )),
+    }
+}
+
+/// How the Factorio processes of a benchmark are scheduled. Anything not set
+/// is inherited from the benchmark helper.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessScheduling {
+    /// The CPUs Factorio is pinned to.
+    pub cpu_affinity: Option<CpuList>,
+    /// The nice value Factorio runs with. Negative values usually need root
+    /// or CAP_SYS_NICE.
+    pub nice: Option<i32>,
+}
+
+impl ProcessScheduling {
+    pub fn is_inherited(&self) -> bool {
+        self.cpu_affinity.is_none() && self.nice.is_none()
+    }
+
+    /// Makes the command's process run with this scheduling once spawned.
+    /// If it cannot be applied the spawn fails.
+    #[cfg(target_os = "linux")]
+    pub fn apply(&self, command: &mut Command) -> Result<(), FbhError> {
+        use std::os::unix::process::CommandExt;
+        if self.is_inherited() {
+            return Ok(());
+        }
+        let cpus = self.cpu_affinity.clone();
+        let nice = self.nice;
+        // Only async-signal-safe libc calls are made between fork and exec.
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(cpus) = &cpus {
+                    let mut set: libc::cpu_set_t = std::mem::zeroed();
+                    for cpu in cpus.cpus() {
+                        libc::CPU_SET(*cpu, &mut set);
+                    }
+                    let size = std::mem::size_of::<libc::cpu_set_t>();
+                    if libc::sched_setaffinity(0, size, &set) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                if let Some(nice) = nice {
+                    if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply(&self, _command: &mut Command) -> Result<(), FbhError> {
+        if self.is_inherited() {
+            return Ok(());
+        }
+        Err(FbhError::InvalidInput(
+            "CPU affinity and nice are only supported on Linux".to_owned(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_list() {
+        let cpus: CpuList = "6, 0-3,2".parse().unwrap();
+        assert_eq!(cpus.cpus(), &[0, 1, 2, 3, 6]);
+        assert_eq!(cpus.to_string(), "0-3,6");
+        assert_eq!("5".parse::<CpuList>().unwrap().to_string(), "5");
+        assert!("".parse::<CpuList>().is_err());
+        assert!("3-1".parse::<CpuList>().is_err());
+        assert!("a".parse::<CpuList>().is_err());
+        assert!(format!("0-{}", MAX_CPUS).parse::<CpuList>().is_err());
+
+        assert_eq!(parse_nice("-5"), Ok(-5));
+        assert!(parse_nice("20").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_apply_scheduling() {
+        let scheduling = ProcessScheduling {
+            cpu_affinity: Some("0".parse().unwrap()),
+            nice: Some(19),
+        };
+        let mut command = Command::new("cat");
+        command.arg("/proc/self/stat");
+        scheduling.apply(&mut command).unwrap();
+        let output = command.output().unwrap();
+        let stat = String::from_utf8_lossy(&output.stdout).to_string();
+        // The nice value is the 19th field, counted after the command name.
+        let fields: Vec<&str> =
+            stat[stat.rfind(')').unwrap() + 2..].split(' ').collect();
+        assert_eq!(fields[16], "19");
+    }
+}